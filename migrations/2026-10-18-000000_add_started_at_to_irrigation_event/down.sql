@@ -0,0 +1 @@
+ALTER TABLE "irrigation_event" DROP COLUMN "started_at";
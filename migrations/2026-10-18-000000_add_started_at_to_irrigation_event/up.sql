@@ -0,0 +1 @@
+ALTER TABLE "irrigation_event" ADD COLUMN "started_at" DATETIME;
@@ -0,0 +1 @@
+DROP TABLE "pool_pump_schedule";
@@ -0,0 +1,13 @@
+CREATE TABLE "pool_pump_schedule" (
+  "id" INTEGER PRIMARY KEY NOT NULL,
+  "active" BOOLEAN NOT NULL,
+  "name" TEXT NOT NULL,
+  "speed" TEXT NOT NULL,
+  "start_time" TIME NOT NULL,
+  "end_time" TIME NOT NULL,
+  "days_of_week" TEXT NOT NULL,
+  "created_at" DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  "updated_at" DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_pool_pump_schedule_on_start_time ON "pool_pump_schedule" ("start_time");
@@ -0,0 +1,5 @@
+CREATE TABLE "equipment_state" (
+  "device" TEXT PRIMARY KEY NOT NULL,
+  "state" TEXT NOT NULL,
+  "updated_at" DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
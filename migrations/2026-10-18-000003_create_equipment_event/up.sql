@@ -0,0 +1,9 @@
+CREATE TABLE "equipment_event"
+(
+  id INTEGER PRIMARY KEY NOT NULL,
+  device TEXT NOT NULL,
+  state TEXT NOT NULL,
+  created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_equipment_event_on_device_created_at ON equipment_event (device, created_at);
@@ -1,24 +1,55 @@
 use actix_cors::Cors;
 use actix_identity::IdentityMiddleware;
 use actix_session::{storage::CookieSessionStore, SessionMiddleware};
-use actix_web::{cookie, dev::Server, http, web, web::Data, App, HttpServer};
+use actix_web::{
+    cookie,
+    dev::Server,
+    http, web,
+    web::{Data, ServiceConfig},
+    App, HttpServer,
+};
 use actix_web::{error::ErrorBadRequest, web::JsonConfig};
 use actix_web_opentelemetry::RequestTracing;
+use anyhow::{anyhow, Error};
 use lazy_static::lazy_static;
 use serde_json::json;
-use std::net::TcpListener;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener};
 use tokio::runtime::Runtime;
 use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Duration, MissedTickBehavior};
 
-use crate::config::Settings;
+use crate::config::{Settings, WatchdogConfig};
 use crate::controllers::{
-    auth::auth_routes, heater::heater, info::info, irrigation::irrigation_routes,
-    pool_pump::pool_pump, sump_event::sump_event,
+    auth::auth_routes,
+    hardware::hardware_selftest,
+    health::health,
+    heater::heater,
+    info::info,
+    irrigation::irrigation_routes,
+    outputs::{outputs, switch_output},
+    pool::pool_usage,
+    pool_pump::{cancel_pool_pump_priming, pool_pump, resume_pool_pump_schedule},
+    pool_pump_schedule::pool_pump_schedule_routes,
+    sump_event::sump_event,
 };
 
-use crate::hydro::{gpio::Gpio, Hydro};
+#[cfg(feature = "stub")]
+use crate::controllers::sim::sim_routes;
+#[cfg(feature = "stub")]
+use crate::hydro::gpio::sim::SimGpio;
+use crate::hydro::{
+    adc::Adc,
+    gpio::Gpio,
+    watchdog::{self, Health, Heartbeat, Watchdog, WatchdogTask},
+    Hydro,
+};
 use crate::repository::Repo;
 
+/// Seconds in-flight requests get to finish on SIGINT or SIGTERM. The outputs stay as they
+/// are until the server stops, so this is kept well short of actix-web's default of 30.
+const SHUTDOWN_TIMEOUT_SEC: u64 = 5;
+
 lazy_static! {
     static ref HYDRO_RT: Runtime = Runtime::new().expect("Failed to initialize runtime");
 }
@@ -27,19 +58,66 @@ pub struct Application {
     port: u16,
     pub repo: Repo,
     server: Server,
+    hydro: Data<Mutex<Hydro>>,
+    /// Where the server can be reached from this host
+    local_address: SocketAddr,
+    health: Health,
+    probe: Option<JoinHandle<()>>,
+    watchdog: Option<WatchdogTask>,
 }
 
 impl Application {
-    pub fn build(settings: Settings, gpio: &dyn Gpio, repo: Repo) -> Application {
+    pub fn build(
+        settings: Settings,
+        gpio: &dyn Gpio,
+        adc: Option<Box<dyn Adc>>,
+        repo: Repo,
+    ) -> Result<Application, Error> {
+        Self::build_with_routes(settings, gpio, adc, repo, |_| {})
+    }
+
+    /// Runs on the simulated GPIO with no ADC, with the `/sim` routes for driving its inputs and
+    /// inspecting its outputs.
+    #[cfg(feature = "stub")]
+    pub fn build_simulated(
+        settings: Settings,
+        gpio: SimGpio,
+        repo: Repo,
+    ) -> Result<Application, Error> {
+        let sim_data = Data::new(gpio.clone());
+
+        Self::build_with_routes(settings, &gpio, None, repo, move |cfg| {
+            cfg.app_data(sim_data.clone())
+                .service(web::scope("/sim").configure(sim_routes));
+        })
+    }
+
+    /// Builds the application, serving `extra_routes` alongside the API. Fails without
+    /// touching the equipment when the pin settings are invalid.
+    fn build_with_routes<F>(
+        settings: Settings,
+        gpio: &dyn Gpio,
+        adc: Option<Box<dyn Adc>>,
+        repo: Repo,
+        extra_routes: F,
+    ) -> Result<Application, Error>
+    where
+        F: Fn(&mut ServiceConfig) + Clone + Send + 'static,
+    {
+        settings.validate_pins()?;
+
         // Web server configuration
         let (_address, port, tcp_listener) = web_server_config(&settings);
+        let local_address = local_address(&tcp_listener);
 
         let handle = HYDRO_RT.handle();
 
-        let hydro = Hydro::new(&settings.hydro, handle.clone(), gpio, repo)
-            .expect("Could not create hydro object");
+        let hydro = Hydro::new(&settings.hydro, handle.clone(), gpio, adc, repo)
+            .map_err(|e| anyhow!("Could not create hydro object: {}", e))?;
+        let hydro_health = hydro.health.clone();
 
         let hydro_data = Data::new(Mutex::new(hydro));
+        let hydro = hydro_data.clone();
         let repo_data = Data::new(repo);
         let settings_data = Data::new(settings.clone());
 
@@ -73,12 +151,21 @@ impl Application {
                     cookie::Key::generate(),
                 ))
                 // HTTP API Routes
+                .service(hardware_selftest)
+                .service(health)
                 .service(heater)
                 .service(info)
+                .service(outputs)
+                .service(switch_output)
+                .service(pool_usage)
                 .service(pool_pump)
+                .service(cancel_pool_pump_priming)
+                .service(resume_pool_pump_schedule)
+                .configure(pool_pump_schedule_routes)
                 .service(sump_event)
                 .service(web::scope("/auth").configure(auth_routes))
                 .service(web::scope("/irrigation").configure(irrigation_routes))
+                .configure(extra_routes.clone())
                 // Application configuration
                 .app_data(JsonConfig::default().error_handler(|err, _req| {
                     ErrorBadRequest(json!({
@@ -89,20 +176,104 @@ impl Application {
                 .app_data(repo_data.clone())
                 .app_data(hydro_data.clone())
         })
+        .shutdown_timeout(SHUTDOWN_TIMEOUT_SEC)
         .listen(tcp_listener)
         .unwrap_or_else(|_| panic!("Could not listen on port {}", port))
         .run();
 
-        Application { server, port, repo }
+        Ok(Application {
+            server,
+            port,
+            repo,
+            hydro,
+            local_address,
+            health: hydro_health,
+            probe: None,
+            watchdog: None,
+        })
     }
 
     pub fn port(&self) -> u16 {
         self.port
     }
 
+    /// Pets `watchdog` while the signal loop, the scheduler and this server, probed on
+    /// `/health`, keep making progress. It is disarmed once the equipment is shut down.
+    pub fn watch(&mut self, watchdog: Box<dyn Watchdog>, config: &WatchdogConfig) {
+        let pet_interval = Duration::from_secs(config.pet_sec);
+
+        self.probe = Some(probe_http(
+            format!("http://{}/health", self.local_address),
+            self.health.register("http"),
+            pet_interval,
+        ));
+        self.watchdog = Some(watchdog::start(
+            watchdog,
+            self.health.clone(),
+            pet_interval,
+            Duration::from_secs(config.stall_sec),
+        ));
+    }
+
+    /// Serves until actix-web receives SIGINT or SIGTERM, then shuts the equipment down.
     pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
-        self.server.await
+        let result = self.server.await;
+        if let Some(probe) = self.probe {
+            probe.abort();
+        }
+        self.hydro.lock().await.shutdown().await;
+        // Only once the equipment is safe, so a hung shutdown still reboots the board
+        if let Some(watchdog) = self.watchdog {
+            watchdog.stop().await;
+        }
+
+        result
+    }
+}
+
+/// Beats `heartbeat` each time the server answers `url`, trying every `period`.
+fn probe_http(url: String, heartbeat: Heartbeat, period: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let client = reqwest::Client::builder()
+            .timeout(period)
+            .build()
+            .expect("Could not build the health probe client");
+        let mut ticks = interval(period);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticks.tick().await;
+
+            match client.get(&url).send().await {
+                Ok(response) if response.status().is_success() => heartbeat.beat(),
+                Ok(response) => tracing::warn!(
+                    target = module_path!(),
+                    status = response.status().as_u16(),
+                    "Health probe failed"
+                ),
+                Err(e) => tracing::warn!(
+                    target = module_path!(),
+                    error = e.to_string(),
+                    "Health probe failed"
+                ),
+            }
+        }
+    })
+}
+
+/// The listener's address, with loopback in place of a wildcard host.
+fn local_address(listener: &TcpListener) -> SocketAddr {
+    let mut address = listener
+        .local_addr()
+        .expect("Could not get server address.");
+    if address.ip().is_unspecified() {
+        address.set_ip(match address.ip() {
+            IpAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+            IpAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+        });
     }
+
+    address
 }
 
 fn web_server_config(settings: &Settings) -> (String, u16, TcpListener) {
@@ -1,16 +1,38 @@
+use anyhow::{anyhow, Error};
 use dotenv::dotenv;
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::env;
+use std::str::FromStr;
+
+use crate::hydro::{
+    adc::AdcBackend, control::Polarity, energy::Tariff, gpio::GpioBackend, outputs::OutputSchedule,
+    pool_pump::PoolPumpSpeed, state::RestorePolicy,
+};
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct Settings {
+    pub adc: Option<AdcConfig>,
     pub console: ConsoleConfig,
     pub database_path: String,
+    pub gpio: GpioConfig,
     pub hydro: HydroConfig,
     pub jwt_secret: String,
     pub mailer: MailerConfig,
     pub server: ServerConfig,
     pub telemetry: TelemetryConfig,
+    pub watchdog: Option<WatchdogConfig>,
+}
+
+/// An analog-to-digital converter for level sensors, set up when `ADC_BACKEND` is.
+#[derive(Clone, Debug, Deserialize)]
+pub struct AdcConfig {
+    pub backend: AdcBackend,
+    /// The `N` of `/dev/spidevN.M`
+    pub spi_bus: u8,
+    /// The `M` of `/dev/spidevN.M`
+    pub chip_select: u8,
+    pub clock_hz: u32,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -18,17 +40,108 @@ pub struct ConsoleConfig {
     pub report_freq_secs: u64,
 }
 
+/// An MCP23017 port expander, set up when `GPIO_EXPANDER_ADDRESS` is.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ExpanderConfig {
+    /// The `N` of `/dev/i2c-N`
+    pub i2c_bus: u8,
+    pub address: u8,
+    /// Pin numbers from here up are the expander's, 0 to 15
+    pub pin_base: u8,
+    /// Milliseconds between reads of the expander's inputs
+    pub poll_ms: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GpioConfig {
+    pub backend: GpioBackend,
+    /// The GPIO chip the cdev backend requests lines from; pin numbers are its line offsets
+    pub chip_path: String,
+    pub expander: Option<ExpanderConfig>,
+    /// Reserved Raspberry Pi pins whose I2C or UART function is turned off, so they can be used
+    pub unreserved_pins: Vec<u8>,
+    /// Records every input level change to this file, for replaying later
+    pub record_path: Option<String>,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct HeaterConfig {
     pub control_pin: u8,
+    pub polarity: Polarity,
+    /// The heater won't run with the pool pump any slower than this
+    pub min_pump_speed: PoolPumpSpeed,
+    /// Seconds the pool pump keeps running after the heater is switched off
+    pub cool_down_sec: u64,
+    /// Seconds between thermostat checks of the water temperature
+    pub thermostat_frequency_sec: u64,
+    /// Whether the heater comes back on after a restart
+    pub restore: RestorePolicy,
+    /// Power drawn while heating, used for energy accounting
+    pub watts: Option<f64>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct HydroConfig {
     pub irrigation: IrrigationConfig,
     pub heater: HeaterConfig,
+    pub outputs: OutputsConfig,
     pub pool_pump: PoolPumpConfig,
     pub sump: SumpConfig,
+    pub temperature: TemperatureConfig,
+    /// Electricity prices, used to estimate what the heater and pool pump cost to run
+    pub tariff: Option<Tariff>,
+}
+
+impl HydroConfig {
+    /// Every pin in the config, with the variable that sets it.
+    pub fn pins(&self) -> Vec<(String, u8)> {
+        let irrigation = &self.irrigation;
+        let pool_pump = &self.pool_pump;
+        let sump = &self.sump;
+
+        let mut pins = vec![
+            ("HEATER_CONTROL_PIN".to_string(), self.heater.control_pin),
+            (
+                "IRRIGATION_LOW_SENSOR_PIN".to_string(),
+                irrigation.low_sensor_pin,
+            ),
+            (
+                "IRRIGATION_PUMP_CONTROL_PIN".to_string(),
+                irrigation.pump_control_pin,
+            ),
+            (
+                "IRRIGATION_VALVE_1_CONTROL_PIN".to_string(),
+                irrigation.valve_1_control_pin,
+            ),
+            (
+                "IRRIGATION_VALVE_2_CONTROL_PIN".to_string(),
+                irrigation.valve_2_control_pin,
+            ),
+            (
+                "IRRIGATION_VALVE_3_CONTROL_PIN".to_string(),
+                irrigation.valve_3_control_pin,
+            ),
+            (
+                "IRRIGATION_VALVE_4_CONTROL_PIN".to_string(),
+                irrigation.valve_4_control_pin,
+            ),
+            ("POOL_PUMP_LOW_PIN".to_string(), pool_pump.low_pin),
+            ("POOL_PUMP_MED_PIN".to_string(), pool_pump.med_pin),
+            ("POOL_PUMP_HIGH_PIN".to_string(), pool_pump.high_pin),
+            ("POOL_PUMP_MAX_PIN".to_string(), pool_pump.max_pin),
+            ("SUMP_HIGH_SENSOR_PIN".to_string(), sump.high_sensor_pin),
+            ("SUMP_LOW_SENSOR_PIN".to_string(), sump.low_sensor_pin),
+            ("SUMP_CONTROL_PIN".to_string(), sump.pump_control_pin),
+        ];
+        pins.extend(self.outputs.outputs.iter().map(|output| {
+            (
+                format!("OUTPUT_{}_PIN", output.name.to_uppercase()),
+                output.pin,
+            )
+        }));
+
+        pins
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -38,10 +151,51 @@ pub struct IrrigationConfig {
     pub max_seconds_runtime: u8,
     pub process_frequency_sec: u64,
     pub pump_control_pin: u8,
+    pub pump_polarity: Polarity,
     pub valve_1_control_pin: u8,
+    pub valve_1_polarity: Polarity,
     pub valve_2_control_pin: u8,
+    pub valve_2_polarity: Polarity,
     pub valve_3_control_pin: u8,
+    pub valve_3_polarity: Polarity,
     pub valve_4_control_pin: u8,
+    pub valve_4_polarity: Polarity,
+    /// Flow rates in litres per minute for each valve, used for water usage accounting
+    pub valve_1_flow_rate: Option<f64>,
+    pub valve_2_flow_rate: Option<f64>,
+    pub valve_3_flow_rate: Option<f64>,
+    pub valve_4_flow_rate: Option<f64>,
+    /// Only irrigate with the water the sump is estimated to have put in the reservoir
+    pub water_budget: bool,
+    /// Size of the reservoir in litres; water pumped in beyond this overflows
+    pub reservoir_capacity: Option<f64>,
+    /// Hours of pump history the water budget is estimated from; older water is taken to be
+    /// used up or lost
+    pub refill_window_hours: u64,
+    /// A continuous reading of the reservoir's level, alongside the low sensor
+    pub level: Option<LevelSensorConfig>,
+}
+
+impl IrrigationConfig {
+    /// Flow rate in litres per minute of the valve for `hose_id`, if one is configured.
+    pub fn flow_rate(&self, hose_id: i32) -> Option<f64> {
+        match hose_id {
+            1 => self.valve_1_flow_rate,
+            2 => self.valve_2_flow_rate,
+            3 => self.valve_3_flow_rate,
+            4 => self.valve_4_flow_rate,
+            _ => None,
+        }
+    }
+}
+
+/// A pressure or ultrasonic level sensor on an ADC channel. `empty` and `full` are the
+/// readings at either end, whichever way round the sensor reads.
+#[derive(Clone, Debug, Deserialize)]
+pub struct LevelSensorConfig {
+    pub channel: u8,
+    pub empty: u16,
+    pub full: u16,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -51,12 +205,76 @@ pub struct MailerConfig {
     pub server_url: String,
 }
 
+/// A spare relay, named in `OUTPUTS`; its settings are read from `OUTPUT_<NAME>_*`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct OutputConfig {
+    pub name: String,
+    pub pin: u8,
+    pub polarity: Polarity,
+    /// Seconds the output may stay on before it is switched off again
+    pub max_on_sec: Option<u64>,
+    /// Switches the output on and off each day
+    pub schedule: Option<OutputSchedule>,
+    /// Whether the output comes back on after a restart
+    pub restore: RestorePolicy,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct OutputsConfig {
+    pub outputs: Vec<OutputConfig>,
+    /// Seconds between checks of the output schedules
+    pub process_frequency_sec: u64,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct PoolPumpConfig {
     pub low_pin: u8,
     pub med_pin: u8,
     pub high_pin: u8,
     pub max_pin: u8,
+    pub low_polarity: Polarity,
+    pub med_polarity: Polarity,
+    pub high_polarity: Polarity,
+    pub max_polarity: Polarity,
+    /// Seconds between checks of the pool pump programs
+    pub process_frequency_sec: u64,
+    /// Degrees Celsius below which freeze protection runs the pump; off when unset
+    pub freeze_temperature: Option<f64>,
+    /// The speed freeze protection runs the pump at
+    pub freeze_speed: PoolPumpSpeed,
+    /// The speed the pump primes at when started from off
+    pub priming_speed: PoolPumpSpeed,
+    /// Seconds to prime for; 0, the default, skips priming
+    pub priming_sec: u64,
+    /// Whether the pump returns to its last speed and override after a restart
+    pub restore: RestorePolicy,
+    /// Power drawn at each speed, used for energy accounting
+    pub low_watts: Option<f64>,
+    pub med_watts: Option<f64>,
+    pub high_watts: Option<f64>,
+    pub max_watts: Option<f64>,
+}
+
+impl PoolPumpConfig {
+    /// Power drawn at `speed`, if configured.
+    pub fn watts(&self, speed: PoolPumpSpeed) -> Option<f64> {
+        match speed {
+            PoolPumpSpeed::Off => Some(0.0),
+            PoolPumpSpeed::Low => self.low_watts,
+            PoolPumpSpeed::Med => self.med_watts,
+            PoolPumpSpeed::High => self.high_watts,
+            PoolPumpSpeed::Max => self.max_watts,
+        }
+    }
+}
+
+/// Levels in percent of full that switch the sump pump, in place of the float switches.
+#[derive(Clone, Debug, Deserialize)]
+pub struct PumpThresholds {
+    pub on_percent: f64,
+    pub off_percent: f64,
+    /// Milliseconds between readings of the level
+    pub poll_ms: u64,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -75,8 +293,15 @@ pub struct SumpConfig {
     pub high_sensor_pin: u8,
     pub low_sensor_pin: u8,
     pub pump_control_pin: u8,
+    pub pump_polarity: Polarity,
     pub pump_shutoff_delay: u64,
     pub pump_max_runtime: u64,
+    /// Flow rate of the sump pump in litres per minute, used for water usage accounting
+    pub pump_flow_rate: Option<f64>,
+    /// A continuous reading of the sump's level
+    pub level: Option<LevelSensorConfig>,
+    /// Switch the pump on the level reading instead of the high and low sensors
+    pub pump_thresholds: Option<PumpThresholds>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -85,7 +310,71 @@ pub struct TelemetryConfig {
     pub receiver_url: String,
 }
 
+#[derive(Clone, Debug, Deserialize)]
+pub struct TemperatureConfig {
+    /// Where the kernel exposes 1-Wire devices
+    pub w1_devices_path: String,
+    /// The 1-Wire id of the pool sensor; the first DS18B20 found is used when unset
+    pub sensor_id: Option<String>,
+    /// The 1-Wire id of an outdoor air sensor, used for freeze protection when set
+    pub air_sensor_id: Option<String>,
+}
+
+/// The hardware watchdog, petted while the app makes progress when `WATCHDOG_PATH` is set.
+#[derive(Clone, Debug, Deserialize)]
+pub struct WatchdogConfig {
+    /// The watchdog device, usually `/dev/watchdog`
+    pub path: String,
+    /// Seconds between pets; must be well under the device's timeout
+    pub pet_sec: u64,
+    /// Seconds a task can go without a heartbeat before the watchdog is left to reboot the board
+    pub stall_sec: u64,
+}
+
+/// Raspberry Pi pins outside this range don't exist, by BCM number.
+const BOARD_PINS: u8 = 28;
+
+/// Raspberry Pi pins with a special function, which has to be turned off to use them.
+const RESERVED_PINS: [(u8, &str); 6] = [
+    (0, "the HAT ID EEPROM (I2C0 SDA)"),
+    (1, "the HAT ID EEPROM (I2C0 SCL)"),
+    (2, "I2C1 SDA"),
+    (3, "I2C1 SCL"),
+    (14, "UART TX"),
+    (15, "UART RX"),
+];
+
+/// SPI0's pins on a Raspberry Pi, taken by an ADC on `/dev/spidev0.*`; CE1 only when the ADC is
+/// on chip select 1.
+const SPI0_PINS: [(u8, &str); 5] = [
+    (7, "SPI0 CE1"),
+    (8, "SPI0 CE0"),
+    (9, "SPI0 MISO"),
+    (10, "SPI0 MOSI"),
+    (11, "SPI0 SCLK"),
+];
+
+/// Pins on an MCP23017 expander.
+const EXPANDER_PINS: u16 = 16;
+
 impl Settings {
+    /// Checks the pins before any are claimed, reporting every problem at once.
+    pub fn validate_pins(&self) -> Result<(), Error> {
+        let problems = pin_problems(&self.gpio, self.adc.as_ref(), &self.hydro.pins());
+        if problems.is_empty() {
+            return Ok(());
+        }
+
+        Err(anyhow!(
+            "Invalid pin configuration:\n{}",
+            problems
+                .iter()
+                .map(|problem| format!("  - {}", problem))
+                .collect::<Vec<String>>()
+                .join("\n")
+        ))
+    }
+
     pub fn new() -> Self {
         set_application_environment();
 
@@ -97,6 +386,7 @@ impl Settings {
             .expect("SERVER_PORT must be a 16-bit unsigned integer.");
 
         Settings {
+            adc: Self::adc_config(),
             console: ConsoleConfig {
                 report_freq_secs: env::var("CONSOLE_REPORT_FREQ_SECS")
                     .unwrap_or_else(|_| "5".to_string())
@@ -104,13 +394,42 @@ impl Settings {
                     .expect("CONSOLE_REPORT_FREQ_SECS must be a number."),
             },
             database_path,
+            gpio: GpioConfig {
+                backend: load_optional_system_var("GPIO_BACKEND").unwrap_or(GpioBackend::Rppal),
+                chip_path: load_optional_system_var("GPIO_CHIP_PATH")
+                    .unwrap_or_else(|| "/dev/gpiochip0".into()),
+                expander: Self::expander_config(),
+                unreserved_pins: load_optional_system_var::<String>("GPIO_UNRESERVED_PINS")
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|pin| !pin.is_empty())
+                    .map(|pin| {
+                        pin.parse()
+                            .expect("GPIO_UNRESERVED_PINS must be a list of pin numbers.")
+                    })
+                    .collect(),
+                record_path: load_optional_system_var("GPIO_RECORD_PATH"),
+            },
             hydro: HydroConfig {
                 irrigation: Self::irrigation_config().expect("Could not load irrigation config."),
                 heater: HeaterConfig {
                     control_pin: load_system_var("HEATER_CONTROL_PIN")
                         .parse()
                         .expect("HEATER_CONTROL_PIN must be a number."),
+                    polarity: load_optional_system_var("HEATER_POLARITY").unwrap_or_default(),
+                    min_pump_speed: load_optional_system_var("HEATER_MIN_PUMP_SPEED")
+                        .unwrap_or(PoolPumpSpeed::Low),
+                    cool_down_sec: load_optional_system_var("HEATER_COOL_DOWN_SEC").unwrap_or(300),
+                    thermostat_frequency_sec: load_optional_system_var(
+                        "HEATER_THERMOSTAT_FREQ_SEC",
+                    )
+                    .unwrap_or(60),
+                    restore: load_optional_system_var("HEATER_RESTORE")
+                        .unwrap_or(RestorePolicy::Off),
+                    watts: load_optional_system_var("HEATER_WATTS"),
                 },
+                outputs: Self::outputs_config(),
                 pool_pump: PoolPumpConfig {
                     low_pin: load_system_var("POOL_PUMP_LOW_PIN")
                         .parse()
@@ -124,8 +443,33 @@ impl Settings {
                     max_pin: load_system_var("POOL_PUMP_MAX_PIN")
                         .parse()
                         .expect("POOL_PUMP_MAX_PIN must be a number."),
+                    low_polarity: load_polarity("POOL_PUMP_LOW_POLARITY", "POOL_PUMP_POLARITY"),
+                    med_polarity: load_polarity("POOL_PUMP_MED_POLARITY", "POOL_PUMP_POLARITY"),
+                    high_polarity: load_polarity("POOL_PUMP_HIGH_POLARITY", "POOL_PUMP_POLARITY"),
+                    max_polarity: load_polarity("POOL_PUMP_MAX_POLARITY", "POOL_PUMP_POLARITY"),
+                    process_frequency_sec: load_optional_system_var("POOL_PUMP_PROCESS_FREQ_SEC")
+                        .unwrap_or(60),
+                    freeze_temperature: load_optional_system_var("POOL_PUMP_FREEZE_TEMPERATURE"),
+                    freeze_speed: load_optional_system_var("POOL_PUMP_FREEZE_SPEED")
+                        .unwrap_or(PoolPumpSpeed::Low),
+                    priming_speed: load_optional_system_var("POOL_PUMP_PRIMING_SPEED")
+                        .unwrap_or(PoolPumpSpeed::Max),
+                    priming_sec: load_optional_system_var("POOL_PUMP_PRIMING_SEC").unwrap_or(0),
+                    restore: load_optional_system_var("POOL_PUMP_RESTORE")
+                        .unwrap_or(RestorePolicy::Restore),
+                    low_watts: load_optional_system_var("POOL_PUMP_LOW_WATTS"),
+                    med_watts: load_optional_system_var("POOL_PUMP_MED_WATTS"),
+                    high_watts: load_optional_system_var("POOL_PUMP_HIGH_WATTS"),
+                    max_watts: load_optional_system_var("POOL_PUMP_MAX_WATTS"),
                 },
                 sump: Self::sump_config().expect("Could not load sump config."),
+                temperature: TemperatureConfig {
+                    w1_devices_path: load_optional_system_var("TEMPERATURE_W1_DEVICES_PATH")
+                        .unwrap_or_else(|| "/sys/bus/w1/devices".into()),
+                    sensor_id: load_optional_system_var("TEMPERATURE_SENSOR_ID"),
+                    air_sensor_id: load_optional_system_var("TEMPERATURE_AIR_SENSOR_ID"),
+                },
+                tariff: load_optional_system_var("ELECTRICITY_TARIFF"),
             },
             jwt_secret,
             mailer: MailerConfig {
@@ -153,6 +497,118 @@ impl Settings {
                 api_key: load_system_var("TELEMETRY_API_KEY"),
                 receiver_url: load_system_var("TELEMETRY_RECEIVER_URL"),
             },
+            watchdog: Self::watchdog_config(),
+        }
+    }
+
+    fn adc_config() -> Option<AdcConfig> {
+        Some(AdcConfig {
+            backend: load_optional_system_var("ADC_BACKEND")?,
+            spi_bus: load_optional_system_var("ADC_SPI_BUS").unwrap_or(0),
+            chip_select: load_optional_system_var("ADC_SPI_CHIP_SELECT").unwrap_or(0),
+            clock_hz: load_optional_system_var("ADC_SPI_CLOCK_HZ").unwrap_or(1_000_000),
+        })
+    }
+
+    /// The level sensor whose variables start with `prefix`, set up when its channel is.
+    fn level_sensor_config(prefix: &str) -> Option<LevelSensorConfig> {
+        let channel = load_optional_system_var(&format!("{}_CHANNEL", prefix))?;
+        let reading = |end: &str| {
+            load_system_var(&format!("{}_{}", prefix, end))
+                .parse()
+                .unwrap_or_else(|_| panic!("{}_{} must be an ADC reading.", prefix, end))
+        };
+        let (empty, full) = (reading("EMPTY"), reading("FULL"));
+
+        if empty == full {
+            panic!("{}_EMPTY and {}_FULL must differ.", prefix, prefix);
+        }
+
+        Some(LevelSensorConfig {
+            channel,
+            empty,
+            full,
+        })
+    }
+
+    fn pump_thresholds_config(level: Option<&LevelSensorConfig>) -> Option<PumpThresholds> {
+        let on_percent: f64 = load_optional_system_var("SUMP_PUMP_ON_PERCENT")?;
+        let off_percent: f64 = load_optional_system_var("SUMP_PUMP_OFF_PERCENT")
+            .expect("SUMP_PUMP_OFF_PERCENT must be set with SUMP_PUMP_ON_PERCENT.");
+
+        if level.is_none() {
+            panic!("SUMP_PUMP_ON_PERCENT needs a sump level sensor; set SUMP_LEVEL_CHANNEL.");
+        }
+        if !(0.0..=100.0).contains(&off_percent)
+            || !(0.0..=100.0).contains(&on_percent)
+            || off_percent >= on_percent
+        {
+            panic!("SUMP_PUMP_OFF_PERCENT must be below SUMP_PUMP_ON_PERCENT, both from 0 to 100.");
+        }
+
+        Some(PumpThresholds {
+            on_percent,
+            off_percent,
+            poll_ms: load_optional_system_var("SUMP_LEVEL_POLL_MS").unwrap_or(1000),
+        })
+    }
+
+    fn expander_config() -> Option<ExpanderConfig> {
+        let address: String = load_optional_system_var("GPIO_EXPANDER_ADDRESS")?;
+        let address = u8::from_str_radix(address.trim_start_matches("0x"), 16)
+            .expect("GPIO_EXPANDER_ADDRESS must be a hex address, such as 0x20.");
+
+        Some(ExpanderConfig {
+            i2c_bus: load_optional_system_var("GPIO_EXPANDER_I2C_BUS").unwrap_or(1),
+            address,
+            pin_base: load_optional_system_var("GPIO_EXPANDER_PIN_BASE").unwrap_or(100),
+            poll_ms: load_optional_system_var("GPIO_EXPANDER_POLL_MS").unwrap_or(50),
+        })
+    }
+
+    fn watchdog_config() -> Option<WatchdogConfig> {
+        Some(WatchdogConfig {
+            path: load_optional_system_var("WATCHDOG_PATH")?,
+            pet_sec: load_optional_system_var("WATCHDOG_PET_SEC").unwrap_or(5),
+            stall_sec: load_optional_system_var("WATCHDOG_STALL_SEC").unwrap_or(30),
+        })
+    }
+
+    fn outputs_config() -> OutputsConfig {
+        let names: String = load_optional_system_var("OUTPUTS").unwrap_or_default();
+
+        let outputs = names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                if !name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+                {
+                    panic!("OUTPUTS names may only use lowercase letters, digits and underscores.");
+                }
+
+                let prefix = format!("OUTPUT_{}", name.to_uppercase());
+                OutputConfig {
+                    name: name.into(),
+                    pin: load_system_var(&format!("{}_PIN", prefix))
+                        .parse()
+                        .unwrap_or_else(|_| panic!("{}_PIN must be a number.", prefix)),
+                    polarity: load_optional_system_var(&format!("{}_POLARITY", prefix))
+                        .unwrap_or_default(),
+                    max_on_sec: load_optional_system_var(&format!("{}_MAX_ON_SEC", prefix)),
+                    schedule: load_optional_system_var(&format!("{}_SCHEDULE", prefix)),
+                    restore: load_optional_system_var(&format!("{}_RESTORE", prefix))
+                        .unwrap_or(RestorePolicy::Restore),
+                }
+            })
+            .collect();
+
+        OutputsConfig {
+            outputs,
+            process_frequency_sec: load_optional_system_var("OUTPUTS_PROCESS_FREQ_SEC")
+                .unwrap_or(60),
         }
     }
 
@@ -173,18 +629,32 @@ impl Settings {
         let pump_control_pin: u8 = load_system_var("IRRIGATION_PUMP_CONTROL_PIN")
             .parse()
             .expect("IRRIGATION_PUMP_CONTROL_PIN must be a number.");
+        let pump_polarity = load_polarity("IRRIGATION_PUMP_POLARITY", "IRRIGATION_POLARITY");
         let valve_1_control_pin: u8 = load_system_var("IRRIGATION_VALVE_1_CONTROL_PIN")
             .parse()
             .expect("IRRIGATION_VALVE_1_CONTROL_PIN must be a number.");
+        let valve_1_polarity = load_polarity("IRRIGATION_VALVE_1_POLARITY", "IRRIGATION_POLARITY");
         let valve_2_control_pin: u8 = load_system_var("IRRIGATION_VALVE_2_CONTROL_PIN")
             .parse()
             .expect("IRRIGATION_VALVE_2_CONTROL_PIN must be a number.");
+        let valve_2_polarity = load_polarity("IRRIGATION_VALVE_2_POLARITY", "IRRIGATION_POLARITY");
         let valve_3_control_pin: u8 = load_system_var("IRRIGATION_VALVE_3_CONTROL_PIN")
             .parse()
             .expect("IRRIGATION_VALVE_3_CONTROL_PIN must be a number.");
+        let valve_3_polarity = load_polarity("IRRIGATION_VALVE_3_POLARITY", "IRRIGATION_POLARITY");
         let valve_4_control_pin: u8 = load_system_var("IRRIGATION_VALVE_4_CONTROL_PIN")
             .parse()
             .expect("IRRIGATION_VALVE_4_CONTROL_PIN must be a number.");
+        let valve_4_polarity = load_polarity("IRRIGATION_VALVE_4_POLARITY", "IRRIGATION_POLARITY");
+        let valve_1_flow_rate = load_optional_system_var("IRRIGATION_VALVE_1_FLOW_RATE");
+        let valve_2_flow_rate = load_optional_system_var("IRRIGATION_VALVE_2_FLOW_RATE");
+        let valve_3_flow_rate = load_optional_system_var("IRRIGATION_VALVE_3_FLOW_RATE");
+        let valve_4_flow_rate = load_optional_system_var("IRRIGATION_VALVE_4_FLOW_RATE");
+        let water_budget = load_optional_system_var("IRRIGATION_WATER_BUDGET").unwrap_or(false);
+        let reservoir_capacity = load_optional_system_var("IRRIGATION_RESERVOIR_CAPACITY");
+        let refill_window_hours =
+            load_optional_system_var("IRRIGATION_REFILL_WINDOW_HOURS").unwrap_or(168);
+        let level = Self::level_sensor_config("IRRIGATION_LEVEL");
 
         Some(IrrigationConfig {
             enabled,
@@ -192,10 +662,23 @@ impl Settings {
             max_seconds_runtime,
             process_frequency_sec,
             pump_control_pin,
+            pump_polarity,
             valve_1_control_pin,
+            valve_1_polarity,
             valve_2_control_pin,
+            valve_2_polarity,
             valve_3_control_pin,
+            valve_3_polarity,
             valve_4_control_pin,
+            valve_4_polarity,
+            valve_1_flow_rate,
+            valve_2_flow_rate,
+            valve_3_flow_rate,
+            valve_4_flow_rate,
+            water_budget,
+            reservoir_capacity,
+            refill_window_hours,
+            level,
         })
     }
 
@@ -213,12 +696,16 @@ impl Settings {
         let pump_control_pin: u8 = load_system_var("SUMP_CONTROL_PIN")
             .parse()
             .expect("SUMP_CONTROL_PIN must be a number.");
+        let pump_polarity = load_optional_system_var("SUMP_PUMP_POLARITY").unwrap_or_default();
         let pump_max_runtime = load_system_var("SUMP_PUMP_MAX_RUNTIME")
             .parse()
             .expect("SUMP_PUMP_MAX_RUNTIME must be a number.");
         let pump_shutoff_delay: u64 = load_system_var("SUMP_SHUTOFF_DELAY")
             .parse()
             .expect("SUMP_SHUTOFF_DELAY must be a number.");
+        let pump_flow_rate = load_optional_system_var("SUMP_PUMP_FLOW_RATE");
+        let level = Self::level_sensor_config("SUMP_LEVEL");
+        let pump_thresholds = Self::pump_thresholds_config(level.as_ref());
 
         if pump_shutoff_delay >= 5 {
             panic!("SUMP_SHUTOFF_DELAY must be 5 seconds or less.");
@@ -229,8 +716,12 @@ impl Settings {
             high_sensor_pin,
             low_sensor_pin,
             pump_control_pin,
+            pump_polarity,
             pump_shutoff_delay,
             pump_max_runtime,
+            pump_flow_rate,
+            level,
+            pump_thresholds,
         })
     }
 }
@@ -241,10 +732,113 @@ impl Default for Settings {
     }
 }
 
+fn pin_problems(gpio: &GpioConfig, adc: Option<&AdcConfig>, pins: &[(String, u8)]) -> Vec<String> {
+    let mut problems = vec![];
+    let rppal = gpio.backend == GpioBackend::Rppal;
+
+    let mut users: BTreeMap<u8, Vec<&str>> = BTreeMap::new();
+    for (var, pin) in pins {
+        users.entry(*pin).or_default().push(var);
+    }
+    for (pin, vars) in users.iter().filter(|(_, vars)| vars.len() > 1) {
+        problems.push(format!("Pin {} is used by {}", pin, vars.join(", ")));
+    }
+
+    if let Some(expander) = &gpio.expander {
+        if rppal && expander.pin_base < BOARD_PINS {
+            problems.push(format!(
+                "GPIO_EXPANDER_PIN_BASE={} overlaps the board's pins (0-{})",
+                expander.pin_base,
+                BOARD_PINS - 1
+            ));
+        }
+    }
+
+    for (var, pin) in pins {
+        if let Some(expander) = &gpio.expander {
+            let last = u16::from(expander.pin_base) + EXPANDER_PINS - 1;
+            if *pin >= expander.pin_base {
+                if u16::from(*pin) > last {
+                    problems.push(format!(
+                        "{}={} is past the expander's last pin ({})",
+                        var, pin, last
+                    ));
+                }
+                continue;
+            }
+        }
+
+        // Other boards' lines are checked against their chip when claimed
+        if !rppal {
+            continue;
+        }
+
+        if *pin >= BOARD_PINS {
+            problems.push(format!(
+                "{}={} is outside the board's pins (0-{})",
+                var,
+                pin,
+                BOARD_PINS - 1
+            ));
+            continue;
+        }
+
+        let spi_pin = adc.filter(|adc| adc.spi_bus == 0).and_then(|adc| {
+            SPI0_PINS
+                .iter()
+                .find(|(spi, _)| spi == pin && (*spi != 7 || adc.chip_select == 1))
+        });
+        if let Some((_, function)) = spi_pin {
+            problems.push(format!(
+                "{}={} is {}, which the ADC is on",
+                var, pin, function
+            ));
+            continue;
+        }
+
+        let expander_bus = gpio
+            .expander
+            .as_ref()
+            .is_some_and(|expander| expander.i2c_bus == 1);
+        let Some((_, function)) = RESERVED_PINS.iter().find(|(reserved, _)| reserved == pin) else {
+            continue;
+        };
+        if expander_bus && (*pin == 2 || *pin == 3) {
+            problems.push(format!(
+                "{}={} is {}, which the expander is on",
+                var, pin, function
+            ));
+        } else if !gpio.unreserved_pins.contains(pin) {
+            problems.push(format!(
+                "{}={} is reserved for {}; add it to GPIO_UNRESERVED_PINS if that is turned off",
+                var, pin, function
+            ));
+        }
+    }
+
+    problems
+}
+
 fn load_system_var(env: &str) -> String {
     env::var(env).unwrap_or_else(|_| panic!("{} environment variable not found.", env))
 }
 
+/// Reads an optional setting, panicking if it is present but can't be parsed.
+fn load_optional_system_var<T: FromStr>(env: &str) -> Option<T> {
+    env::var(env).ok().map(|value| {
+        value
+            .parse()
+            .unwrap_or_else(|_| panic!("{} has an invalid value.", env))
+    })
+}
+
+/// A relay's polarity from `env`, falling back to `group_env`, which its group shares.
+fn load_polarity(env: &str, group_env: &str) -> Polarity {
+    load_optional_system_var(env)
+        .or_else(|| load_optional_system_var(group_env))
+        .unwrap_or_default()
+}
+
 fn set_application_environment() {
     if std::env::var("RPSUMP_TEST").is_ok() {
         dotenv::from_filename(".env.test").ok();
@@ -253,3 +847,135 @@ fn set_application_environment() {
 
     dotenv().ok();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{load_polarity, pin_problems, AdcConfig, ExpanderConfig, GpioConfig, Polarity};
+    use crate::hydro::adc::AdcBackend;
+    use crate::hydro::gpio::GpioBackend;
+
+    fn gpio(backend: GpioBackend, expander: Option<ExpanderConfig>) -> GpioConfig {
+        GpioConfig {
+            backend,
+            chip_path: "/dev/gpiochip0".into(),
+            expander,
+            unreserved_pins: vec![14],
+            record_path: None,
+        }
+    }
+
+    fn expander(pin_base: u8) -> Option<ExpanderConfig> {
+        Some(ExpanderConfig {
+            i2c_bus: 1,
+            address: 0x20,
+            pin_base,
+            poll_ms: 50,
+        })
+    }
+
+    fn pins(pins: &[(&str, u8)]) -> Vec<(String, u8)> {
+        pins.iter()
+            .map(|(var, pin)| (var.to_string(), *pin))
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_pins() {
+        let pins = pins(&[("HEATER_CONTROL_PIN", 10), ("SUMP_HIGH_SENSOR_PIN", 14)]);
+
+        assert!(pin_problems(&gpio(GpioBackend::Rppal, None), None, &pins).is_empty());
+    }
+
+    #[test]
+    fn test_every_problem_reported() {
+        let pins = pins(&[
+            ("HEATER_CONTROL_PIN", 8),
+            ("IRRIGATION_VALVE_2_CONTROL_PIN", 8),
+            ("SUMP_CONTROL_PIN", 40),
+            ("POOL_PUMP_LOW_PIN", 15),
+        ]);
+
+        let problems = pin_problems(&gpio(GpioBackend::Rppal, None), None, &pins);
+
+        assert!(
+            problems
+                == vec![
+                    "Pin 8 is used by HEATER_CONTROL_PIN, IRRIGATION_VALVE_2_CONTROL_PIN",
+                    "SUMP_CONTROL_PIN=40 is outside the board's pins (0-27)",
+                    "POOL_PUMP_LOW_PIN=15 is reserved for UART RX; add it to \
+                     GPIO_UNRESERVED_PINS if that is turned off",
+                ]
+        );
+    }
+
+    #[test]
+    fn test_expander_pins() {
+        let pins = pins(&[
+            ("OUTPUT_FOUNTAIN_PIN", 100),
+            ("OUTPUT_POOL_LIGHTS_PIN", 116),
+            ("HEATER_CONTROL_PIN", 3),
+        ]);
+
+        let problems = pin_problems(&gpio(GpioBackend::Rppal, expander(100)), None, &pins);
+
+        assert!(
+            problems
+                == vec![
+                    "OUTPUT_POOL_LIGHTS_PIN=116 is past the expander's last pin (115)",
+                    "HEATER_CONTROL_PIN=3 is I2C1 SCL, which the expander is on",
+                ]
+        );
+        assert!(pin_problems(&gpio(GpioBackend::Rppal, expander(20)), None, &[]).len() == 1);
+    }
+
+    #[test]
+    fn test_spi_pins() {
+        let pins = pins(&[
+            ("HEATER_CONTROL_PIN", 10),
+            ("IRRIGATION_VALVE_3_CONTROL_PIN", 7),
+            ("SUMP_CONTROL_PIN", 11),
+        ]);
+        let adc = |chip_select| AdcConfig {
+            backend: AdcBackend::Mcp3008,
+            spi_bus: 0,
+            chip_select,
+            clock_hz: 1_000_000,
+        };
+        let gpio = gpio(GpioBackend::Rppal, None);
+
+        let problems = pin_problems(&gpio, Some(&adc(1)), &pins);
+
+        assert!(
+            problems
+                == vec![
+                    "HEATER_CONTROL_PIN=10 is SPI0 MOSI, which the ADC is on",
+                    "IRRIGATION_VALVE_3_CONTROL_PIN=7 is SPI0 CE1, which the ADC is on",
+                    "SUMP_CONTROL_PIN=11 is SPI0 SCLK, which the ADC is on",
+                ]
+        );
+        // CE1 is free with the ADC on CE0, and every pin is without an ADC
+        assert!(pin_problems(&gpio, Some(&adc(0)), &pins).len() == 2);
+        assert!(pin_problems(&gpio, None, &pins).is_empty());
+    }
+
+    #[test]
+    fn test_cdev_lines_unchecked() {
+        let pins = pins(&[("HEATER_CONTROL_PIN", 2), ("SUMP_CONTROL_PIN", 60)]);
+
+        assert!(pin_problems(&gpio(GpioBackend::Cdev, None), None, &pins).is_empty());
+    }
+
+    #[test]
+    fn test_polarity_overrides_group() {
+        std::env::set_var("TEST_GROUP_POLARITY", "active_low");
+        std::env::set_var("TEST_RELAY_2_POLARITY", "active_high");
+
+        assert!(
+            load_polarity("TEST_RELAY_1_POLARITY", "TEST_GROUP_POLARITY") == Polarity::ActiveLow
+        );
+        assert!(
+            load_polarity("TEST_RELAY_2_POLARITY", "TEST_GROUP_POLARITY") == Polarity::ActiveHigh
+        );
+        assert!(load_polarity("TEST_RELAY_3_POLARITY", "TEST_NO_POLARITY") == Polarity::ActiveHigh);
+    }
+}
@@ -0,0 +1,60 @@
+use actix_web::{
+    post,
+    web::{self, Data},
+    HttpResponse, Result,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::Mutex;
+
+use crate::{
+    auth::authenticated_user::AuthenticatedUser,
+    hydro::{
+        selftest::{self, SelfTestError, DEFAULT_PULSE_MS},
+        Hydro,
+    },
+    util::ApiResponse,
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestParams {
+    /// How long each relay is switched on for
+    pub pulse_ms: Option<u64>,
+}
+
+/// Pulses every relay in turn and reads back the sensors. The other equipment endpoints wait
+/// until the test is done.
+#[post("/hardware/selftest")]
+#[tracing::instrument(skip(user, hydro))]
+pub async fn hardware_selftest(
+    params: web::Json<SelfTestParams>,
+    user: AuthenticatedUser,
+    hydro: Data<Mutex<Hydro>>,
+) -> Result<HttpResponse> {
+    let hydro = hydro.lock().await;
+    let pulse_ms = params.pulse_ms.unwrap_or(DEFAULT_PULSE_MS);
+
+    tracing::info!(
+        target = module_path!(),
+        user_id = user.id,
+        pulse_ms,
+        "Starting hardware self-test"
+    );
+
+    match selftest::run(&hydro, pulse_ms).await {
+        Ok(report) => Ok(HttpResponse::Ok().json(report)),
+        Err(SelfTestError::InvalidPulse) => Ok(ApiResponse::bad_request(
+            SelfTestError::InvalidPulse.to_string(),
+        )),
+        Err(SelfTestError::Other(e)) => {
+            tracing::error!(
+                target = module_path!(),
+                error = e.to_string(),
+                "Could not run hardware self-test"
+            );
+            Ok(ApiResponse::internal_server_error())
+        }
+        Err(e) => Ok(HttpResponse::Conflict().json(json!({"message": e.to_string()}))),
+    }
+}
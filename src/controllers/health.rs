@@ -0,0 +1,8 @@
+use actix_web::{get, HttpResponse, Result};
+use serde_json::json;
+
+/// Answers without auth or locks, so the watchdog's probe only shows the server is serving.
+#[get("/health")]
+async fn health() -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(json!({ "status": "ok" })))
+}
@@ -8,34 +8,90 @@ use actix_web::{
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-use crate::{auth::authenticated_user::AuthenticatedUser, hydro::Hydro};
+use crate::{
+    auth::authenticated_user::AuthenticatedUser,
+    controllers::auth::helpers::error_response,
+    hydro::{
+        control::ChangeSource,
+        heater::InterlockError,
+        state::{self, HeaterState},
+        thermostat::Thermostat,
+        Hydro,
+    },
+    util::ApiResponse,
+};
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum HeaterLevel {
     Off,
     On,
+    Thermostat,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct HeaterParams {
     pub switch: HeaterLevel,
+    /// Degrees Celsius; required by the thermostat
+    pub setpoint: Option<f64>,
+    pub hysteresis: Option<f64>,
 }
 
 #[post("/heater")]
-#[tracing::instrument(skip(_user, hydro))]
+#[tracing::instrument(skip(user, hydro))]
 pub async fn heater(
     params: web::Json<HeaterParams>,
-    _user: AuthenticatedUser,
+    user: AuthenticatedUser,
     hydro: Data<Mutex<Hydro>>,
 ) -> Result<HttpResponse> {
     let mut hydro = hydro.lock().await;
+    let source = ChangeSource::User { id: user.id };
+
+    // Switching by hand takes the heater out of thermostat mode
+    if params.switch != HeaterLevel::Thermostat {
+        hydro.heater.set_thermostat(None);
+    }
 
-    match params.switch {
-        HeaterLevel::On => hydro.heater.on().await,
-        HeaterLevel::Off => hydro.heater.off().await,
+    let heater_state = match params.switch {
+        HeaterLevel::On => {
+            if let Err(e) = hydro.heater.on(source).await {
+                if e.downcast_ref::<InterlockError>().is_none() {
+                    return Ok(error_response(e, "Could not switch the heater on"));
+                }
+                tracing::warn!(
+                    target = module_path!(),
+                    error = e.to_string(),
+                    "Heater interlock refused to switch on"
+                );
+                return Ok(HttpResponse::Conflict().json(json!({"message": e.to_string()})));
+            }
+            HeaterState::On
+        }
+        HeaterLevel::Off => {
+            if let Err(e) = hydro.heater.off(source).await {
+                return Ok(error_response(e, "Could not switch the heater off"));
+            }
+            HeaterState::Off
+        }
+        HeaterLevel::Thermostat => {
+            let Some(setpoint) = params.setpoint else {
+                return Ok(ApiResponse::bad_request(
+                    "setpoint is required for the thermostat".into(),
+                ));
+            };
+
+            match Thermostat::new(setpoint, params.hysteresis) {
+                Ok(thermostat) => {
+                    hydro.heater.set_thermostat(Some(thermostat));
+                    HeaterState::Thermostat(thermostat)
+                }
+                Err(e) => return Ok(ApiResponse::bad_request(e.to_string())),
+            }
+        }
     };
 
+    state::save(hydro.repo, state::HEATER, &heater_state).await;
+
     tracing::info!("Heater status changed: {:?}", params.switch);
 
     Ok(HttpResponse::Ok().json(json!({"status":"ok"})))
@@ -4,7 +4,7 @@ use serde_json::json;
 use tokio::sync::Mutex;
 
 use crate::auth::authenticated_user::AuthenticatedUser;
-use crate::hydro::Hydro;
+use crate::hydro::{level::LevelSensor, temperature::read_temperature, Hydro};
 
 #[get("/info")]
 #[tracing::instrument(skip(hydro, _user))]
@@ -15,8 +15,45 @@ async fn info(
 ) -> Result<HttpResponse> {
     let hydro = hydro.lock().await;
 
+    // A missing or failing sensor is reported as no reading rather than failing the request
+    let temperature = match read_temperature(&hydro.temperature).await {
+        Ok(temperature) => Some(temperature),
+        Err(e) => {
+            tracing::warn!(
+                target = module_path!(),
+                error = e.to_string(),
+                "Could not read pool temperature"
+            );
+            None
+        }
+    };
+
     Ok(HttpResponse::Ok().json(json!({
+        "equipment": hydro.equipment().await,
+        "reservoirLevel": read_level(hydro.reservoir_level.as_ref(), "reservoir"),
+        "freezeProtection": hydro.freeze_protection.as_ref().map(|f| f.is_active()),
         "heater": hydro.heater.is_on().await,
+        "poolPumpCommandedSpeed": hydro.pool_pump.commanded_speed(),
+        "poolPumpPriming": hydro.pool_pump.is_priming().await,
         "poolPumpSpeed": hydro.pool_pump.speed().await,
+        "sumpLevel": read_level(hydro.sump_level.as_ref(), "sump"),
+        "temperature": temperature,
+        "thermostat": hydro.heater.thermostat(),
     })))
 }
+
+/// Percent full, or none when there is no sensor or it can't be read.
+fn read_level(sensor: Option<&LevelSensor>, name: &str) -> Option<f64> {
+    match sensor?.percent() {
+        Ok(percent) => Some(percent),
+        Err(e) => {
+            tracing::warn!(
+                target = module_path!(),
+                error = e.to_string(),
+                sensor = name,
+                "Could not read water level"
+            );
+            None
+        }
+    }
+}
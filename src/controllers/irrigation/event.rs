@@ -5,15 +5,12 @@ use actix_web::{
     HttpResponse, Result,
 };
 
-use serde::Deserialize;
-
 use crate::auth::authenticated_user::AuthenticatedUser;
+use crate::repository::models::irrigation_event::IrrigationEventFilter;
 use crate::{controllers::auth::helpers::error_response, repository::Repo};
 
-#[derive(Debug, Deserialize)]
-pub struct Params {
-    pub status: Option<String>,
-}
+/// Response header holding the cursor for the next page of events, when there is one.
+pub const NEXT_CURSOR_HEADER: &str = "X-Next-Cursor";
 
 #[get("/event")]
 #[tracing::instrument(skip(req, repo, _user))]
@@ -22,17 +19,22 @@ pub async fn irrigation_event(
     repo: Data<Repo>,
     _user: AuthenticatedUser,
 ) -> Result<HttpResponse> {
-    let _filter = match Query::<Params>::from_query(req.query_string()) {
-        Ok(filter) => filter,
+    let filter = match Query::<IrrigationEventFilter>::from_query(req.query_string()) {
+        Ok(filter) => filter.into_inner(),
         Err(_e) => {
             return Ok(HttpResponse::BadRequest().body("invalid filter"));
         }
     };
 
-    let irrigation_events = match repo.irrigation_events().await {
+    let irrigation_events = match repo.irrigation_events(filter.clone()).await {
         Ok(irrigation_events) => irrigation_events,
         Err(e) => return Ok(error_response(e, "Could not get irrigation events")),
     };
 
-    Ok(HttpResponse::Ok().json(irrigation_events))
+    let mut response = HttpResponse::Ok();
+    if let Some(cursor) = filter.next_cursor(&irrigation_events) {
+        response.insert_header((NEXT_CURSOR_HEADER, cursor.to_string()));
+    }
+
+    Ok(response.json(irrigation_events))
 }
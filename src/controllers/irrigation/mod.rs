@@ -2,6 +2,7 @@ use actix_web::web::ServiceConfig;
 
 pub mod event;
 pub mod schedule;
+pub mod usage;
 
 pub fn irrigation_routes(cfg: &mut ServiceConfig) {
     cfg.service(event::irrigation_event);
@@ -10,4 +11,5 @@ pub fn irrigation_routes(cfg: &mut ServiceConfig) {
     cfg.service(schedule::irrigation_schedule);
     cfg.service(schedule::irrigation_schedules);
     cfg.service(schedule::new_irrigation_schedule);
+    cfg.service(usage::irrigation_usage);
 }
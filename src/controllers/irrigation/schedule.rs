@@ -1,13 +1,30 @@
 use actix_web::{delete, get, patch, post, web, web::Data, HttpResponse, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 
 use crate::auth::authenticated_user::AuthenticatedUser;
 use crate::controllers::auth::helpers::error_response;
+use crate::hydro::schedule::conflict::{find_conflicts, ScheduleConflict, ScheduleWindow};
 use crate::repository::models::irrigation_schedule::{
-    CreateIrrigationScheduleParams, UpdateIrrigationScheduleParams,
+    CreateIrrigationScheduleParams, IrrigationSchedule, UpdateIrrigationScheduleParams,
 };
 use crate::repository::Repo;
 use crate::util::ApiResponse;
 
+#[derive(Debug, Deserialize)]
+pub struct SaveScheduleQuery {
+    /// Reject the schedule instead of saving it with warnings when it conflicts with another.
+    pub strict: Option<bool>,
+}
+
+/// A saved schedule along with any conflicts found with the other schedules.
+#[derive(Debug, Serialize)]
+pub struct SaveScheduleResponse {
+    #[serde(flatten)]
+    pub schedule: IrrigationSchedule,
+    pub warnings: Vec<ScheduleConflict>,
+}
+
 #[get("/schedule")]
 #[tracing::instrument(skip(_req_body, repo, _user))]
 pub async fn irrigation_schedules(
@@ -68,6 +85,7 @@ pub async fn delete_irrigation_schedule(
 #[tracing::instrument(skip(req_body, repo, _user))]
 pub async fn edit_irrigation_schedule(
     path: web::Path<i32>,
+    query: web::Query<SaveScheduleQuery>,
     req_body: web::Json<UpdateIrrigationScheduleParams>,
     repo: Data<Repo>,
     _user: AuthenticatedUser,
@@ -76,16 +94,27 @@ pub async fn edit_irrigation_schedule(
 
     let params: UpdateIrrigationScheduleParams = req_body.into_inner();
 
-    // let irrigation_sched = match repo.update_irrigation_schedule(id, params).await {
-    //     Ok(irrigation_sched) => irrigation_sched,
-    //     Err(e) => {
-    //         return Ok(ApiResponse::bad_request(e.to_string()));
-    //     }
-    // };
+    let schedules = match repo.irrigation_schedules().await {
+        Ok(schedules) => schedules,
+        Err(e) => return Ok(error_response(e, "Could not get irrigation schedules")),
+    };
+
+    let mut candidate = match schedules.iter().find(|s| s.id == id) {
+        Some(schedule) => schedule.clone(),
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+    candidate.apply_update(params.clone());
+
+    let warnings = schedule_conflicts(&ScheduleWindow::from(&candidate), &schedules);
+    if query.strict.unwrap_or(false) && !warnings.is_empty() {
+        return Ok(conflict_response(warnings));
+    }
 
     match repo.update_irrigation_schedule(id, params).await {
         Ok(None) => Ok(HttpResponse::NotFound().finish()),
-        Ok(schedule) => Ok(HttpResponse::Ok().json(schedule)),
+        Ok(Some(schedule)) => {
+            Ok(HttpResponse::Ok().json(SaveScheduleResponse { schedule, warnings }))
+        }
         Err(e) => {
             tracing::error!(
                 target = module_path!(),
@@ -101,13 +130,24 @@ pub async fn edit_irrigation_schedule(
 #[post("/schedule")]
 #[tracing::instrument(skip(req_body, repo, _user))]
 pub async fn new_irrigation_schedule(
+    query: web::Query<SaveScheduleQuery>,
     req_body: web::Json<CreateIrrigationScheduleParams>,
     repo: Data<Repo>,
     _user: AuthenticatedUser,
 ) -> Result<HttpResponse> {
     let params: CreateIrrigationScheduleParams = req_body.into_inner();
 
-    let response = match repo.create_irrigation_schedule(params).await {
+    let schedules = match repo.irrigation_schedules().await {
+        Ok(schedules) => schedules,
+        Err(e) => return Ok(error_response(e, "Could not get irrigation schedules")),
+    };
+
+    let warnings = schedule_conflicts(&ScheduleWindow::from(&params), &schedules);
+    if query.strict.unwrap_or(false) && !warnings.is_empty() {
+        return Ok(conflict_response(warnings));
+    }
+
+    let schedule = match repo.create_irrigation_schedule(params).await {
         Ok(schedule) => schedule,
         Err(e) => {
             // TODO: check bad request or ISE
@@ -115,5 +155,21 @@ pub async fn new_irrigation_schedule(
         }
     };
 
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().json(SaveScheduleResponse { schedule, warnings }))
+}
+
+fn schedule_conflicts(
+    candidate: &ScheduleWindow,
+    schedules: &[IrrigationSchedule],
+) -> Vec<ScheduleConflict> {
+    let windows: Vec<ScheduleWindow> = schedules.iter().map(ScheduleWindow::from).collect();
+
+    find_conflicts(candidate, &windows)
+}
+
+fn conflict_response(conflicts: Vec<ScheduleConflict>) -> HttpResponse {
+    HttpResponse::Conflict().json(json!({
+        "message": "Schedule conflicts with existing schedules",
+        "conflicts": conflicts,
+    }))
 }
@@ -0,0 +1,60 @@
+use actix_web::HttpRequest;
+use actix_web::{
+    get,
+    web::{Data, Query},
+    HttpResponse, Result,
+};
+use chrono::{NaiveDate, Utc};
+use serde::Deserialize;
+
+use crate::auth::authenticated_user::AuthenticatedUser;
+use crate::config::Settings;
+use crate::hydro::usage::{completed_irrigation_events, report, UsagePeriod};
+use crate::{controllers::auth::helpers::error_response, repository::Repo};
+
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+    pub period: Option<UsagePeriod>,
+    /// First day to report on; defaults to the last twelve periods
+    pub since: Option<NaiveDate>,
+}
+
+#[get("/usage")]
+#[tracing::instrument(skip(req, repo, settings, _user))]
+pub async fn irrigation_usage(
+    req: HttpRequest,
+    repo: Data<Repo>,
+    settings: Data<Settings>,
+    _user: AuthenticatedUser,
+) -> Result<HttpResponse> {
+    let query = match Query::<UsageQuery>::from_query(req.query_string()) {
+        Ok(query) => query.into_inner(),
+        Err(_e) => {
+            return Ok(HttpResponse::BadRequest().body("invalid usage query"));
+        }
+    };
+
+    let period = query.period.unwrap_or_default();
+    let since = query
+        .since
+        .unwrap_or_else(|| period.default_since(Utc::now().date_naive()));
+    let since_time = since.and_hms_opt(0, 0, 0).unwrap_or_default();
+
+    let irrigation_events = match completed_irrigation_events(**repo, since_time).await {
+        Ok(irrigation_events) => irrigation_events,
+        Err(e) => return Ok(error_response(e, "Could not get irrigation events")),
+    };
+
+    let sump_events = match repo.sump_pump_events(since_time).await {
+        Ok(sump_events) => sump_events,
+        Err(e) => return Ok(error_response(e, "Could not get sump events")),
+    };
+
+    Ok(HttpResponse::Ok().json(report(
+        period,
+        since,
+        &irrigation_events,
+        &sump_events,
+        &settings.hydro,
+    )))
+}
@@ -1,6 +1,13 @@
 pub mod auth;
+pub mod hardware;
+pub mod health;
 pub mod heater;
 pub mod info;
 pub mod irrigation;
+pub mod outputs;
+pub mod pool;
 pub mod pool_pump;
+pub mod pool_pump_schedule;
+#[cfg(feature = "stub")]
+pub mod sim;
 pub mod sump_event;
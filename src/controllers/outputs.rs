@@ -0,0 +1,83 @@
+use actix_web::{
+    get, post,
+    web::{self, Data},
+    HttpResponse, Result,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::Mutex;
+
+use crate::{
+    auth::authenticated_user::AuthenticatedUser,
+    hydro::{control::ChangeSource, state, Hydro},
+    util::ApiResponse,
+};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputLevel {
+    Off,
+    On,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OutputParams {
+    pub switch: OutputLevel,
+}
+
+#[get("/outputs")]
+#[tracing::instrument(skip(_user, hydro))]
+pub async fn outputs(_user: AuthenticatedUser, hydro: Data<Mutex<Hydro>>) -> Result<HttpResponse> {
+    let hydro = hydro.lock().await;
+
+    let mut outputs = vec![];
+    for output in hydro.outputs.iter() {
+        outputs.push(output.status().await);
+    }
+
+    Ok(HttpResponse::Ok().json(outputs))
+}
+
+#[post("/outputs/{name}")]
+#[tracing::instrument(skip(user, hydro))]
+pub async fn switch_output(
+    path: web::Path<String>,
+    params: web::Json<OutputParams>,
+    user: AuthenticatedUser,
+    hydro: Data<Mutex<Hydro>>,
+) -> Result<HttpResponse> {
+    let hydro = hydro.lock().await;
+    let name = path.into_inner();
+
+    let Some(output) = hydro.output(&name) else {
+        return Ok(ApiResponse::not_found());
+    };
+
+    let source = ChangeSource::User { id: user.id };
+    let mut output = output.clone();
+    let switched = match params.switch {
+        OutputLevel::On => output.on(source).await,
+        OutputLevel::Off => output.off(source).await,
+    };
+
+    if let Err(e) = switched {
+        tracing::error!(
+            target = module_path!(),
+            error = e.to_string(),
+            output = name,
+            "Could not switch output"
+        );
+        return Ok(ApiResponse::internal_server_error());
+    }
+
+    state::save(
+        hydro.repo,
+        &state::output_device(&name),
+        &(params.switch == OutputLevel::On),
+    )
+    .await;
+
+    tracing::info!("Output {} switched: {:?}", name, params.switch);
+
+    Ok(HttpResponse::Ok().json(json!({"status":"ok"})))
+}
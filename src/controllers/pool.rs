@@ -0,0 +1,43 @@
+use actix_web::HttpRequest;
+use actix_web::{
+    get,
+    web::{Data, Query},
+    HttpResponse, Result,
+};
+use chrono::Utc;
+
+use crate::auth::authenticated_user::AuthenticatedUser;
+use crate::config::Settings;
+use crate::controllers::irrigation::usage::UsageQuery;
+use crate::hydro::energy::report;
+use crate::{controllers::auth::helpers::error_response, repository::Repo};
+
+#[get("/pool/usage")]
+#[tracing::instrument(skip(req, repo, settings, _user))]
+pub async fn pool_usage(
+    req: HttpRequest,
+    repo: Data<Repo>,
+    settings: Data<Settings>,
+    _user: AuthenticatedUser,
+) -> Result<HttpResponse> {
+    let query = match Query::<UsageQuery>::from_query(req.query_string()) {
+        Ok(query) => query.into_inner(),
+        Err(_e) => {
+            return Ok(HttpResponse::BadRequest().body("invalid usage query"));
+        }
+    };
+
+    let now = Utc::now().naive_utc();
+    let period = query.period.unwrap_or_default();
+    let since = query
+        .since
+        .unwrap_or_else(|| period.default_since(now.date()));
+    let since_time = since.and_hms_opt(0, 0, 0).unwrap_or_default();
+
+    let events = match repo.equipment_events(since_time).await {
+        Ok(events) => events,
+        Err(e) => return Ok(error_response(e, "Could not get equipment events")),
+    };
+
+    Ok(HttpResponse::Ok().json(report(period, since, &events, now, &settings.hydro)))
+}
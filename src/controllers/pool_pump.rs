@@ -10,7 +10,7 @@ use tokio::sync::Mutex;
 
 use crate::auth::authenticated_user::AuthenticatedUser;
 use crate::{
-    hydro::{pool_pump::PoolPumpSpeed, Hydro},
+    hydro::{control::ChangeSource, pool_pump::PoolPumpSpeed, state, Hydro},
     util::ApiResponse,
 };
 
@@ -20,53 +20,111 @@ pub struct PoolPumpParams {
 }
 
 #[post("/pool_pump")]
-#[tracing::instrument(skip(_user, hydro))]
+#[tracing::instrument(skip(user, hydro))]
 pub async fn pool_pump(
     params: web::Json<PoolPumpParams>,
-    _user: AuthenticatedUser,
+    user: AuthenticatedUser,
     hydro: Data<Mutex<Hydro>>,
 ) -> Result<HttpResponse> {
     let hydro = hydro.lock().await;
 
+    if let Some(freeze_protection) = &hydro.freeze_protection {
+        if !freeze_protection.allows(params.speed) {
+            return Ok(HttpResponse::Conflict().json(json!({
+                "message": format!(
+                    "Freeze protection is running the pool pump at {} speed or faster",
+                    freeze_protection.speed
+                )
+            })));
+        }
+    }
+
+    hydro.pool_pump_override.hold();
+
+    let source = ChangeSource::User { id: user.id };
     let mut pool_pump = hydro.pool_pump.clone();
     match params.speed {
         PoolPumpSpeed::Off => {
-            if let Err(e) = pool_pump.off().await {
+            if let Err(e) = pool_pump.off(source).await {
                 error_trace(&params.speed, &e);
                 return Ok(ApiResponse::internal_server_error());
             }
         }
         PoolPumpSpeed::Low => {
-            if let Err(e) = pool_pump.on(PoolPumpSpeed::Low).await {
+            if let Err(e) = pool_pump.on(PoolPumpSpeed::Low, source).await {
                 error_trace(&params.speed, &e);
                 return Ok(ApiResponse::internal_server_error());
             }
         }
         PoolPumpSpeed::Med => {
-            if let Err(e) = pool_pump.on(PoolPumpSpeed::Med).await {
+            if let Err(e) = pool_pump.on(PoolPumpSpeed::Med, source).await {
                 error_trace(&params.speed, &e);
                 return Ok(ApiResponse::internal_server_error());
             }
         }
         PoolPumpSpeed::High => {
-            if let Err(e) = pool_pump.on(PoolPumpSpeed::High).await {
+            if let Err(e) = pool_pump.on(PoolPumpSpeed::High, source).await {
                 error_trace(&params.speed, &e);
                 return Ok(ApiResponse::internal_server_error());
             }
         }
         PoolPumpSpeed::Max => {
-            if let Err(e) = pool_pump.on(PoolPumpSpeed::Max).await {
+            if let Err(e) = pool_pump.on(PoolPumpSpeed::Max, source).await {
                 error_trace(&params.speed, &e);
                 return Ok(ApiResponse::internal_server_error());
             }
         }
     };
 
+    state::save(hydro.repo, state::POOL_PUMP, &params.speed).await;
+    state::save(hydro.repo, state::POOL_PUMP_OVERRIDE, &true).await;
+
     tracing::info!("Heater status changed: {:?}", params.speed);
 
     Ok(HttpResponse::Ok().json(json!({"status":"ok"})))
 }
 
+/// Skips the rest of the priming run, dropping the pool pump to the speed it was asked for.
+#[post("/pool_pump/cancel_priming")]
+#[tracing::instrument(skip(user, hydro))]
+pub async fn cancel_pool_pump_priming(
+    user: AuthenticatedUser,
+    hydro: Data<Mutex<Hydro>>,
+) -> Result<HttpResponse> {
+    let hydro = hydro.lock().await;
+
+    let mut pump = hydro.pool_pump.clone();
+    match pump
+        .cancel_priming(ChangeSource::User { id: user.id })
+        .await
+    {
+        Ok(true) => Ok(HttpResponse::Ok().json(json!({"status":"ok"}))),
+        Ok(false) => Ok(HttpResponse::Conflict().json(json!({
+            "message": "The pool pump is not priming"
+        }))),
+        Err(e) => {
+            tracing::error!("Error cancelling pool pump priming: {:?}", e);
+            Ok(ApiResponse::internal_server_error())
+        }
+    }
+}
+
+/// Releases a manual override so the pool pump follows its programs again.
+#[post("/pool_pump/resume")]
+#[tracing::instrument(skip(_user, hydro))]
+pub async fn resume_pool_pump_schedule(
+    _user: AuthenticatedUser,
+    hydro: Data<Mutex<Hydro>>,
+) -> Result<HttpResponse> {
+    let hydro = hydro.lock().await;
+    hydro.pool_pump_override.resume();
+    state::save(hydro.repo, state::POOL_PUMP_OVERRIDE, &false).await;
+
+    tracing::info!("Pool pump schedule resumed");
+
+    Ok(HttpResponse::Ok().json(json!({"status":"ok"})))
+}
+
 fn error_trace(speed: &PoolPumpSpeed, e: &Error) {
     tracing::error!("Error while setting the pool pump to {:?}: {:?}", speed, e);
 }
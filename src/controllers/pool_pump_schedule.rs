@@ -0,0 +1,115 @@
+use actix_web::web::ServiceConfig;
+use actix_web::{delete, get, patch, post, web, web::Data, HttpResponse, Result};
+
+use crate::auth::authenticated_user::AuthenticatedUser;
+use crate::controllers::auth::helpers::error_response;
+use crate::repository::models::pool_pump_schedule::{
+    CreatePoolPumpScheduleParams, UpdatePoolPumpScheduleParams,
+};
+use crate::repository::Repo;
+use crate::util::ApiResponse;
+
+pub fn pool_pump_schedule_routes(cfg: &mut ServiceConfig) {
+    cfg.service(delete_pool_pump_schedule);
+    cfg.service(edit_pool_pump_schedule);
+    cfg.service(new_pool_pump_schedule);
+    cfg.service(pool_pump_schedule);
+    cfg.service(pool_pump_schedules);
+}
+
+#[get("/pool_pump/schedule")]
+#[tracing::instrument(skip(repo, _user))]
+pub async fn pool_pump_schedules(
+    repo: Data<Repo>,
+    _user: AuthenticatedUser,
+) -> Result<HttpResponse> {
+    let schedules = match repo.pool_pump_schedules().await {
+        Ok(schedules) => schedules,
+        Err(e) => {
+            return Ok(error_response(e, "Could not get pool pump schedules"));
+        }
+    };
+
+    Ok(HttpResponse::Ok().json(schedules))
+}
+
+#[get("/pool_pump/schedule/{id}")]
+#[tracing::instrument(skip(repo, _user))]
+pub async fn pool_pump_schedule(
+    path: web::Path<i32>,
+    repo: Data<Repo>,
+    _user: AuthenticatedUser,
+) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    let schedule = match repo.pool_pump_schedule_by_id(id).await {
+        Ok(schedule) => schedule,
+        Err(_e) => return Ok(ApiResponse::not_found()),
+    };
+
+    Ok(HttpResponse::Ok().json(schedule))
+}
+
+#[delete("/pool_pump/schedule/{id}")]
+#[tracing::instrument(skip(repo, _user))]
+pub async fn delete_pool_pump_schedule(
+    path: web::Path<i32>,
+    repo: Data<Repo>,
+    _user: AuthenticatedUser,
+) -> Result<HttpResponse> {
+    let id = path.into_inner();
+
+    match repo.delete_pool_pump_schedule(id).await {
+        Ok(None) => Ok(ApiResponse::not_found()),
+        Ok(rows) => Ok(HttpResponse::Ok().json(rows)),
+        Err(e) => Ok(error_response(e, "Could not delete pool pump schedule")),
+    }
+}
+
+#[patch("/pool_pump/schedule/{id}")]
+#[tracing::instrument(skip(req_body, repo, _user))]
+pub async fn edit_pool_pump_schedule(
+    path: web::Path<i32>,
+    req_body: web::Json<UpdatePoolPumpScheduleParams>,
+    repo: Data<Repo>,
+    _user: AuthenticatedUser,
+) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    let params: UpdatePoolPumpScheduleParams = req_body.into_inner();
+
+    let mut candidate = match repo.pool_pump_schedule_by_id(id).await {
+        Ok(schedule) => schedule,
+        Err(_e) => return Ok(ApiResponse::not_found()),
+    };
+    candidate.apply_update(params.clone());
+    if candidate.start_time == candidate.end_time {
+        return Ok(empty_window_response());
+    }
+
+    match repo.update_pool_pump_schedule(id, params).await {
+        Ok(None) => Ok(ApiResponse::not_found()),
+        Ok(Some(schedule)) => Ok(HttpResponse::Ok().json(schedule)),
+        Err(e) => Ok(error_response(e, "Could not update pool pump schedule")),
+    }
+}
+
+#[post("/pool_pump/schedule")]
+#[tracing::instrument(skip(req_body, repo, _user))]
+pub async fn new_pool_pump_schedule(
+    req_body: web::Json<CreatePoolPumpScheduleParams>,
+    repo: Data<Repo>,
+    _user: AuthenticatedUser,
+) -> Result<HttpResponse> {
+    let params: CreatePoolPumpScheduleParams = req_body.into_inner();
+    if params.start_time == params.end_time {
+        return Ok(empty_window_response());
+    }
+
+    match repo.create_pool_pump_schedule(params).await {
+        Ok(schedule) => Ok(HttpResponse::Ok().json(schedule)),
+        Err(e) => Ok(ApiResponse::bad_request(e.to_string())),
+    }
+}
+
+fn empty_window_response() -> HttpResponse {
+    ApiResponse::bad_request("start_time and end_time must be different".to_string())
+}
@@ -0,0 +1,51 @@
+use actix_web::{
+    get, post,
+    web::{self, Data, ServiceConfig},
+    HttpResponse, Result,
+};
+use serde::Deserialize;
+
+use crate::{
+    auth::authenticated_user::AuthenticatedUser,
+    hydro::gpio::{sim::SimGpio, Level},
+    util::ApiResponse,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct LevelParams {
+    pub level: Level,
+}
+
+/// Only served by builds with the simulated GPIO, under `/sim`.
+pub fn sim_routes(cfg: &mut ServiceConfig) {
+    cfg.service(pins);
+    cfg.service(set_pin_level);
+}
+
+#[get("/pins")]
+#[tracing::instrument(skip(_user, gpio))]
+pub async fn pins(_user: AuthenticatedUser, gpio: Data<SimGpio>) -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(gpio.pins()))
+}
+
+/// Drives an input, such as a float switch, as if the water had moved it.
+#[post("/pin/{pin}/level")]
+#[tracing::instrument(skip(_user, gpio))]
+pub async fn set_pin_level(
+    path: web::Path<u8>,
+    params: web::Json<LevelParams>,
+    _user: AuthenticatedUser,
+    gpio: Data<SimGpio>,
+) -> Result<HttpResponse> {
+    let pin = path.into_inner();
+
+    if let Err(e) = gpio.set_input(pin, params.level) {
+        return Ok(ApiResponse::bad_request(e.to_string()));
+    }
+
+    tracing::info!("Simulated pin {} driven {:?}", pin, params.level);
+
+    let status = gpio.pins().into_iter().find(|status| status.pin == pin);
+
+    Ok(HttpResponse::Ok().json(status))
+}
@@ -0,0 +1,76 @@
+use anyhow::{anyhow, Error};
+
+use crate::hydro::adc::{Adc, SpiBus};
+
+/// Inputs on the converter.
+pub const CHANNELS: u8 = 8;
+
+/// The largest 10-bit reading.
+pub const MAX_READING: u16 = 1023;
+
+/// The start bit, sent on its own so the reply lines up on a byte boundary.
+pub const START: u8 = 0x01;
+
+/// Picks a single-ended rather than a differential reading, ahead of the channel bits.
+pub const SINGLE_ENDED: u8 = 0x80;
+
+/// An MCP3008 10-bit converter. Each channel reads from 0 at ground to 1023 at the reference
+/// voltage.
+pub struct Mcp3008 {
+    bus: Box<dyn SpiBus>,
+}
+
+impl Mcp3008 {
+    pub fn new(bus: Box<dyn SpiBus>) -> Self {
+        Self { bus }
+    }
+}
+
+impl Adc for Mcp3008 {
+    fn channels(&self) -> u8 {
+        CHANNELS
+    }
+
+    fn max_reading(&self) -> u16 {
+        MAX_READING
+    }
+
+    fn read(&mut self, channel: u8) -> Result<u16, Error> {
+        if channel >= CHANNELS {
+            return Err(anyhow!("The MCP3008 has no channel {}", channel));
+        }
+
+        let write = [START, SINGLE_ENDED | (channel << 4), 0];
+        let mut read = [0; 3];
+        self.bus.transfer(&write, &mut read)?;
+
+        // The reply's top two bits end the second byte, after a null bit
+        Ok(u16::from(read[1] & 0x03) << 8 | u16::from(read[2]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mcp3008;
+    use crate::{hydro::adc::Adc, test_fixtures::spi::FakeMcp3008};
+
+    #[test]
+    fn test_read_channels() {
+        let fake = FakeMcp3008::new();
+        fake.set_reading(0, 0);
+        fake.set_reading(3, 512);
+        fake.set_reading(7, 1023);
+        let mut adc = Mcp3008::new(Box::new(fake));
+
+        assert!(adc.read(0).unwrap() == 0);
+        assert!(adc.read(3).unwrap() == 512);
+        assert!(adc.read(7).unwrap() == 1023);
+    }
+
+    #[test]
+    fn test_invalid_channel() {
+        let mut adc = Mcp3008::new(Box::new(FakeMcp3008::new()));
+
+        assert!(adc.read(8).is_err());
+    }
+}
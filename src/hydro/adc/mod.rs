@@ -0,0 +1,72 @@
+use anyhow::{anyhow, Error};
+use serde::Deserialize;
+use std::str::FromStr;
+
+pub mod mcp3008;
+
+/// The converter analog sensors are read through.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AdcBackend {
+    /// A 10-bit, 8-channel MCP3008 on SPI
+    Mcp3008,
+}
+
+impl FromStr for AdcBackend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mcp3008" => Ok(AdcBackend::Mcp3008),
+            _ => Err(anyhow!("Invalid ADC backend: {}", s)),
+        }
+    }
+}
+
+/// An analog-to-digital converter, read one channel at a time.
+pub trait Adc: Send {
+    /// Channels are numbered from 0 up to this, exclusive.
+    fn channels(&self) -> u8;
+    /// The reading at the reference voltage.
+    fn max_reading(&self) -> u16;
+    fn read(&mut self, channel: u8) -> Result<u16, Error>;
+}
+
+/// The SPI bus a converter is reached through.
+pub trait SpiBus: Send {
+    /// Clocks `write` out while filling `read`, which is the same length.
+    fn transfer(&mut self, write: &[u8], read: &mut [u8]) -> Result<(), Error>;
+}
+
+impl SpiBus for rppal::spi::Spi {
+    fn transfer(&mut self, write: &[u8], read: &mut [u8]) -> Result<(), Error> {
+        rppal::spi::Spi::transfer(self, read, write)?;
+
+        Ok(())
+    }
+}
+
+/// Opens `/dev/spidev<bus>.<chip_select>` in mode 0.
+pub fn open_spi(bus: u8, chip_select: u8, clock_hz: u32) -> Result<rppal::spi::Spi, Error> {
+    use rppal::spi::{Bus, Mode, SlaveSelect, Spi};
+
+    let bus = match bus {
+        0 => Bus::Spi0,
+        1 => Bus::Spi1,
+        2 => Bus::Spi2,
+        3 => Bus::Spi3,
+        4 => Bus::Spi4,
+        5 => Bus::Spi5,
+        6 => Bus::Spi6,
+        _ => return Err(anyhow!("Invalid SPI bus: {}", bus)),
+    };
+    let slave_select = match chip_select {
+        0 => SlaveSelect::Ss0,
+        1 => SlaveSelect::Ss1,
+        2 => SlaveSelect::Ss2,
+        _ => return Err(anyhow!("Invalid SPI chip select: {}", chip_select)),
+    };
+
+    Spi::new(bus, slave_select, clock_hz, Mode::Mode0)
+        .map_err(|e| anyhow!("Could not open SPI bus: {}", e))
+}
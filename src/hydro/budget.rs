@@ -0,0 +1,222 @@
+use anyhow::Error;
+use chrono::{Duration, NaiveDateTime, Utc};
+
+use crate::config::{HydroConfig, IrrigationConfig};
+use crate::hydro::usage::{completed_irrigation_events, irrigation_runs, litres, sump_runs};
+use crate::repository::{
+    models::{irrigation_event::IrrigationEvent, sump_event::SumpEvent},
+    Repo,
+};
+
+/// Shortened runs below this many seconds are deferred instead; they barely wet the soil.
+pub const MIN_RUNTIME_SECONDS: i32 = 5;
+
+/// How much of a scheduled run the reservoir can cover.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Allowance {
+    /// Run for the full duration
+    Full,
+    /// Run for this many seconds instead
+    Shortened(i32),
+    /// Leave the event queued until the sump has reclaimed more water
+    Deferred,
+}
+
+/// Estimates the reservoir volume from recorded sump pump runtime (inflow) and irrigation
+/// runtime (outflow), scaled by the configured pump flow rates. Only runs within the refill
+/// window count, so each estimate reads a bounded slice of the history.
+#[derive(Clone, Debug)]
+pub struct WaterBudget {
+    pub irrigation: IrrigationConfig,
+    /// Litres per minute
+    pub sump_flow_rate: f64,
+}
+
+impl WaterBudget {
+    /// Returns `None` when water budget mode is off, or when the sump flow rate it depends on
+    /// is not configured.
+    pub fn from_config(config: &HydroConfig) -> Option<Self> {
+        if !config.irrigation.water_budget {
+            return None;
+        }
+
+        let Some(sump_flow_rate) = config.sump.pump_flow_rate else {
+            tracing::warn!(
+                target = module_path!(),
+                "Water budget mode needs SUMP_PUMP_FLOW_RATE; irrigating without a budget"
+            );
+            return None;
+        };
+
+        Some(Self {
+            irrigation: config.irrigation.clone(),
+            sump_flow_rate,
+        })
+    }
+
+    /// Works out how long `event` may run for with `available` litres in the reservoir.
+    pub fn allowance(&self, available: f64, event: &IrrigationEvent, duration: i32) -> Allowance {
+        allowance(
+            available,
+            duration,
+            self.irrigation.flow_rate(event.hose_id),
+        )
+    }
+
+    /// The estimated volume of water in the reservoir, from the pump runs within the refill
+    /// window.
+    pub async fn available_litres(&self, repo: Repo) -> Result<f64, Error> {
+        let since =
+            Utc::now().naive_utc() - Duration::hours(self.irrigation.refill_window_hours as i64);
+        let irrigation_events = completed_irrigation_events(repo, since).await?;
+        let sump_events = repo.sump_pump_events(since).await?;
+
+        Ok(self.estimate(&irrigation_events, &sump_events))
+    }
+
+    /// Replays the recorded pump runs in order, from an empty reservoir, so it never drops
+    /// below empty or rises above its capacity.
+    pub fn estimate(
+        &self,
+        irrigation_events: &[IrrigationEvent],
+        sump_events: &[SumpEvent],
+    ) -> f64 {
+        let mut changes: Vec<(NaiveDateTime, f64)> = sump_runs(sump_events)
+            .iter()
+            .filter_map(|run| Some((run.end, litres(run.seconds(), Some(self.sump_flow_rate))?)))
+            .collect();
+
+        for hose_id in 1..=4 {
+            let flow_rate = self.irrigation.flow_rate(hose_id);
+            changes.extend(
+                irrigation_runs(irrigation_events, hose_id)
+                    .iter()
+                    .filter_map(|run| Some((run.end, -litres(run.seconds(), flow_rate)?))),
+            );
+        }
+
+        changes.sort_by_key(|(time, _)| *time);
+
+        let capacity = self.irrigation.reservoir_capacity.unwrap_or(f64::INFINITY);
+        changes.iter().fold(0.0, |level, (_, change)| {
+            (level + change).clamp(0.0, capacity)
+        })
+    }
+}
+
+/// How much of a `duration` second run `available` litres can cover at `flow_rate`.
+/// A zone without a flow rate can't be budgeted, so it always runs in full.
+pub fn allowance(available: f64, duration: i32, flow_rate: Option<f64>) -> Allowance {
+    let Some(flow_rate) = flow_rate.filter(|rate| *rate > 0.0) else {
+        return Allowance::Full;
+    };
+
+    let affordable_seconds = (available / flow_rate * 60.0).floor() as i32;
+    if affordable_seconds >= duration {
+        Allowance::Full
+    } else if affordable_seconds >= MIN_RUNTIME_SECONDS {
+        Allowance::Shortened(affordable_seconds)
+    } else {
+        Allowance::Deferred
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+    use rstest::rstest;
+
+    use super::{allowance, Allowance, WaterBudget};
+    use crate::repository::models::{
+        irrigation_event::IrrigationEventStatus,
+        sump_event::{SumpEvent, PUMP_OFF, PUMP_ON, SUMP_PUMP_KIND},
+    };
+    use crate::test_fixtures::irrigation::event::completed_event;
+    use crate::test_fixtures::settings::SETTINGS;
+
+    fn at(time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn sump_event(id: i32, info: &str, created_at: &str) -> SumpEvent {
+        SumpEvent {
+            id,
+            kind: SUMP_PUMP_KIND.into(),
+            info: info.into(),
+            created_at: at(created_at),
+        }
+    }
+
+    fn budget() -> WaterBudget {
+        let mut irrigation = SETTINGS.hydro.irrigation.clone();
+        irrigation.water_budget = true;
+
+        WaterBudget {
+            irrigation,
+            sump_flow_rate: 12.0,
+        }
+    }
+
+    #[rstest]
+    #[case(10.0, 60, Some(6.0), Allowance::Full)]
+    #[case(3.0, 60, Some(6.0), Allowance::Shortened(30))]
+    #[case(0.1, 60, Some(6.0), Allowance::Deferred)]
+    #[case(0.0, 60, None, Allowance::Full)]
+    fn test_allowance(
+        #[case] available: f64,
+        #[case] duration: i32,
+        #[case] flow_rate: Option<f64>,
+        #[case] expected: Allowance,
+    ) {
+        assert_eq!(allowance(available, duration, flow_rate), expected);
+    }
+
+    #[rstest]
+    fn test_estimate() {
+        let budget = budget();
+        let sump_events = vec![
+            // 12 litres in
+            sump_event(1, PUMP_ON, "2021-01-01 00:00:00"),
+            sump_event(2, PUMP_OFF, "2021-01-01 00:01:00"),
+            // 12 more litres in
+            sump_event(3, PUMP_ON, "2021-01-01 02:00:00"),
+            sump_event(4, PUMP_OFF, "2021-01-01 02:01:00"),
+        ];
+        let irrigation_events = vec![
+            // Hose 1 takes 6 litres per minute, but only 12 are there to take
+            completed_event(
+                1,
+                1,
+                at("2021-01-01 01:00:00"),
+                Some(at("2021-01-01 01:03:00")),
+                IrrigationEventStatus::Completed,
+                1,
+                None,
+            ),
+            // Hose 3 takes 3 litres
+            completed_event(
+                2,
+                3,
+                at("2021-01-01 03:00:00"),
+                Some(at("2021-01-01 03:01:00")),
+                IrrigationEventStatus::Completed,
+                1,
+                None,
+            ),
+        ];
+
+        assert_eq!(budget.estimate(&irrigation_events, &sump_events), 9.0);
+    }
+
+    #[rstest]
+    fn test_estimate_capacity() {
+        let mut budget = budget();
+        budget.irrigation.reservoir_capacity = Some(5.0);
+        let sump_events = vec![
+            sump_event(1, PUMP_ON, "2021-01-01 00:00:00"),
+            sump_event(2, PUMP_OFF, "2021-01-01 00:01:00"),
+        ];
+
+        assert_eq!(budget.estimate(&[], &sump_events), 5.0);
+    }
+}
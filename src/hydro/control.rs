@@ -1,8 +1,11 @@
 use crate::hydro::gpio::{Gpio, OutputPin};
 use crate::util::spawn_blocking_with_tracing;
-use anyhow::Error;
+use anyhow::{anyhow, Error};
 use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::str::FromStr;
 use std::sync::Arc;
 use tokio::sync::{Mutex, MutexGuard};
 
@@ -11,27 +14,161 @@ pub type PinLock<'a> = MutexGuard<'a, Box<dyn OutputPin>>;
 /// Represents a GPIO output for controlling stateful equipment. Water pump, etc.
 pub type SharedOutputPin = Arc<Mutex<Box<dyn OutputPin>>>;
 
+/// The level that energizes a relay. Many relay boards switch on when their input is pulled
+/// low.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Polarity {
+    #[default]
+    ActiveHigh,
+    ActiveLow,
+}
+
+impl FromStr for Polarity {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "active_high" => Ok(Polarity::ActiveHigh),
+            "active_low" => Ok(Polarity::ActiveLow),
+            _ => Err(anyhow!("Invalid polarity: {}", s)),
+        }
+    }
+}
+
+/// Turns an active-low pin into one switched on by `on`, so everything holding the pin can
+/// ignore its polarity.
+struct ActiveLowPin(Box<dyn OutputPin>);
+
+impl OutputPin for ActiveLowPin {
+    fn is_on(&self) -> bool {
+        self.0.is_off()
+    }
+
+    fn is_off(&self) -> bool {
+        self.0.is_on()
+    }
+
+    fn on(&mut self) {
+        self.0.off()
+    }
+
+    fn off(&mut self) {
+        self.0.on()
+    }
+}
+
+/// Who or what switched an output.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ChangeSource {
+    /// A request to the API by the user with this id
+    User {
+        id: i32,
+    },
+    Schedule,
+    /// The heater interlock, keeping water flowing through the heater
+    Interlock,
+    Thermostat,
+    FreezeProtection,
+    /// Put back the way it was before a restart
+    Restore,
+    /// A water level sensor, or the sump pump safety timer
+    Sensor,
+    /// An output's maximum on time running out
+    Timer,
+    Shutdown,
+}
+
+/// The state an output was last switched to. Outputs start off, with no change recorded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Command {
+    pub on: bool,
+    pub changed_at: Option<NaiveDateTime>,
+    pub changed_by: Option<ChangeSource>,
+}
+
+/// What an output was told to do next to what its pin reads back.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ControlStatus {
+    pub label: String,
+    pub commanded: bool,
+    pub pin: bool,
+    /// The pin doesn't match the last command
+    pub mismatch: bool,
+    pub changed_at: Option<NaiveDateTime>,
+    pub changed_by: Option<ChangeSource>,
+}
+
 #[derive(Clone)]
 pub struct Control {
     pub label: String,
     pub pin: SharedOutputPin,
+    /// Shared by every clone of the control
+    command: Arc<std::sync::Mutex<Command>>,
 }
 
 impl Control {
     /// Creates a new output on a GPIO pin.
     pub fn new(label: String, pin: u8, gpio: &dyn Gpio) -> Result<Self, Error> {
+        Self::with_polarity(label, pin, Polarity::ActiveHigh, gpio)
+    }
+
+    /// Creates a new output on a GPIO pin that is on at the `polarity` level. The output
+    /// starts off either way.
+    pub fn with_polarity(
+        label: String,
+        pin: u8,
+        polarity: Polarity,
+        gpio: &dyn Gpio,
+    ) -> Result<Self, Error> {
         let pin = gpio.get(pin)?;
-        let pin_io = pin.into_output_low();
+        let pin_io: Box<dyn OutputPin> = match polarity {
+            Polarity::ActiveHigh => pin.into_output_low(),
+            Polarity::ActiveLow => Box::new(ActiveLowPin(pin.into_output_high())),
+        };
 
         Ok(Self {
             label,
             pin: Arc::from(Mutex::new(pin_io)),
+            command: Arc::new(std::sync::Mutex::new(Command::default())),
         })
     }
 
-    pub async fn lock(&self) -> PinLock {
+    /// Holds the pin. Switching it through the lock leaves the last command as it was; `on`
+    /// and `off` record the change.
+    pub async fn lock(&self) -> PinLock<'_> {
         self.pin.lock().await
     }
+
+    pub fn command(&self) -> Command {
+        *self.command.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Notes that the output was switched `on` or off by `source`.
+    fn record(&self, on: bool, source: ChangeSource) {
+        *self.command.lock().unwrap_or_else(|e| e.into_inner()) = Command {
+            on,
+            changed_at: Some(Utc::now().naive_utc()),
+            changed_by: Some(source),
+        };
+    }
+
+    pub async fn status(&self) -> ControlStatus {
+        let command = self.command();
+        let pin = self.is_on().await;
+
+        ControlStatus {
+            label: self.label.clone(),
+            commanded: command.on,
+            pin,
+            mismatch: pin != command.on,
+            changed_at: command.changed_at,
+            changed_by: command.changed_by,
+        }
+    }
 }
 
 impl fmt::Debug for Control {
@@ -50,15 +187,16 @@ impl PartialEq for Control {
 
 #[async_trait]
 pub trait Output {
-    async fn on(&mut self) -> Result<(), Error>;
-    async fn off(&mut self) -> Result<(), Error>;
+    async fn on(&mut self, source: ChangeSource) -> Result<(), Error>;
+    async fn off(&mut self, source: ChangeSource) -> Result<(), Error>;
     async fn is_on(&self) -> bool;
     async fn is_off(&self) -> bool;
 }
 
 #[async_trait]
 impl Output for Control {
-    async fn on(&mut self) -> Result<(), Error> {
+    async fn on(&mut self, source: ChangeSource) -> Result<(), Error> {
+        self.record(true, source);
         let self = self.clone();
         spawn_blocking_with_tracing(|| async move {
             let pin = self.pin.clone();
@@ -71,7 +209,8 @@ impl Output for Control {
         Ok(())
     }
 
-    async fn off(&mut self) -> Result<(), Error> {
+    async fn off(&mut self, source: ChangeSource) -> Result<(), Error> {
+        self.record(false, source);
         let self = self.clone();
         spawn_blocking_with_tracing(|| async move {
             let pin = self.pin.clone();
@@ -97,13 +236,90 @@ impl Output for Control {
 
 #[cfg(test)]
 mod tests {
-    use super::Control;
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    use super::{ChangeSource, Control, Output, Polarity};
+    use crate::hydro::gpio::{MockGpio, MockOutputPin, MockPin};
     use crate::test_fixtures::gpio::mock_control_gpio;
 
+    /// A gpio whose one pin opens high and records its raw level in `level`.
+    fn mock_level_gpio(level: Arc<AtomicBool>) -> MockGpio {
+        let mut mock_gpio = MockGpio::new();
+        mock_gpio.expect_get().times(1).returning(move |_| {
+            let level = Arc::clone(&level);
+            let mut pin = MockPin::new();
+            pin.expect_into_output_low().never();
+            pin.expect_into_output_high().times(1).returning(move || {
+                level.store(true, Ordering::SeqCst);
+                let mut output = MockOutputPin::new();
+                let (on, off, is_on, is_off) = (
+                    Arc::clone(&level),
+                    Arc::clone(&level),
+                    Arc::clone(&level),
+                    Arc::clone(&level),
+                );
+                output
+                    .expect_on()
+                    .returning(move || on.store(true, Ordering::SeqCst));
+                output
+                    .expect_off()
+                    .returning(move || off.store(false, Ordering::SeqCst));
+                output
+                    .expect_is_on()
+                    .returning(move || is_on.load(Ordering::SeqCst));
+                output
+                    .expect_is_off()
+                    .returning(move || !is_off.load(Ordering::SeqCst));
+                Box::new(output)
+            });
+            Ok(Box::new(pin))
+        });
+
+        mock_gpio
+    }
+
     #[tokio::test]
     async fn test_control_new() {
         let control = Control::new("test control".to_string(), 1, &mock_control_gpio());
 
         assert!(control.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_active_low() {
+        let level = Arc::new(AtomicBool::new(false));
+        let mut control = Control::with_polarity(
+            "relay".to_string(),
+            1,
+            Polarity::ActiveLow,
+            &mock_level_gpio(Arc::clone(&level)),
+        )
+        .unwrap();
+
+        // Starts off, with the pin high
+        assert!(level.load(Ordering::SeqCst));
+        assert!(control.is_off().await);
+
+        control.on(ChangeSource::Schedule).await.unwrap();
+
+        assert!(!level.load(Ordering::SeqCst));
+        assert!(control.is_on().await);
+        assert!(!control.status().await.mismatch);
+
+        // Switching the pin directly honours the polarity too
+        control.lock().await.off();
+
+        assert!(level.load(Ordering::SeqCst));
+        assert!(control.is_off().await);
+    }
+
+    #[test]
+    fn test_polarity_from_str() {
+        assert!("active_low".parse::<Polarity>().unwrap() == Polarity::ActiveLow);
+        assert!("active_high".parse::<Polarity>().unwrap() == Polarity::ActiveHigh);
+        assert!("low".parse::<Polarity>().is_err());
+    }
 }
@@ -81,6 +81,20 @@ impl Debouncer {
     }
 }
 
+/// Starts the debouncer on the first interrupt, and pushes back its deadline on any that follow
+/// before it elapses. Shared by every `InputPin` implementation.
+pub async fn handle_interrupt(debouncer: Arc<Mutex<Debouncer>>, level: Level) {
+    let mut debouncer = debouncer.lock().await;
+    let running = debouncer.running.lock().await;
+    if *running {
+        drop(running);
+        debouncer.reset_deadline(level).await;
+    } else {
+        drop(running);
+        debouncer.start().await;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,530 @@
+use anyhow::{anyhow, Error};
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::config::HydroConfig;
+use crate::hydro::{
+    pool_pump::PoolPumpSpeed,
+    state::{HEATER, POOL_PUMP},
+    usage::{PumpRun, UsagePeriod},
+};
+use crate::repository::{
+    models::equipment_event::{EquipmentEvent, HEATER_OFF, HEATER_ON},
+    Repo,
+};
+
+/// Records equipment changing state, for runtime and energy reporting.
+#[derive(Clone, Copy)]
+pub struct EventLog {
+    repo: Repo,
+}
+
+impl EventLog {
+    pub fn new(repo: Repo) -> Self {
+        Self { repo }
+    }
+
+    /// Failing to record shouldn't fail the change itself, so errors are logged rather than
+    /// returned.
+    pub async fn record(&self, device: &str, state: &str) {
+        if let Err(e) = self
+            .repo
+            .create_equipment_event(device.into(), state.into())
+            .await
+        {
+            tracing::error!(
+                target = module_path!(),
+                error = e.to_string(),
+                device,
+                state,
+                "Could not record equipment event"
+            );
+        }
+    }
+
+    /// Every output starts off. Records the heater and pool pump going off where the log
+    /// last had them running, so a run cut short by a restart ends there; a device the log
+    /// already has off is left alone.
+    pub async fn record_startup(&self) {
+        let last_events = match self.repo.equipment_events(Utc::now().naive_utc()).await {
+            Ok(events) => events,
+            Err(e) => {
+                tracing::error!(
+                    target = module_path!(),
+                    error = e.to_string(),
+                    "Could not load equipment events"
+                );
+                return;
+            }
+        };
+
+        let pool_pump_off = PoolPumpSpeed::Off.to_string();
+        for (device, off) in [(HEATER, HEATER_OFF), (POOL_PUMP, pool_pump_off.as_str())] {
+            let last = last_events
+                .iter()
+                .rev()
+                .find(|event| event.device == device);
+            if last.is_some_and(|event| event.state != off) {
+                self.record(device, off).await;
+            }
+        }
+    }
+}
+
+/// The price of a kWh from `start` until the next rate starts.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct TariffRate {
+    pub start: NaiveTime,
+    pub price: f64,
+}
+
+/// A time-of-use electricity tariff. The last rate of the day runs on past midnight until the
+/// first rate of the next.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Tariff {
+    /// Sorted by start time
+    pub rates: Vec<TariffRate>,
+}
+
+/// Parses rates written as `HH:MM=price`, separated by commas; e.g. `00:00=0.12,16:00=0.35`.
+impl FromStr for Tariff {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rates = s
+            .split(',')
+            .map(|rate| {
+                let (start, price) = rate
+                    .trim()
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("Invalid tariff rate: {}", rate))?;
+
+                Ok(TariffRate {
+                    start: NaiveTime::parse_from_str(start, "%H:%M")?,
+                    price: price.parse()?,
+                })
+            })
+            .collect::<Result<Vec<TariffRate>, Error>>()?;
+
+        if rates.is_empty() {
+            return Err(anyhow!("A tariff needs at least one rate"));
+        }
+        rates.sort_by_key(|rate| rate.start);
+
+        Ok(Self { rates })
+    }
+}
+
+impl Tariff {
+    /// The price of a kWh at `time` of day.
+    pub fn price_at(&self, time: NaiveTime) -> f64 {
+        self.rates
+            .iter()
+            .rev()
+            .find(|rate| rate.start <= time)
+            .or(self.rates.last())
+            .map(|rate| rate.price)
+            .unwrap_or_default()
+    }
+
+    /// When the price next changes after `at`.
+    fn next_change(&self, at: NaiveDateTime) -> NaiveDateTime {
+        match self.rates.iter().find(|rate| rate.start > at.time()) {
+            Some(rate) => at.date().and_time(rate.start),
+            None => (at.date() + Duration::days(1)).and_time(self.rates[0].start),
+        }
+    }
+
+    /// The cost of drawing `kw` for the length of `run`, split at each change of price.
+    pub fn cost(&self, run: &PumpRun, kw: f64) -> f64 {
+        let mut cost = 0.0;
+        let mut at = run.start;
+
+        while at < run.end {
+            let until = self.next_change(at).min(run.end);
+            cost += (until - at).num_seconds() as f64 / 3600.0 * kw * self.price_at(at.time());
+            at = until;
+        }
+
+        cost
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EnergyBucket {
+    pub start: NaiveDate,
+    pub runtime_seconds: i64,
+    /// `None` when no wattage is configured
+    pub kwh: Option<f64>,
+    /// `None` when no wattage or tariff is configured
+    pub cost: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EnergySeries {
+    pub watts: Option<f64>,
+    pub runtime_seconds: i64,
+    pub kwh: Option<f64>,
+    pub cost: Option<f64>,
+    pub buckets: Vec<EnergyBucket>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SpeedUsage {
+    pub speed: PoolPumpSpeed,
+    #[serde(flatten)]
+    pub usage: EnergySeries,
+}
+
+/// Runtime and electricity used by the heater, and by the pool pump at each speed.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PoolUsageReport {
+    pub period: UsagePeriod,
+    pub since: NaiveDate,
+    pub heater: EnergySeries,
+    pub pool_pump: Vec<SpeedUsage>,
+}
+
+/// The stretches of time `device` spent in `state`. Each event holds until the device's next
+/// event, so a repeated state extends the open run, and a run still going ends at `now`.
+///
+/// # Arguments
+///
+/// * `events` - Equipment events, oldest first
+/// * `device` - The device to report on
+/// * `state`  - The state to report on
+/// * `now`    - Where a run still going ends
+pub fn runs(
+    events: &[EquipmentEvent],
+    device: &str,
+    state: &str,
+    now: NaiveDateTime,
+) -> Vec<PumpRun> {
+    let mut runs = vec![];
+    let mut started_at: Option<NaiveDateTime> = None;
+
+    for event in events.iter().filter(|event| event.device == device) {
+        if event.state == state {
+            started_at.get_or_insert(event.created_at);
+        } else if let Some(start) = started_at.take() {
+            runs.push(PumpRun {
+                start,
+                end: event.created_at,
+            });
+        }
+    }
+
+    if let Some(start) = started_at {
+        runs.push(PumpRun { start, end: now });
+    }
+
+    runs
+}
+
+/// The part of `run` from the start of `since` on, split at each midnight so every piece falls
+/// on a single day.
+fn daily_pieces(run: &PumpRun, since: NaiveDate) -> Vec<PumpRun> {
+    let mut pieces = vec![];
+    let mut at = run.start.max(since.and_time(NaiveTime::MIN));
+    // Switched on and off within the same second; still a run, just without runtime
+    if run.start == run.end && at == run.start {
+        pieces.push(PumpRun { start: at, end: at });
+    }
+
+    while at < run.end {
+        let until = (at.date() + Duration::days(1))
+            .and_time(NaiveTime::MIN)
+            .min(run.end);
+        pieces.push(PumpRun {
+            start: at,
+            end: until,
+        });
+        at = until;
+    }
+
+    pieces
+}
+
+/// Totals `runs` into one bucket per `period`. Each run is split at midnight so every day's
+/// share lands in that day's bucket, and time before `since` is left out.
+pub fn series(
+    runs: &[PumpRun],
+    period: UsagePeriod,
+    since: NaiveDate,
+    watts: Option<f64>,
+    tariff: Option<&Tariff>,
+) -> EnergySeries {
+    let kwh = |seconds: i64| watts.map(|watts| seconds as f64 / 3600.0 * watts / 1000.0);
+    let cost = |run: &PumpRun| match (watts, tariff) {
+        (Some(watts), Some(tariff)) => Some(tariff.cost(run, watts / 1000.0)),
+        _ => None,
+    };
+
+    let mut buckets: Vec<EnergyBucket> = vec![];
+    for run in runs.iter().flat_map(|run| daily_pieces(run, since)) {
+        let start = period.bucket_start(run.start.date());
+        match buckets.iter_mut().find(|bucket| bucket.start == start) {
+            Some(bucket) => {
+                bucket.runtime_seconds += run.seconds();
+                bucket.cost = bucket
+                    .cost
+                    .zip(cost(&run))
+                    .map(|(total, cost)| total + cost);
+            }
+            None => buckets.push(EnergyBucket {
+                start,
+                runtime_seconds: run.seconds(),
+                kwh: None,
+                cost: cost(&run),
+            }),
+        }
+    }
+
+    buckets.sort_by_key(|bucket| bucket.start);
+    for bucket in buckets.iter_mut() {
+        bucket.kwh = kwh(bucket.runtime_seconds);
+    }
+
+    let runtime_seconds = buckets.iter().map(|bucket| bucket.runtime_seconds).sum();
+    let total_cost = (watts.is_some() && tariff.is_some())
+        .then(|| buckets.iter().filter_map(|bucket| bucket.cost).sum());
+
+    EnergySeries {
+        watts,
+        runtime_seconds,
+        kwh: kwh(runtime_seconds),
+        cost: total_cost,
+        buckets,
+    }
+}
+
+/// Builds the runtime and energy report for the heater and each pool pump speed.
+///
+/// # Arguments
+///
+/// * `period` - The size of each bucket
+/// * `since`  - The first day to report on
+/// * `events` - Equipment events, oldest first
+/// * `now`    - Where runs still going end
+/// * `config` - Provides the configured wattages and tariff
+pub fn report(
+    period: UsagePeriod,
+    since: NaiveDate,
+    events: &[EquipmentEvent],
+    now: NaiveDateTime,
+    config: &HydroConfig,
+) -> PoolUsageReport {
+    let tariff = config.tariff.as_ref();
+    let pool_pump = [
+        PoolPumpSpeed::Low,
+        PoolPumpSpeed::Med,
+        PoolPumpSpeed::High,
+        PoolPumpSpeed::Max,
+    ]
+    .into_iter()
+    .map(|speed| SpeedUsage {
+        speed,
+        usage: series(
+            &runs(events, POOL_PUMP, &speed.to_string(), now),
+            period,
+            since,
+            config.pool_pump.watts(speed),
+            tariff,
+        ),
+    })
+    .collect();
+
+    PoolUsageReport {
+        period,
+        since,
+        heater: series(
+            &runs(events, HEATER, HEATER_ON, now),
+            period,
+            since,
+            config.heater.watts,
+            tariff,
+        ),
+        pool_pump,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+    use rstest::rstest;
+    use std::sync::{Arc, Mutex};
+
+    use super::{report, runs, EventLog, Tariff};
+    use crate::hydro::{
+        pool_pump::PoolPumpSpeed,
+        state::{HEATER, POOL_PUMP},
+        usage::{PumpRun, UsagePeriod},
+    };
+    use crate::repository::{
+        models::equipment_event::{EquipmentEvent, HEATER_OFF, HEATER_ON},
+        MockRepository,
+    };
+    use crate::test_fixtures::settings::SETTINGS;
+
+    fn at(time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn event(id: i32, device: &str, state: &str, created_at: &str) -> EquipmentEvent {
+        EquipmentEvent {
+            id,
+            device: device.into(),
+            state: state.into(),
+            created_at: at(created_at),
+        }
+    }
+
+    #[rstest]
+    #[case("00:00=0.1,16:00=0.3,21:00=0.1", "15:59", 0.1)]
+    #[case("00:00=0.1,16:00=0.3,21:00=0.1", "16:00", 0.3)]
+    // Before the first rate of the day, the last rate of the day before still applies
+    #[case("21:00=0.1, 07:00=0.2", "06:00", 0.1)]
+    fn test_price_at(#[case] tariff: &str, #[case] time: &str, #[case] price: f64) {
+        let tariff: Tariff = tariff.parse().unwrap();
+        let time = NaiveTime::parse_from_str(time, "%H:%M").unwrap();
+
+        assert_eq!(tariff.price_at(time), price);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("16:00")]
+    #[case("4pm=0.3")]
+    fn test_invalid_tariff(#[case] tariff: &str) {
+        assert!(tariff.parse::<Tariff>().is_err());
+    }
+
+    #[rstest]
+    fn test_tariff_cost() {
+        let tariff: Tariff = "00:00=0.1,16:00=0.3".parse().unwrap();
+        // Two hours at the cheap rate, one at the peak rate, then two past midnight
+        let run = PumpRun {
+            start: at("2021-01-01 14:00:00"),
+            end: at("2021-01-02 02:00:00"),
+        };
+
+        let cost = tariff.cost(&run, 1.0);
+
+        assert!((cost - (2.0 * 0.1 + 8.0 * 0.3 + 2.0 * 0.1)).abs() < 1e-9);
+    }
+
+    #[rstest]
+    fn test_runs() {
+        let events = vec![
+            event(1, POOL_PUMP, "low", "2021-01-01 00:00:00"),
+            // Other devices don't end the run
+            event(2, HEATER, HEATER_ON, "2021-01-01 00:30:00"),
+            event(3, POOL_PUMP, "low", "2021-01-01 00:45:00"),
+            event(4, POOL_PUMP, "max", "2021-01-01 01:00:00"),
+            event(5, POOL_PUMP, "low", "2021-01-01 02:00:00"),
+        ];
+
+        let runs = runs(&events, POOL_PUMP, "low", at("2021-01-01 02:30:00"));
+
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].seconds(), 3600);
+        // Still running
+        assert_eq!(runs[1].seconds(), 1800);
+    }
+
+    #[rstest]
+    fn test_report_totals() {
+        let events = vec![
+            event(1, HEATER, HEATER_ON, "2021-01-01 10:00:00"),
+            event(2, HEATER, HEATER_OFF, "2021-01-01 12:00:00"),
+            event(3, POOL_PUMP, "low", "2021-01-01 10:00:00"),
+            event(4, POOL_PUMP, "off", "2021-01-01 11:00:00"),
+            event(5, HEATER, HEATER_ON, "2021-01-02 10:00:00"),
+            event(6, HEATER, HEATER_OFF, "2021-01-02 11:00:00"),
+        ];
+
+        let report = report(
+            UsagePeriod::Day,
+            NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+            &events,
+            at("2021-01-03 00:00:00"),
+            &SETTINGS.hydro,
+        );
+
+        assert_eq!(report.heater.runtime_seconds, 3 * 3600);
+        assert_eq!(report.heater.buckets.len(), 2);
+        // 5000 watts for the heater, at 0.10 a kWh before 16:00, in .env.test
+        assert_eq!(report.heater.kwh, Some(15.0));
+        assert!((report.heater.cost.unwrap() - 1.5).abs() < 1e-9);
+
+        let low = &report.pool_pump[0];
+        assert_eq!(low.speed, PoolPumpSpeed::Low);
+        assert_eq!(low.usage.runtime_seconds, 3600);
+        // 300 watts at low speed in .env.test
+        assert_eq!(low.usage.kwh, Some(0.3));
+        assert!(report.pool_pump[1].usage.buckets.is_empty());
+    }
+
+    #[rstest]
+    fn test_report_splits_runs() {
+        let events = vec![
+            // Started the day before the report, and ran past midnight
+            event(1, HEATER, HEATER_ON, "2020-12-31 23:00:00"),
+            event(2, HEATER, HEATER_OFF, "2021-01-01 01:00:00"),
+            event(3, HEATER, HEATER_ON, "2021-01-01 23:30:00"),
+            event(4, HEATER, HEATER_OFF, "2021-01-02 00:30:00"),
+        ];
+
+        let report = report(
+            UsagePeriod::Day,
+            NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+            &events,
+            at("2021-01-03 00:00:00"),
+            &SETTINGS.hydro,
+        );
+
+        let buckets: Vec<(NaiveDate, i64)> = report
+            .heater
+            .buckets
+            .iter()
+            .map(|bucket| (bucket.start, bucket.runtime_seconds))
+            .collect();
+        assert_eq!(
+            buckets,
+            vec![
+                (NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(), 3600 + 1800),
+                (NaiveDate::from_ymd_opt(2021, 1, 2).unwrap(), 1800),
+            ]
+        );
+        assert_eq!(report.heater.runtime_seconds, 2 * 3600);
+    }
+
+    #[tokio::test]
+    async fn test_record_startup() {
+        let mut repo = MockRepository::new();
+        repo.expect_equipment_events().returning(|_| {
+            Ok(vec![
+                event(1, HEATER, HEATER_ON, "2021-01-01 10:00:00"),
+                event(2, POOL_PUMP, "low", "2021-01-01 10:00:00"),
+                event(3, POOL_PUMP, "off", "2021-01-01 11:00:00"),
+            ])
+        });
+        let recorded = Arc::new(Mutex::new(vec![]));
+        let events = recorded.clone();
+        repo.expect_create_equipment_event()
+            .returning(move |device, state| {
+                events.lock().unwrap().push((device, state));
+                Ok(())
+            });
+        let repo = Box::leak(Box::new(repo));
+
+        EventLog::new(repo).record_startup().await;
+
+        // Only the heater was still running when the log ends
+        assert_eq!(
+            *recorded.lock().unwrap(),
+            vec![(HEATER.to_string(), HEATER_OFF.to_string())]
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use tokio::time::{sleep, Duration};
+
+use crate::config::PoolPumpConfig;
+use crate::hydro::{
+    control::ChangeSource,
+    pool_pump::{PoolPump, PoolPumpSpeed},
+    temperature::{read_temperature, TemperatureSensor},
+};
+
+/// Degrees above the threshold the temperature has to climb before protection stands down,
+/// so a reading hovering at the threshold doesn't cycle the pump.
+pub const RECOVERY_MARGIN: f64 = 1.0;
+
+/// Keeps water moving through the pool plumbing in freezing weather. While active, the pump
+/// runs at `speed` or faster whatever the programs or the API ask for.
+#[derive(Clone, Debug)]
+pub struct FreezeProtection {
+    /// Degrees Celsius
+    pub threshold: f64,
+    pub speed: PoolPumpSpeed,
+    active: Arc<AtomicBool>,
+}
+
+impl FreezeProtection {
+    /// Returns `None` when no freeze temperature is configured.
+    pub fn from_config(config: &PoolPumpConfig) -> Option<Self> {
+        Some(Self::new(config.freeze_temperature?, config.freeze_speed))
+    }
+
+    pub fn new(threshold: f64, speed: PoolPumpSpeed) -> Self {
+        Self {
+            threshold,
+            speed,
+            active: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// Whether the pump may run at `speed` right now.
+    pub fn allows(&self, speed: PoolPumpSpeed) -> bool {
+        !self.is_active() || speed >= self.speed
+    }
+
+    /// Whether protection should be on at `temperature`, given whether it is on now.
+    pub fn is_freezing(&self, temperature: f64, active: bool) -> bool {
+        if active {
+            temperature < self.threshold + RECOVERY_MARGIN
+        } else {
+            temperature < self.threshold
+        }
+    }
+}
+
+/// Intended to be run at startup, once any saved state is restored. Each tick reads the
+/// temperature, and while it is freezing holds the pool pump at the protection speed or faster.
+/// Once the temperature recovers the pump goes back to the speed it was asked for before, by
+/// whoever asked for it, so a program or manual setting carries on; if anything else has
+/// changed the speed in the meantime, that change stands. Readings that fail leave protection
+/// as it is.
+///
+///  # Arguments
+///
+///  * `pool_pump` - The pump to drive
+///  * `sensor` - An air or water temperature sensor
+///  * `protection` - Thresholds, and the active flag shared with the API and scheduler
+///  * `frequency_sec` - Seconds between ticks
+///
+pub async fn run(
+    mut pool_pump: PoolPump,
+    sensor: Arc<dyn TemperatureSensor>,
+    protection: FreezeProtection,
+    frequency_sec: u64,
+) {
+    let mut before = None;
+
+    loop {
+        match read_temperature(&sensor).await {
+            Ok(temperature) => {
+                let was_active = protection.is_active();
+                let active = protection.is_freezing(temperature, was_active);
+                protection.active.store(active, Ordering::SeqCst);
+
+                if active && !was_active {
+                    before = Some(pool_pump.requested().await);
+                    tracing::warn!(
+                        target = module_path!(),
+                        temperature,
+                        speed = protection.speed.to_string(),
+                        "Freeze protection activated"
+                    );
+                } else if !active && was_active {
+                    tracing::info!(
+                        target = module_path!(),
+                        temperature,
+                        "Freeze protection deactivated"
+                    );
+
+                    stand_down(&mut pool_pump, before.take()).await;
+                }
+
+                if active && pool_pump.speed().await < protection.speed {
+                    if let Err(e) = pool_pump
+                        .on(protection.speed, ChangeSource::FreezeProtection)
+                        .await
+                    {
+                        tracing::error!(
+                            target = module_path!(),
+                            error = e.to_string(),
+                            "Could not run the pool pump for freeze protection"
+                        );
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::error!(
+                    target = module_path!(),
+                    error = e.to_string(),
+                    "Could not read temperature for freeze protection"
+                );
+            }
+        }
+
+        sleep(Duration::from_secs(frequency_sec)).await;
+    }
+}
+
+/// Puts the pump back to the speed `before` protection, unless something other than
+/// protection has changed it since.
+async fn stand_down(
+    pool_pump: &mut PoolPump,
+    before: Option<(PoolPumpSpeed, Option<ChangeSource>)>,
+) {
+    let (_, changed_by) = pool_pump.requested().await;
+    if changed_by != Some(ChangeSource::FreezeProtection) {
+        return;
+    }
+
+    let (speed, source) = before.unwrap_or((PoolPumpSpeed::Off, None));
+    let source = source.unwrap_or(ChangeSource::FreezeProtection);
+    if let Err(e) = pool_pump.on(speed, source).await {
+        tracing::error!(
+            target = module_path!(),
+            error = e.to_string(),
+            "Could not restore the pool pump after freeze protection"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use std::sync::{Arc, Mutex};
+    use tokio::time::{sleep, Duration};
+
+    use super::{run, FreezeProtection};
+    use crate::hydro::control::ChangeSource;
+    use crate::hydro::gpio::MockGpio;
+    use crate::hydro::pool_pump::{PoolPump, PoolPumpSpeed};
+    use crate::hydro::temperature::MockTemperatureSensor;
+    use crate::test_fixtures::{gpio::mock_pool_pump, settings::SETTINGS};
+
+    #[rstest]
+    #[case(1.9, false, true)]
+    #[case(2.0, false, false)]
+    #[case(2.5, true, true)]
+    #[case(3.0, true, false)]
+    fn test_is_freezing(#[case] temperature: f64, #[case] active: bool, #[case] expected: bool) {
+        let protection = FreezeProtection::new(2.0, PoolPumpSpeed::Low);
+
+        assert_eq!(protection.is_freezing(temperature, active), expected);
+    }
+
+    #[rstest]
+    #[case(-5.0, true)]
+    #[case(10.0, false)]
+    #[tokio::test(start_paused = true)]
+    async fn test_freeze_protection(#[case] temperature: f64, #[case] active: bool) {
+        let mock_gpio = mock_pool_pump(MockGpio::new(), PoolPumpSpeed::Off);
+        let pool_pump = PoolPump::new(&SETTINGS.hydro.pool_pump, &mock_gpio).unwrap();
+        let mut sensor = MockTemperatureSensor::new();
+        sensor.expect_read().returning(move || Ok(temperature));
+
+        let protection = FreezeProtection::new(2.0, PoolPumpSpeed::Med);
+        let handle = tokio::spawn(run(pool_pump, Arc::new(sensor), protection.clone(), 1));
+        sleep(Duration::from_millis(200)).await;
+        handle.abort();
+
+        assert_eq!(protection.is_active(), active);
+        assert_eq!(protection.allows(PoolPumpSpeed::Off), !active);
+        assert!(protection.allows(PoolPumpSpeed::High));
+    }
+
+    #[rstest]
+    // Nothing else touched the pump, so it goes back to the user's speed
+    #[case(None, (PoolPumpSpeed::Low, Some(ChangeSource::User { id: 1 })))]
+    // A later request stands
+    #[case(
+        Some(PoolPumpSpeed::High),
+        (PoolPumpSpeed::High, Some(ChangeSource::User { id: 2 }))
+    )]
+    #[tokio::test(start_paused = true)]
+    async fn test_freeze_protection_stands_down(
+        #[case] during: Option<PoolPumpSpeed>,
+        #[case] expected: (PoolPumpSpeed, Option<ChangeSource>),
+    ) {
+        let mock_gpio = mock_pool_pump(MockGpio::new(), PoolPumpSpeed::Off);
+        let mut pool_pump = PoolPump::new(&SETTINGS.hydro.pool_pump, &mock_gpio).unwrap();
+        pool_pump
+            .on(PoolPumpSpeed::Low, ChangeSource::User { id: 1 })
+            .await
+            .unwrap();
+        let temperatures = Arc::new(Mutex::new(vec![10.0, -5.0]));
+        let mut sensor = MockTemperatureSensor::new();
+        sensor
+            .expect_read()
+            .returning(move || Ok(temperatures.lock().unwrap().pop().unwrap_or(10.0)));
+
+        let protection = FreezeProtection::new(2.0, PoolPumpSpeed::Med);
+        let handle = tokio::spawn(run(
+            pool_pump.clone(),
+            Arc::new(sensor),
+            protection.clone(),
+            1,
+        ));
+        sleep(Duration::from_millis(500)).await;
+        assert!(
+            pool_pump.requested().await
+                == (PoolPumpSpeed::Med, Some(ChangeSource::FreezeProtection))
+        );
+        if let Some(speed) = during {
+            pool_pump
+                .on(speed, ChangeSource::User { id: 2 })
+                .await
+                .unwrap();
+        }
+        sleep(Duration::from_millis(2000)).await;
+        handle.abort();
+
+        assert!(!protection.is_active());
+        assert!(pool_pump.requested().await == expected);
+    }
+}
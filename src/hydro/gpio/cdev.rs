@@ -0,0 +1,604 @@
+use anyhow::{anyhow, Error};
+use std::{
+    fs::{File, OpenOptions},
+    io, mem,
+    os::fd::{AsRawFd, FromRawFd, RawFd},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::hydro::gpio::{Gpio, InputPin, InterruptCallback, Level, OutputPin, Pin, Trigger};
+
+// The GPIO character device v2 uAPI, from linux/gpio.h
+
+const GPIO_V2_LINES_MAX: usize = 64;
+const GPIO_V2_LINE_NUM_ATTRS_MAX: usize = 10;
+const GPIO_MAX_NAME_SIZE: usize = 32;
+
+const GPIO_V2_LINE_FLAG_INPUT: u64 = 1 << 2;
+const GPIO_V2_LINE_FLAG_OUTPUT: u64 = 1 << 3;
+const GPIO_V2_LINE_FLAG_EDGE_RISING: u64 = 1 << 4;
+const GPIO_V2_LINE_FLAG_EDGE_FALLING: u64 = 1 << 5;
+const GPIO_V2_LINE_FLAG_BIAS_PULL_UP: u64 = 1 << 8;
+
+const GPIO_V2_LINE_ATTR_ID_OUTPUT_VALUES: u32 = 2;
+const GPIO_V2_LINE_EVENT_RISING_EDGE: u32 = 1;
+
+#[repr(C)]
+struct ChipInfo {
+    name: [u8; GPIO_MAX_NAME_SIZE],
+    label: [u8; GPIO_MAX_NAME_SIZE],
+    lines: u32,
+}
+
+#[repr(C)]
+struct LineValues {
+    bits: u64,
+    mask: u64,
+}
+
+/// The kernel's attribute is a union of flags, output values and a debounce period; only
+/// output values are set here.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct LineAttribute {
+    id: u32,
+    padding: u32,
+    values: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct LineConfigAttribute {
+    attr: LineAttribute,
+    mask: u64,
+}
+
+#[repr(C)]
+struct LineConfig {
+    flags: u64,
+    num_attrs: u32,
+    padding: [u32; 5],
+    attrs: [LineConfigAttribute; GPIO_V2_LINE_NUM_ATTRS_MAX],
+}
+
+#[repr(C)]
+struct LineRequest {
+    offsets: [u32; GPIO_V2_LINES_MAX],
+    consumer: [u8; GPIO_MAX_NAME_SIZE],
+    config: LineConfig,
+    num_lines: u32,
+    event_buffer_size: u32,
+    padding: [u32; 5],
+    fd: i32,
+}
+
+#[repr(C)]
+struct LineEvent {
+    timestamp_ns: u64,
+    id: u32,
+    offset: u32,
+    seqno: u32,
+    line_seqno: u32,
+    padding: [u32; 6],
+}
+
+const fn ioctl_number(direction: u64, nr: u64, size: usize) -> u64 {
+    (direction << 30) | ((size as u64) << 16) | (0xB4 << 8) | nr
+}
+
+const IOC_READ: u64 = 2;
+const IOC_READ_WRITE: u64 = 3;
+
+const GPIO_GET_CHIPINFO_IOCTL: u64 = ioctl_number(IOC_READ, 0x01, mem::size_of::<ChipInfo>());
+const GPIO_V2_GET_LINE_IOCTL: u64 =
+    ioctl_number(IOC_READ_WRITE, 0x07, mem::size_of::<LineRequest>());
+const GPIO_V2_LINE_SET_CONFIG_IOCTL: u64 =
+    ioctl_number(IOC_READ_WRITE, 0x0D, mem::size_of::<LineConfig>());
+const GPIO_V2_LINE_GET_VALUES_IOCTL: u64 =
+    ioctl_number(IOC_READ_WRITE, 0x0E, mem::size_of::<LineValues>());
+const GPIO_V2_LINE_SET_VALUES_IOCTL: u64 =
+    ioctl_number(IOC_READ_WRITE, 0x0F, mem::size_of::<LineValues>());
+
+/// Lines show up under this name in `gpioinfo`.
+const CONSUMER: &[u8] = b"rpsump";
+
+/// How long an interrupt thread waits for an edge before checking if its pin was dropped.
+const EVENT_POLL_MS: i32 = 200;
+
+fn ioctl<T>(fd: RawFd, request: u64, arg: &mut T) -> io::Result<()> {
+    // SAFETY: every request is paired with the struct the kernel expects for it
+    let result = unsafe { libc::ioctl(fd, request as _, arg as *mut T) };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Edge detection flags for `trigger`.
+fn edge_flags(trigger: Trigger) -> u64 {
+    match trigger {
+        Trigger::Disabled => 0,
+        Trigger::RisingEdge => GPIO_V2_LINE_FLAG_EDGE_RISING,
+        Trigger::FallingEdge => GPIO_V2_LINE_FLAG_EDGE_FALLING,
+        Trigger::Both => GPIO_V2_LINE_FLAG_EDGE_RISING | GPIO_V2_LINE_FLAG_EDGE_FALLING,
+    }
+}
+
+/// GPIO through a Linux character device such as `/dev/gpiochip0`, for boards rppal doesn't
+/// support. Pin numbers are the chip's line offsets.
+pub struct CdevGpio {
+    chip: File,
+    path: String,
+    lines: u32,
+}
+
+impl CdevGpio {
+    pub fn open(path: &str) -> Result<Self, Error> {
+        let chip = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(|e| anyhow!("Could not open {}: {}", path, e))?;
+
+        // SAFETY: ChipInfo is plain data, for which all zeroes is valid
+        let mut info: ChipInfo = unsafe { mem::zeroed() };
+        ioctl(chip.as_raw_fd(), GPIO_GET_CHIPINFO_IOCTL, &mut info)
+            .map_err(|e| anyhow!("{} is not a GPIO chip: {}", path, e))?;
+
+        Ok(Self {
+            chip,
+            path: path.to_string(),
+            lines: info.lines,
+        })
+    }
+}
+
+impl Gpio for CdevGpio {
+    fn get(&self, pin: u8) -> Result<Box<dyn Pin>, Error> {
+        if u32::from(pin) >= self.lines {
+            return Err(anyhow!("{} has no line {}", self.path, pin));
+        }
+
+        // Claim the line now so one in use fails here, as with rppal. Without a direction
+        // flag the line is left as it is until the pin is configured.
+        let line = Line::request(&self.chip, pin, 0)
+            .map_err(|e| anyhow!("Could not request line {} of {}: {}", pin, self.path, e))?;
+
+        Ok(Box::new(CdevPin { line }))
+    }
+}
+
+/// A requested line; the kernel releases it once its file is closed.
+struct Line {
+    file: File,
+    offset: u8,
+}
+
+impl Line {
+    fn request(chip: &File, offset: u8, flags: u64) -> io::Result<Self> {
+        // SAFETY: LineRequest is plain data, for which all zeroes is valid
+        let mut request: LineRequest = unsafe { mem::zeroed() };
+        request.offsets[0] = offset.into();
+        request.num_lines = 1;
+        request.consumer[..CONSUMER.len()].copy_from_slice(CONSUMER);
+        request.config.flags = flags;
+
+        ioctl(chip.as_raw_fd(), GPIO_V2_GET_LINE_IOCTL, &mut request)?;
+
+        Ok(Self {
+            // SAFETY: the kernel hands over a new file descriptor for the line
+            file: unsafe { File::from_raw_fd(request.fd) },
+            offset,
+        })
+    }
+
+    /// Reconfigures the line, setting its value too when it is an output.
+    fn configure(&self, flags: u64, value: Option<bool>) -> io::Result<()> {
+        // SAFETY: LineConfig is plain data, for which all zeroes is valid
+        let mut config: LineConfig = unsafe { mem::zeroed() };
+        config.flags = flags;
+        if let Some(value) = value {
+            config.num_attrs = 1;
+            config.attrs[0] = LineConfigAttribute {
+                attr: LineAttribute {
+                    id: GPIO_V2_LINE_ATTR_ID_OUTPUT_VALUES,
+                    padding: 0,
+                    values: value.into(),
+                },
+                mask: 1,
+            };
+        }
+
+        ioctl(
+            self.file.as_raw_fd(),
+            GPIO_V2_LINE_SET_CONFIG_IOCTL,
+            &mut config,
+        )
+    }
+
+    fn value(&self) -> io::Result<bool> {
+        let mut values = LineValues { bits: 0, mask: 1 };
+        ioctl(
+            self.file.as_raw_fd(),
+            GPIO_V2_LINE_GET_VALUES_IOCTL,
+            &mut values,
+        )?;
+
+        Ok(values.bits & 1 == 1)
+    }
+
+    fn set_value(&self, value: bool) -> io::Result<()> {
+        let mut values = LineValues {
+            bits: value.into(),
+            mask: 1,
+        };
+
+        ioctl(
+            self.file.as_raw_fd(),
+            GPIO_V2_LINE_SET_VALUES_IOCTL,
+            &mut values,
+        )
+    }
+
+    /// Waits up to `timeout_ms` for an edge, returning the level the line moved to.
+    fn next_edge(&self, timeout_ms: i32) -> io::Result<Option<Level>> {
+        let mut poll = libc::pollfd {
+            fd: self.file.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: a single, valid pollfd
+        let ready = unsafe { libc::poll(&mut poll, 1, timeout_ms) };
+        if ready < 0 {
+            let error = io::Error::last_os_error();
+            return match error.kind() {
+                io::ErrorKind::Interrupted => Ok(None),
+                _ => Err(error),
+            };
+        }
+        if ready == 0 {
+            return Ok(None);
+        }
+
+        // SAFETY: LineEvent is plain data, for which all zeroes is valid
+        let mut event: LineEvent = unsafe { mem::zeroed() };
+        let size = mem::size_of::<LineEvent>();
+        // SAFETY: reads at most `size` bytes into the event
+        let read = unsafe {
+            libc::read(
+                self.file.as_raw_fd(),
+                &mut event as *mut LineEvent as *mut libc::c_void,
+                size,
+            )
+        };
+        if read < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if read as usize != size {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Short read of a line event",
+            ));
+        }
+
+        Ok(Some(match event.id {
+            GPIO_V2_LINE_EVENT_RISING_EDGE => Level::High,
+            _ => Level::Low,
+        }))
+    }
+
+    fn log_error(&self, error: io::Error, message: &str) {
+        tracing::error!(
+            target = module_path!(),
+            error = error.to_string(),
+            line = self.offset,
+            message
+        );
+    }
+}
+
+pub struct CdevPin {
+    line: Line,
+}
+
+impl Pin for CdevPin {
+    fn into_input_pullup(self: Box<Self>) -> Box<dyn InputPin> {
+        let line = self.line;
+        if let Err(e) = line.configure(
+            GPIO_V2_LINE_FLAG_INPUT | GPIO_V2_LINE_FLAG_BIAS_PULL_UP,
+            None,
+        ) {
+            line.log_error(e, "Could not configure line as an input");
+        }
+
+        Box::new(CdevInputPin {
+            line: Arc::new(line),
+            stopped: Arc::new(AtomicBool::new(false)),
+            thread: None,
+        })
+    }
+
+    fn into_output_high(self: Box<Self>) -> Box<dyn OutputPin> {
+        self.into_output(true)
+    }
+
+    fn into_output_low(self: Box<Self>) -> Box<dyn OutputPin> {
+        self.into_output(false)
+    }
+}
+
+impl CdevPin {
+    fn into_output(self, value: bool) -> Box<dyn OutputPin> {
+        let line = self.line;
+        if let Err(e) = line.configure(GPIO_V2_LINE_FLAG_OUTPUT, Some(value)) {
+            line.log_error(e, "Could not configure line as an output");
+        }
+
+        Box::new(CdevOutputPin { line })
+    }
+}
+
+pub struct CdevInputPin {
+    line: Arc<Line>,
+    /// Tells the interrupt thread to let go of the line
+    stopped: Arc<AtomicBool>,
+    /// The interrupt thread, if one is listening
+    thread: Option<JoinHandle<()>>,
+}
+
+impl InputPin for CdevInputPin {
+    fn is_high(&self) -> bool {
+        self.read() == Level::High
+    }
+
+    fn is_low(&self) -> bool {
+        self.read() == Level::Low
+    }
+
+    fn read(&self) -> Level {
+        match self.line.value() {
+            Ok(true) => Level::High,
+            Ok(false) => Level::Low,
+            Err(e) => {
+                self.line.log_error(e, "Could not read line");
+                Level::Low
+            }
+        }
+    }
+
+    /// Reads edge events on a thread of their own, as rppal does.
+    fn set_interrupt(
+        &mut self,
+        trigger: Trigger,
+        mut callback: InterruptCallback,
+    ) -> Result<(), Error> {
+        // Replace any interrupt already listening on the line, waiting out its last poll so
+        // the two never read events at once
+        self.stopped.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        self.stopped = Arc::new(AtomicBool::new(false));
+
+        self.line.configure(
+            GPIO_V2_LINE_FLAG_INPUT | GPIO_V2_LINE_FLAG_BIAS_PULL_UP | edge_flags(trigger),
+            None,
+        )?;
+        if trigger == Trigger::Disabled {
+            return Ok(());
+        }
+
+        let line = Arc::clone(&self.line);
+        let stopped = Arc::clone(&self.stopped);
+
+        self.thread = Some(
+            thread::Builder::new()
+                .name(format!("gpio-line-{}", line.offset))
+                .spawn(move || {
+                    while !stopped.load(Ordering::Relaxed) {
+                        match line.next_edge(EVENT_POLL_MS) {
+                            Ok(Some(level)) => callback(level),
+                            Ok(None) => continue,
+                            Err(e) => {
+                                line.log_error(e, "Could not read line events");
+                                break;
+                            }
+                        }
+                    }
+                })?,
+        );
+
+        Ok(())
+    }
+}
+
+impl Drop for CdevInputPin {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+pub struct CdevOutputPin {
+    line: Line,
+}
+
+impl CdevOutputPin {
+    fn set(&self, value: bool) {
+        if let Err(e) = self.line.set_value(value) {
+            self.line.log_error(e, "Could not set line");
+        }
+    }
+}
+
+impl OutputPin for CdevOutputPin {
+    fn is_on(&self) -> bool {
+        matches!(self.line.value(), Ok(true))
+    }
+
+    fn is_off(&self) -> bool {
+        matches!(self.line.value(), Ok(false))
+    }
+
+    fn on(&mut self) {
+        self.set(true)
+    }
+
+    fn off(&mut self) {
+        self.set(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, process, sync::atomic::AtomicUsize, time::Duration};
+    use tokio::{runtime::Handle, sync::mpsc};
+
+    use super::*;
+    use crate::hydro::signal::{Message, Signal};
+
+    /// A chip from the kernel's gpio-sim module, removed again when dropped. Needs root and
+    /// configfs, so the tests using it are ignored unless run with `--ignored`.
+    struct GpioSim {
+        config: PathBuf,
+        device: PathBuf,
+        chip: String,
+    }
+
+    impl GpioSim {
+        fn new(lines: u8) -> Option<Self> {
+            static CHIPS: AtomicUsize = AtomicUsize::new(0);
+
+            let config = PathBuf::from(format!(
+                "/sys/kernel/config/gpio-sim/rpsump-{}-{}",
+                process::id(),
+                CHIPS.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir(&config).ok()?;
+            // Cleans up after itself from here on, should any step fail
+            let mut sim = Self {
+                config,
+                device: PathBuf::new(),
+                chip: String::new(),
+            };
+
+            let bank = sim.config.join("bank0");
+            fs::create_dir(&bank).ok()?;
+            fs::write(bank.join("num_lines"), lines.to_string()).ok()?;
+            fs::write(sim.config.join("live"), "1").ok()?;
+
+            let device_name = fs::read_to_string(sim.config.join("dev_name")).ok()?;
+            sim.chip = fs::read_to_string(bank.join("chip_name"))
+                .ok()?
+                .trim()
+                .to_string();
+            sim.device = PathBuf::from("/sys/devices/platform")
+                .join(device_name.trim())
+                .join(&sim.chip);
+
+            Some(sim)
+        }
+
+        fn path(&self) -> String {
+            format!("/dev/{}", self.chip)
+        }
+
+        /// Drives an input the way a switch to ground or a pull-up would.
+        fn pull(&self, offset: u8, level: Level) {
+            let pull = match level {
+                Level::High => "pull-up",
+                _ => "pull-down",
+            };
+            fs::write(self.line(offset).join("pull"), pull).unwrap();
+        }
+
+        fn value(&self, offset: u8) -> String {
+            fs::read_to_string(self.line(offset).join("value"))
+                .unwrap()
+                .trim()
+                .to_string()
+        }
+
+        fn line(&self, offset: u8) -> PathBuf {
+            self.device.join(format!("sim_gpio{}", offset))
+        }
+    }
+
+    impl Drop for GpioSim {
+        fn drop(&mut self) {
+            let _ = fs::write(self.config.join("live"), "0");
+            let _ = fs::remove_dir(self.config.join("bank0"));
+            let _ = fs::remove_dir(&self.config);
+        }
+    }
+
+    #[test]
+    fn test_uapi_layout() {
+        assert_eq!(mem::size_of::<LineRequest>(), 592);
+        assert_eq!(mem::size_of::<LineConfig>(), 272);
+        assert_eq!(mem::size_of::<LineEvent>(), 48);
+        assert_eq!(GPIO_GET_CHIPINFO_IOCTL, 0x8044_B401);
+        assert_eq!(GPIO_V2_GET_LINE_IOCTL, 0xC250_B407);
+        assert_eq!(GPIO_V2_LINE_SET_CONFIG_IOCTL, 0xC110_B40D);
+        assert_eq!(GPIO_V2_LINE_GET_VALUES_IOCTL, 0xC010_B40E);
+        assert_eq!(GPIO_V2_LINE_SET_VALUES_IOCTL, 0xC010_B40F);
+    }
+
+    #[test]
+    fn test_missing_chip() {
+        assert!(CdevGpio::open("/dev/gpiochip-missing").is_err());
+    }
+
+    #[test]
+    #[ignore = "needs the gpio-sim kernel module, configfs and root"]
+    fn test_gpio_sim_output() {
+        let sim = GpioSim::new(4).expect("Could not create a gpio-sim chip");
+        let gpio = CdevGpio::open(&sim.path()).unwrap();
+        let mut pin = gpio.get(1).unwrap().into_output_low();
+
+        assert!(gpio.get(1).is_err());
+        assert!(gpio.get(4).is_err());
+        assert!(sim.value(1) == "0");
+        assert!(pin.is_off());
+
+        pin.on();
+
+        assert!(sim.value(1) == "1");
+        assert!(pin.is_on());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the gpio-sim kernel module, configfs and root"]
+    async fn test_gpio_sim_input() {
+        let sim = GpioSim::new(4).expect("Could not create a gpio-sim chip");
+        let (tx, mut rx) = mpsc::channel(32);
+        let gpio = CdevGpio::open(&sim.path()).unwrap();
+        let mut pin = gpio.get(2).unwrap().into_input_pullup();
+        pin.set_async_interrupt(
+            Message::SumpEmpty,
+            Trigger::FallingEdge,
+            &tx,
+            Duration::from_millis(100),
+            Handle::current(),
+        )
+        .unwrap();
+
+        assert!(pin.is_high());
+
+        sim.pull(2, Level::Low);
+        let signal = tokio::time::timeout(Duration::from_secs(2), rx.recv()).await;
+
+        assert!(pin.is_low());
+        assert!(matches!(
+            signal,
+            Ok(Some(Signal {
+                message: Message::SumpEmpty,
+                ..
+            }))
+        ));
+    }
+}
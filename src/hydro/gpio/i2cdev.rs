@@ -0,0 +1,92 @@
+use anyhow::{anyhow, Error};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    os::fd::AsRawFd,
+};
+
+use crate::hydro::gpio::mcp23017::I2cBus;
+
+/// From linux/i2c-dev.h; takes the 7-bit address to talk to by value.
+const I2C_SLAVE: u64 = 0x0703;
+
+/// An I2C bus through the kernel's i2c-dev interface, such as `/dev/i2c-1`, for the boards
+/// the cdev GPIO backend serves.
+pub struct I2cDev {
+    bus: File,
+    path: String,
+    /// The device the bus was last pointed at
+    address: Option<u8>,
+}
+
+impl I2cDev {
+    /// Opens `/dev/i2c-<bus>`.
+    pub fn open(bus: u8) -> Result<Self, Error> {
+        let path = format!("/dev/i2c-{}", bus);
+        let bus = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .map_err(|e| anyhow!("Could not open {}: {}", path, e))?;
+
+        Ok(Self {
+            bus,
+            path,
+            address: None,
+        })
+    }
+
+    fn select(&mut self, address: u8) -> Result<(), Error> {
+        if self.address == Some(address) {
+            return Ok(());
+        }
+
+        // SAFETY: I2C_SLAVE reads its argument as an integer, not a pointer
+        let result = unsafe {
+            libc::ioctl(
+                self.bus.as_raw_fd(),
+                I2C_SLAVE as _,
+                libc::c_ulong::from(address),
+            )
+        };
+        if result < 0 {
+            return Err(anyhow!(
+                "Could not address {:#04x} on {}: {}",
+                address,
+                self.path,
+                io::Error::last_os_error()
+            ));
+        }
+        self.address = Some(address);
+
+        Ok(())
+    }
+}
+
+impl I2cBus for I2cDev {
+    fn read_register(&mut self, address: u8, register: u8) -> Result<u8, Error> {
+        self.select(address)?;
+
+        let mut value = [0];
+        self.bus.write_all(&[register])?;
+        self.bus.read_exact(&mut value)?;
+
+        Ok(value[0])
+    }
+
+    fn write_register(&mut self, address: u8, register: u8, value: u8) -> Result<(), Error> {
+        self.select(address)?;
+
+        Ok(self.bus.write_all(&[register, value])?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::I2cDev;
+
+    #[test]
+    fn test_missing_bus() {
+        assert!(I2cDev::open(250).is_err());
+    }
+}
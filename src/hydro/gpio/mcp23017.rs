@@ -0,0 +1,535 @@
+use anyhow::{anyhow, Error};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, MutexGuard, Weak},
+    thread,
+    time::Duration,
+};
+
+use crate::hydro::gpio::{Gpio, InputPin, InterruptCallback, Level, OutputPin, Pin, Trigger};
+
+// Register addresses with IOCON.BANK = 0, the power-on default. Each register has an A and
+// a B port, one after the other.
+const IODIR: u8 = 0x00;
+const GPPU: u8 = 0x0C;
+pub const GPIO: u8 = 0x12;
+pub const OLAT: u8 = 0x14;
+
+/// Pins on the expander: 0-7 on port A and 8-15 on port B.
+pub const PINS: u8 = 16;
+
+/// The I2C bus an expander is reached through.
+pub trait I2cBus: Send {
+    fn read_register(&mut self, address: u8, register: u8) -> Result<u8, Error>;
+    fn write_register(&mut self, address: u8, register: u8, value: u8) -> Result<(), Error>;
+}
+
+/// Called once the expander's lock is released, so the callback can read the pins.
+type SharedCallback = Arc<Mutex<InterruptCallback>>;
+
+struct Interrupt {
+    trigger: Trigger,
+    callback: SharedCallback,
+}
+
+/// The expander's state; registers are cached so a pin can be changed without reading
+/// the others back first.
+struct Expander {
+    bus: Box<dyn I2cBus>,
+    address: u8,
+    iodir: [u8; 2],
+    gppu: [u8; 2],
+    olat: [u8; 2],
+    claimed: u16,
+    interrupts: BTreeMap<u8, Interrupt>,
+    /// Input levels seen by the last poll
+    last_levels: Option<u16>,
+    polling: bool,
+}
+
+impl Expander {
+    fn write(
+        &mut self,
+        register: u8,
+        pin: u8,
+        cache: fn(&mut Self) -> &mut [u8; 2],
+    ) -> Result<(), Error> {
+        let port = usize::from(pin / 8);
+        let value = cache(self)[port];
+        self.bus
+            .write_register(self.address, register + port as u8, value)
+    }
+
+    fn set_bit(
+        &mut self,
+        register: u8,
+        pin: u8,
+        on: bool,
+        cache: fn(&mut Self) -> &mut [u8; 2],
+    ) -> Result<(), Error> {
+        let port = usize::from(pin / 8);
+        let bit = 1 << (pin % 8);
+        let registers = cache(self);
+        if on {
+            registers[port] |= bit;
+        } else {
+            registers[port] &= !bit;
+        }
+
+        self.write(register, pin, cache)
+    }
+
+    /// The level of every pin, port B in the high byte.
+    fn levels(&mut self) -> Result<u16, Error> {
+        let a = self.bus.read_register(self.address, GPIO)?;
+        let b = self.bus.read_register(self.address, GPIO + 1)?;
+
+        Ok(u16::from_le_bytes([a, b]))
+    }
+
+    fn level(&mut self, pin: u8) -> Result<Level, Error> {
+        let levels = self.levels()?;
+
+        Ok(level_of(levels, pin))
+    }
+}
+
+fn level_of(levels: u16, pin: u8) -> Level {
+    match levels & (1 << pin) {
+        0 => Level::Low,
+        _ => Level::High,
+    }
+}
+
+/// Whether `pin` moving from `previous` to `level` is an edge `trigger` fires on.
+fn fires(trigger: Trigger, previous: Level, level: Level) -> bool {
+    match trigger {
+        Trigger::Disabled => false,
+        Trigger::RisingEdge => previous == Level::Low && level == Level::High,
+        Trigger::FallingEdge => previous == Level::High && level == Level::Low,
+        Trigger::Both => previous != level,
+    }
+}
+
+/// An MCP23017 I2C port expander, for more pins than the board has. Inputs are polled, and
+/// changes are handed to the same debouncer as the board's pins.
+#[derive(Clone)]
+pub struct Mcp23017 {
+    expander: Arc<Mutex<Expander>>,
+    poll_interval: Duration,
+}
+
+impl Mcp23017 {
+    /// Resets every pin to an input without a pull-up, failing if the expander doesn't answer.
+    pub fn new(bus: Box<dyn I2cBus>, address: u8, poll_interval: Duration) -> Result<Self, Error> {
+        let mut expander = Expander {
+            bus,
+            address,
+            iodir: [0xFF; 2],
+            gppu: [0x00; 2],
+            olat: [0x00; 2],
+            claimed: 0,
+            interrupts: BTreeMap::new(),
+            last_levels: None,
+            polling: false,
+        };
+
+        for pin in [0, 8] {
+            expander
+                .write(IODIR, pin, |e| &mut e.iodir)
+                .and_then(|_| expander.write(GPPU, pin, |e| &mut e.gppu))
+                .and_then(|_| expander.write(OLAT, pin, |e| &mut e.olat))
+                .map_err(|e| anyhow!("No MCP23017 at {:#04x}: {}", address, e))?;
+        }
+
+        Ok(Self {
+            expander: Arc::new(Mutex::new(expander)),
+            poll_interval,
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Expander> {
+        self.expander.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Starts the thread that polls the inputs for interrupts, if it isn't running already.
+    fn poll(&self) -> Result<(), Error> {
+        let mut expander = self.lock();
+        if expander.polling {
+            return Ok(());
+        }
+
+        let weak = Arc::downgrade(&self.expander);
+        let poll_interval = self.poll_interval;
+        thread::Builder::new()
+            .name(format!("mcp23017-{:#04x}", expander.address))
+            .spawn(move || poll_inputs(weak, poll_interval))?;
+        expander.polling = true;
+
+        Ok(())
+    }
+}
+
+/// Reads the inputs every `poll_interval`, until the expander is dropped. A bus that stops
+/// answering is reported once, and again when it recovers, rather than on every poll.
+fn poll_inputs(expander: Weak<Mutex<Expander>>, poll_interval: Duration) {
+    let mut failed_polls: u64 = 0;
+
+    loop {
+        thread::sleep(poll_interval);
+        let Some(expander) = expander.upgrade() else {
+            return;
+        };
+
+        let mut lock = expander.lock().unwrap_or_else(|e| e.into_inner());
+        let levels = match lock.levels() {
+            Ok(levels) => levels,
+            Err(e) => {
+                if failed_polls == 0 {
+                    tracing::error!(
+                        target = module_path!(),
+                        error = e.to_string(),
+                        "Could not poll GPIO expander"
+                    );
+                }
+                failed_polls += 1;
+                continue;
+            }
+        };
+        if failed_polls > 0 {
+            tracing::info!(
+                target = module_path!(),
+                failed_polls,
+                "GPIO expander answering again"
+            );
+            failed_polls = 0;
+        }
+        let previous = lock.last_levels.replace(levels).unwrap_or(levels);
+
+        let fired: Vec<(SharedCallback, Level)> = lock
+            .interrupts
+            .iter()
+            .filter_map(|(pin, interrupt)| {
+                let level = level_of(levels, *pin);
+                fires(interrupt.trigger, level_of(previous, *pin), level)
+                    .then(|| (Arc::clone(&interrupt.callback), level))
+            })
+            .collect();
+        drop(lock);
+
+        for (callback, level) in fired {
+            (callback.lock().unwrap_or_else(|e| e.into_inner()))(level);
+        }
+    }
+}
+
+impl Gpio for Mcp23017 {
+    fn get(&self, pin: u8) -> Result<Box<dyn Pin>, Error> {
+        if pin >= PINS {
+            return Err(anyhow!("MCP23017 has no pin {}", pin));
+        }
+
+        let mut expander = self.lock();
+        if expander.claimed & (1 << pin) != 0 {
+            return Err(anyhow!("MCP23017 pin {} is already in use", pin));
+        }
+        expander.claimed |= 1 << pin;
+
+        Ok(Box::new(ExpanderPin {
+            claim: Claim {
+                mcp: self.clone(),
+                pin,
+            },
+        }))
+    }
+}
+
+/// A pin held by the application; released again when dropped.
+struct Claim {
+    mcp: Mcp23017,
+    pin: u8,
+}
+
+impl Claim {
+    fn log_error(&self, error: Error, message: &str) {
+        tracing::error!(
+            target = module_path!(),
+            error = error.to_string(),
+            pin = self.pin,
+            message
+        );
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        let mut expander = self.mcp.lock();
+        expander.claimed &= !(1 << self.pin);
+        expander.interrupts.remove(&self.pin);
+    }
+}
+
+pub struct ExpanderPin {
+    claim: Claim,
+}
+
+impl Pin for ExpanderPin {
+    fn into_input_pullup(self: Box<Self>) -> Box<dyn InputPin> {
+        let claim = self.claim;
+        let pin = claim.pin;
+        let configured = {
+            let mut expander = claim.mcp.lock();
+            expander
+                .set_bit(IODIR, pin, true, |e| &mut e.iodir)
+                .and_then(|_| expander.set_bit(GPPU, pin, true, |e| &mut e.gppu))
+        };
+        if let Err(e) = configured {
+            claim.log_error(e, "Could not configure expander pin as an input");
+        }
+
+        Box::new(ExpanderInputPin { claim })
+    }
+
+    fn into_output_high(self: Box<Self>) -> Box<dyn OutputPin> {
+        self.into_output(true)
+    }
+
+    fn into_output_low(self: Box<Self>) -> Box<dyn OutputPin> {
+        self.into_output(false)
+    }
+}
+
+impl ExpanderPin {
+    fn into_output(self, high: bool) -> Box<dyn OutputPin> {
+        let claim = self.claim;
+        let pin = claim.pin;
+        // Latch the level before switching direction so the pin never drives the other one
+        let configured = {
+            let mut expander = claim.mcp.lock();
+            expander
+                .set_bit(OLAT, pin, high, |e| &mut e.olat)
+                .and_then(|_| expander.set_bit(IODIR, pin, false, |e| &mut e.iodir))
+        };
+        if let Err(e) = configured {
+            claim.log_error(e, "Could not configure expander pin as an output");
+        }
+
+        Box::new(ExpanderOutputPin { claim })
+    }
+}
+
+pub struct ExpanderInputPin {
+    claim: Claim,
+}
+
+impl InputPin for ExpanderInputPin {
+    fn is_high(&self) -> bool {
+        self.read() == Level::High
+    }
+
+    fn is_low(&self) -> bool {
+        self.read() == Level::Low
+    }
+
+    fn read(&self) -> Level {
+        let level = self.claim.mcp.lock().level(self.claim.pin);
+        level.unwrap_or_else(|e| {
+            self.claim.log_error(e, "Could not read expander pin");
+            Level::Low
+        })
+    }
+
+    /// Fired from the thread polling the expander's inputs.
+    fn set_interrupt(
+        &mut self,
+        trigger: Trigger,
+        callback: InterruptCallback,
+    ) -> Result<(), Error> {
+        self.claim.mcp.lock().interrupts.insert(
+            self.claim.pin,
+            Interrupt {
+                trigger,
+                callback: Arc::new(Mutex::new(callback)),
+            },
+        );
+
+        self.claim.mcp.poll()
+    }
+}
+
+pub struct ExpanderOutputPin {
+    claim: Claim,
+}
+
+impl ExpanderOutputPin {
+    fn set(&mut self, on: bool) {
+        let pin = self.claim.pin;
+        let set = self
+            .claim
+            .mcp
+            .lock()
+            .set_bit(OLAT, pin, on, |e| &mut e.olat);
+        if let Err(e) = set {
+            self.claim.log_error(e, "Could not set expander pin");
+        }
+    }
+}
+
+impl OutputPin for ExpanderOutputPin {
+    fn is_on(&self) -> bool {
+        let level = self.claim.mcp.lock().level(self.claim.pin);
+        matches!(level, Ok(Level::High))
+    }
+
+    fn is_off(&self) -> bool {
+        let level = self.claim.mcp.lock().level(self.claim.pin);
+        matches!(level, Ok(Level::Low))
+    }
+
+    fn on(&mut self) {
+        self.set(true)
+    }
+
+    fn off(&mut self) {
+        self.set(false)
+    }
+}
+
+/// The board's GPIO with an expander alongside; pin numbers from `pin_base` up are the
+/// expander's, so one config can mix the two.
+pub struct ExpandedGpio {
+    board: Box<dyn Gpio>,
+    expander: Mcp23017,
+    pin_base: u8,
+}
+
+impl ExpandedGpio {
+    pub fn new(board: Box<dyn Gpio>, expander: Mcp23017, pin_base: u8) -> Self {
+        Self {
+            board,
+            expander,
+            pin_base,
+        }
+    }
+}
+
+impl Gpio for ExpandedGpio {
+    fn get(&self, pin: u8) -> Result<Box<dyn Pin>, Error> {
+        match pin.checked_sub(self.pin_base) {
+            Some(expander_pin) => self.expander.get(expander_pin),
+            None => self.board.get(pin),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use tokio::{runtime::Handle, sync::mpsc};
+
+    use super::{ExpandedGpio, Mcp23017, GPIO, OLAT};
+    use crate::hydro::{
+        gpio::{Gpio, Level, MockGpio, MockOutputPin, MockPin, Trigger},
+        signal::{Message, Signal},
+    };
+    use crate::test_fixtures::i2c::FakeMcp23017;
+
+    const ADDRESS: u8 = 0x20;
+
+    fn mcp(bus: &FakeMcp23017) -> Mcp23017 {
+        Mcp23017::new(Box::new(bus.clone()), ADDRESS, Duration::from_millis(10)).unwrap()
+    }
+
+    #[test]
+    fn test_missing_expander() {
+        let bus = FakeMcp23017::new(ADDRESS);
+
+        assert!(Mcp23017::new(Box::new(bus), 0x21, Duration::from_millis(10)).is_err());
+    }
+
+    #[test]
+    fn test_output() {
+        let bus = FakeMcp23017::new(ADDRESS);
+        let gpio = mcp(&bus);
+        let mut pin = gpio.get(9).unwrap().into_output_low();
+
+        assert!(gpio.get(9).is_err());
+        assert!(gpio.get(16).is_err());
+        assert!(pin.is_off());
+
+        pin.on();
+
+        assert!(bus.register(OLAT + 1) == 0b0000_0010);
+        assert!(bus.register(GPIO + 1) & 0b0000_0010 != 0);
+        assert!(pin.is_on());
+
+        drop(pin);
+
+        assert!(gpio.get(9).is_ok());
+    }
+
+    #[test]
+    fn test_input() {
+        let bus = FakeMcp23017::new(ADDRESS);
+        let gpio = mcp(&bus);
+        let pin = gpio.get(3).unwrap().into_input_pullup();
+
+        assert!(pin.is_high());
+
+        bus.set_input(3, Level::Low);
+
+        assert!(pin.is_low());
+    }
+
+    #[tokio::test]
+    async fn test_interrupt_debounced() {
+        let (tx, mut rx) = mpsc::channel(32);
+        let bus = FakeMcp23017::new(ADDRESS);
+        let gpio = mcp(&bus);
+        let mut pin = gpio.get(12).unwrap().into_input_pullup();
+        pin.set_async_interrupt(
+            Message::IrrigatorEmpty,
+            Trigger::FallingEdge,
+            &tx,
+            Duration::from_millis(200),
+            Handle::current(),
+        )
+        .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        bus.set_input(12, Level::Low);
+        let signal = tokio::time::timeout(Duration::from_secs(2), rx.recv()).await;
+
+        assert!(matches!(
+            signal,
+            Ok(Some(Signal {
+                message: Message::IrrigatorEmpty,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_mixed_pins() {
+        let bus = FakeMcp23017::new(ADDRESS);
+        let mut board = MockGpio::new();
+        board
+            .expect_get()
+            .withf(|pin| *pin == 5)
+            .times(1)
+            .returning(|_| {
+                let mut pin = MockPin::new();
+                pin.expect_into_output_low()
+                    .returning(|| Box::new(MockOutputPin::new()));
+                Ok(Box::new(pin))
+            });
+        let gpio = ExpandedGpio::new(Box::new(board), mcp(&bus), 100);
+
+        let _board_pin = gpio.get(5).unwrap().into_output_low();
+        let mut expander_pin = gpio.get(100).unwrap().into_output_low();
+        expander_pin.on();
+
+        assert!(bus.register(OLAT) == 0b0000_0001);
+        assert!(gpio.get(116).is_err());
+    }
+}
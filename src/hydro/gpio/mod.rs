@@ -1,15 +1,48 @@
-use anyhow::Error;
+use anyhow::{anyhow, Error};
 use mockall::automock;
-use std::{fmt, time::Duration};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr, sync::Arc, time::Duration};
 use tokio::{runtime::Handle, sync::mpsc::Sender};
 
-use crate::hydro::signal::Message;
+use crate::hydro::{
+    debounce::{handle_interrupt, Debouncer},
+    signal::Message,
+};
 
 use super::signal::Signal;
 
+pub mod cdev;
+pub mod i2cdev;
+pub mod mcp23017;
+pub mod record;
+pub mod replay;
 pub mod rppal;
+pub mod sim;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+/// The driver pins are switched through; the `stub` feature replaces it with `sim`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GpioBackend {
+    /// A Raspberry Pi's GPIO, through rppal
+    Rppal,
+    /// Any board's GPIO chip, through the Linux character device
+    Cdev,
+}
+
+impl FromStr for GpioBackend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rppal" => Ok(GpioBackend::Rppal),
+            "cdev" => Ok(GpioBackend::Cdev),
+            _ => Err(anyhow!("Invalid GPIO backend: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Level {
     Low = 0,
     High = 1,
@@ -24,6 +57,21 @@ pub enum Trigger {
     Both,
 }
 
+impl Trigger {
+    /// Whether a change to `level` is an edge the trigger fires on.
+    pub fn fires_on(&self, level: Level) -> bool {
+        matches!(
+            (self, level),
+            (Trigger::Both, _)
+                | (Trigger::RisingEdge, Level::High)
+                | (Trigger::FallingEdge, Level::Low)
+        )
+    }
+}
+
+/// Handed each level change that matches an interrupt's trigger, on a thread of the pin's own.
+pub type InterruptCallback = Box<dyn FnMut(Level) + Send>;
+
 #[automock]
 pub trait Gpio {
     fn get(&self, pin: u8) -> Result<Box<dyn Pin>, Error>;
@@ -32,6 +80,7 @@ pub trait Gpio {
 #[automock]
 pub trait Pin: Send + Sync {
     fn into_input_pullup(self: Box<Self>) -> Box<dyn InputPin>;
+    fn into_output_high(self: Box<Self>) -> Box<dyn OutputPin>;
     fn into_output_low(self: Box<Self>) -> Box<dyn OutputPin>;
 }
 
@@ -40,6 +89,13 @@ pub trait InputPin: Send + Sync {
     fn is_high(&self) -> bool;
     fn is_low(&self) -> bool;
     fn read(&self) -> Level;
+
+    /// Calls `callback` with every level change matching `trigger`, before any debouncing.
+    fn set_interrupt(&mut self, trigger: Trigger, callback: InterruptCallback)
+        -> Result<(), Error>;
+
+    /// Debounces the level changes matching `trigger`, sending `message` on `tx` once the
+    /// pin has settled for `delay`.
     fn set_async_interrupt(
         &mut self,
         message: Message,
@@ -47,7 +103,20 @@ pub trait InputPin: Send + Sync {
         tx: &Sender<Signal>,
         delay: Duration,
         handle: Handle,
-    ) -> Result<(), Error>;
+    ) -> Result<(), Error> {
+        let debouncer = Arc::from(tokio::sync::Mutex::new(Debouncer::new(
+            delay,
+            message,
+            tx.clone(),
+        )));
+
+        self.set_interrupt(
+            trigger,
+            Box::new(move |level| {
+                handle.block_on(handle_interrupt(Arc::clone(&debouncer), level));
+            }),
+        )
+    }
 }
 
 impl fmt::Debug for dyn InputPin {
@@ -0,0 +1,229 @@
+use anyhow::{anyhow, Error};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, LineWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use crate::hydro::gpio::{Gpio, InputPin, InterruptCallback, Level, OutputPin, Pin, Trigger};
+
+/// One raw level change, as a line of a recording.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordedEvent {
+    pub pin: u8,
+    pub level: Level,
+    /// Microseconds since the recording started, from a monotonic clock
+    pub elapsed_us: u64,
+}
+
+/// Writes every level change it is handed to a file, one JSON line each, timed from when the
+/// recorder was created. Clones share the file and the start time, so one recorder can be
+/// handed to every pin.
+#[derive(Clone)]
+pub struct Recorder {
+    start: Instant,
+    out: Arc<Mutex<LineWriter<File>>>,
+}
+
+impl Recorder {
+    /// Starts a recording at `path`, replacing any recording already there.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .map_err(|e| anyhow!("Could not create GPIO recording {:?}: {}", path, e))?;
+
+        Ok(Self {
+            start: Instant::now(),
+            out: Arc::new(Mutex::new(LineWriter::new(file))),
+        })
+    }
+
+    pub fn record(&self, pin: u8, level: Level) {
+        let event = RecordedEvent {
+            pin,
+            level,
+            elapsed_us: self.start.elapsed().as_micros() as u64,
+        };
+
+        let written = serde_json::to_string(&event)
+            .map_err(Error::from)
+            .and_then(|line| {
+                let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+                Ok(writeln!(out, "{}", line)?)
+            });
+        if let Err(e) = written {
+            tracing::error!(
+                target = module_path!(),
+                error = e.to_string(),
+                pin,
+                "Could not record GPIO event"
+            );
+        }
+    }
+}
+
+/// Reads a recording back, in the order it was written.
+pub fn read_recording(path: impl AsRef<Path>) -> Result<Vec<RecordedEvent>, Error> {
+    let path = path.as_ref();
+    let file =
+        File::open(path).map_err(|e| anyhow!("Could not open GPIO recording {:?}: {}", path, e))?;
+
+    BufReader::new(file)
+        .lines()
+        .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .collect()
+}
+
+/// Records the level changes of every input pin claimed through it.
+pub struct RecordingGpio {
+    inner: Box<dyn Gpio>,
+    recorder: Recorder,
+}
+
+impl RecordingGpio {
+    pub fn new(inner: Box<dyn Gpio>, recorder: Recorder) -> Self {
+        Self { inner, recorder }
+    }
+}
+
+impl Gpio for RecordingGpio {
+    fn get(&self, pin: u8) -> Result<Box<dyn Pin>, Error> {
+        Ok(Box::new(RecordingPin {
+            inner: self.inner.get(pin)?,
+            number: pin,
+            recorder: self.recorder.clone(),
+        }))
+    }
+}
+
+struct RecordingPin {
+    inner: Box<dyn Pin>,
+    number: u8,
+    recorder: Recorder,
+}
+
+impl Pin for RecordingPin {
+    fn into_input_pullup(self: Box<Self>) -> Box<dyn InputPin> {
+        Box::new(RecordingInputPin::new(
+            self.inner.into_input_pullup(),
+            self.number,
+            self.recorder,
+        ))
+    }
+
+    fn into_output_high(self: Box<Self>) -> Box<dyn OutputPin> {
+        self.inner.into_output_high()
+    }
+
+    fn into_output_low(self: Box<Self>) -> Box<dyn OutputPin> {
+        self.inner.into_output_low()
+    }
+}
+
+/// Wraps an input pin, recording every level change it sees whatever the interrupt's trigger,
+/// and passing on those the trigger fires on.
+pub struct RecordingInputPin {
+    inner: Box<dyn InputPin>,
+    number: u8,
+    recorder: Recorder,
+}
+
+impl RecordingInputPin {
+    pub fn new(inner: Box<dyn InputPin>, number: u8, recorder: Recorder) -> Self {
+        Self {
+            inner,
+            number,
+            recorder,
+        }
+    }
+}
+
+impl InputPin for RecordingInputPin {
+    fn is_high(&self) -> bool {
+        self.inner.is_high()
+    }
+
+    fn is_low(&self) -> bool {
+        self.inner.is_low()
+    }
+
+    fn read(&self) -> Level {
+        self.inner.read()
+    }
+
+    fn set_interrupt(
+        &mut self,
+        trigger: Trigger,
+        mut callback: InterruptCallback,
+    ) -> Result<(), Error> {
+        if trigger == Trigger::Disabled {
+            return self.inner.set_interrupt(trigger, callback);
+        }
+
+        let number = self.number;
+        let recorder = self.recorder.clone();
+        self.inner.set_interrupt(
+            Trigger::Both,
+            Box::new(move |level| {
+                recorder.record(number, level);
+                if trigger.fires_on(level) {
+                    callback(level);
+                }
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+    use tempfile::tempdir;
+
+    use super::{read_recording, Recorder, RecordingGpio};
+    use crate::hydro::gpio::{sim::SimGpio, Gpio, Level, Trigger};
+
+    #[test]
+    fn test_records_every_change() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("gpio.jsonl");
+        let sim = SimGpio::new();
+        let gpio = RecordingGpio::new(Box::new(sim.clone()), Recorder::create(&path).unwrap());
+        let fired = Arc::new(Mutex::new(vec![]));
+
+        let mut pin = gpio.get(14).unwrap().into_input_pullup();
+        let callback_fired = Arc::clone(&fired);
+        pin.set_interrupt(
+            Trigger::RisingEdge,
+            Box::new(move |level| callback_fired.lock().unwrap().push(level)),
+        )
+        .unwrap();
+        for level in [Level::Low, Level::High, Level::Low, Level::High] {
+            sim.set_input(14, level).unwrap();
+        }
+        std::thread::sleep(Duration::from_millis(200));
+
+        let events = read_recording(&path).unwrap();
+        let levels: Vec<Level> = events.iter().map(|event| event.level).collect();
+
+        assert!(levels == vec![Level::Low, Level::High, Level::Low, Level::High]);
+        assert!(events.iter().all(|event| event.pin == 14));
+        assert!(events
+            .windows(2)
+            .all(|pair| pair[0].elapsed_us <= pair[1].elapsed_us));
+        // The interrupt only sees the edges it asked for
+        assert!(*fired.lock().unwrap() == vec![Level::High, Level::High]);
+    }
+
+    #[test]
+    fn test_missing_recording() {
+        assert!(read_recording("/nonexistent/gpio.jsonl").is_err());
+    }
+}
@@ -0,0 +1,347 @@
+use anyhow::Error;
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+use tokio::{
+    runtime::Handle,
+    sync::mpsc::Sender,
+    task::JoinHandle,
+    time::{sleep_until, Instant},
+};
+
+use crate::hydro::{
+    debounce::{handle_interrupt, Debouncer},
+    gpio::{
+        record::{read_recording, RecordedEvent},
+        Gpio, InputPin, InterruptCallback, Level, OutputPin, Pin, Trigger,
+    },
+    signal::{Message, Signal},
+};
+
+/// Plays a recording back through the pins it was made on, starting from each pin's
+/// interrupt being set. Debounced interrupts play on the runtime's clock, so under a paused
+/// clock a field capture replays the same way every time. Outputs keep whatever level they
+/// were last set to.
+#[derive(Clone)]
+pub struct ReplayGpio {
+    events: Arc<Vec<RecordedEvent>>,
+}
+
+impl ReplayGpio {
+    /// Replays `events` in time order, timed from the earliest. Events at the same time keep
+    /// the order they were given in.
+    pub fn new(mut events: Vec<RecordedEvent>) -> Self {
+        events.sort_by_key(|event| event.elapsed_us);
+        let first_us = events.first().map_or(0, |event| event.elapsed_us);
+        for event in events.iter_mut() {
+            event.elapsed_us -= first_us;
+        }
+
+        Self {
+            events: Arc::new(events),
+        }
+    }
+
+    /// Replays a recording made by a `Recorder`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Ok(Self::new(read_recording(path)?))
+    }
+}
+
+impl Gpio for ReplayGpio {
+    fn get(&self, pin: u8) -> Result<Box<dyn Pin>, Error> {
+        Ok(Box::new(ReplayPin {
+            events: self
+                .events
+                .iter()
+                .filter(|event| event.pin == pin)
+                .copied()
+                .collect(),
+        }))
+    }
+}
+
+struct ReplayPin {
+    events: Vec<RecordedEvent>,
+}
+
+impl Pin for ReplayPin {
+    fn into_input_pullup(self: Box<Self>) -> Box<dyn InputPin> {
+        // The pin sat at the other level until its first change
+        let level = match self.events.first() {
+            Some(event) if event.level == Level::High => Level::Low,
+            _ => Level::High,
+        };
+
+        Box::new(ReplayInputPin {
+            events: self.events,
+            level: Arc::new(Mutex::new(level)),
+            task: None,
+            stopped: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    fn into_output_high(self: Box<Self>) -> Box<dyn OutputPin> {
+        Box::new(ReplayOutputPin { on: true })
+    }
+
+    fn into_output_low(self: Box<Self>) -> Box<dyn OutputPin> {
+        Box::new(ReplayOutputPin { on: false })
+    }
+}
+
+pub struct ReplayInputPin {
+    events: Vec<RecordedEvent>,
+    level: Arc<Mutex<Level>>,
+    /// Plays debounced interrupts on the runtime
+    task: Option<JoinHandle<()>>,
+    /// Stops a playback thread
+    stopped: Arc<AtomicBool>,
+}
+
+impl ReplayInputPin {
+    /// Stops any playback already under way.
+    fn stop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+        self.stopped.store(true, Ordering::Relaxed);
+        self.stopped = Arc::new(AtomicBool::new(false));
+    }
+}
+
+impl InputPin for ReplayInputPin {
+    fn is_high(&self) -> bool {
+        self.read() == Level::High
+    }
+
+    fn is_low(&self) -> bool {
+        self.read() == Level::Low
+    }
+
+    fn read(&self) -> Level {
+        *self.level.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Plays the recording on a thread of its own in real time, as the hardware would.
+    fn set_interrupt(
+        &mut self,
+        trigger: Trigger,
+        mut callback: InterruptCallback,
+    ) -> Result<(), Error> {
+        self.stop();
+
+        let events = self.events.clone();
+        let level = Arc::clone(&self.level);
+        let stopped = Arc::clone(&self.stopped);
+        let start = std::time::Instant::now();
+
+        thread::Builder::new()
+            .name("gpio-replay".to_string())
+            .spawn(move || {
+                for event in events {
+                    let due = start + Duration::from_micros(event.elapsed_us);
+                    thread::sleep(due.saturating_duration_since(std::time::Instant::now()));
+                    if stopped.load(Ordering::Relaxed) {
+                        return;
+                    }
+
+                    *level.lock().unwrap_or_else(|e| e.into_inner()) = event.level;
+                    if trigger.fires_on(event.level) {
+                        callback(event.level);
+                    }
+                }
+            })?;
+
+        Ok(())
+    }
+
+    /// Plays the recording on the runtime, straight into the debouncer, so it follows the
+    /// runtime's clock rather than the wall clock.
+    fn set_async_interrupt(
+        &mut self,
+        message: Message,
+        trigger: Trigger,
+        tx: &Sender<Signal>,
+        delay: Duration,
+        handle: Handle,
+    ) -> Result<(), Error> {
+        self.stop();
+
+        let debouncer = Arc::from(tokio::sync::Mutex::new(Debouncer::new(
+            delay,
+            message,
+            tx.clone(),
+        )));
+        let events = self.events.clone();
+        let level = Arc::clone(&self.level);
+
+        self.task = Some(handle.spawn(async move {
+            let start = Instant::now();
+            for event in events {
+                sleep_until(start + Duration::from_micros(event.elapsed_us)).await;
+
+                *level.lock().unwrap_or_else(|e| e.into_inner()) = event.level;
+                if trigger.fires_on(event.level) {
+                    handle_interrupt(Arc::clone(&debouncer), event.level).await;
+                }
+            }
+        }));
+
+        Ok(())
+    }
+}
+
+impl Drop for ReplayInputPin {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+pub struct ReplayOutputPin {
+    on: bool,
+}
+
+impl OutputPin for ReplayOutputPin {
+    fn is_on(&self) -> bool {
+        self.on
+    }
+
+    fn is_off(&self) -> bool {
+        !self.on
+    }
+
+    fn on(&mut self) {
+        self.on = true;
+    }
+
+    fn off(&mut self) {
+        self.on = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        runtime::Handle,
+        sync::mpsc,
+        time::{sleep, Duration, Instant},
+    };
+
+    use super::ReplayGpio;
+    use crate::hydro::{
+        control::{Control, Output},
+        gpio::{record::RecordedEvent, Gpio, Level, Trigger},
+        signal::{listen, Message, Signal},
+        sump::Sump,
+        watchdog::Health,
+    };
+    use crate::repository::{
+        models::sump_event::{PUMP_OFF, PUMP_ON},
+        MockRepository,
+    };
+    use crate::test_fixtures::settings::SETTINGS;
+
+    /// The sump filling with the high float bouncing, then draining until the low float rises.
+    const SUMP_CYCLE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/gpio/sump_cycle.jsonl"
+    );
+
+    #[test]
+    fn test_events_sorted() {
+        let event = |pin, elapsed_us| RecordedEvent {
+            pin,
+            level: Level::High,
+            elapsed_us,
+        };
+
+        let gpio = ReplayGpio::new(vec![event(5, 900), event(18, 400), event(5, 400)]);
+
+        assert!(*gpio.events == vec![event(18, 0), event(5, 0), event(5, 500)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_chatter_debounced() {
+        let (tx, mut rx) = mpsc::channel(32);
+        let gpio = ReplayGpio::open(SUMP_CYCLE).unwrap();
+        let mut pin = gpio.get(5).unwrap().into_input_pullup();
+        let start = Instant::now();
+
+        assert!(pin.is_low());
+
+        pin.set_async_interrupt(
+            Message::SumpFull,
+            Trigger::RisingEdge,
+            &tx,
+            Duration::from_secs(2),
+            Handle::current(),
+        )
+        .unwrap();
+        let signal = rx.recv().await;
+        let signalled_after = start.elapsed();
+        sleep(Duration::from_secs(10)).await;
+
+        // Two seconds after the float last rose, at 0.9s, and only the once
+        assert!(
+            signal
+                == Some(Signal {
+                    message: Message::SumpFull,
+                    level: Level::High
+                })
+        );
+        assert!(signalled_after == Duration::from_millis(2900));
+        assert!(rx.try_recv().is_err());
+        assert!(pin.is_low());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_listen_replays_sump_cycle() {
+        let (tx, rx) = mpsc::channel(32);
+        let gpio = ReplayGpio::open(SUMP_CYCLE).unwrap();
+        let config = &SETTINGS.hydro.sump;
+
+        let mut mock_repo = MockRepository::new();
+        let mut sequence = mockall::Sequence::new();
+        for info in [PUMP_ON, PUMP_OFF] {
+            mock_repo
+                .expect_create_sump_event()
+                .withf(move |recorded, _| recorded.as_str() == info)
+                .times(1)
+                .in_sequence(&mut sequence)
+                .returning(|_, _| Ok(()));
+        }
+        let repo = Box::leak(Box::new(mock_repo));
+
+        let sump = Sump::new(config, &tx, Handle::current(), &gpio).unwrap();
+        let irrigator_pump = Control::new("Irrigation Pump".into(), 24, &gpio).unwrap();
+        let _listener = listen(
+            rx,
+            Handle::current(),
+            repo,
+            irrigator_pump,
+            sump.pump.clone(),
+            config.pump_shutoff_delay,
+            config.pump_max_runtime,
+            Health::default().register("signals"),
+        );
+
+        // Full at 2.9s; empty at 10.1s, then the pump clears the hose for two seconds
+        sleep(Duration::from_secs(3)).await;
+        let filled = sump.pump.is_on().await;
+        sleep(Duration::from_secs(8)).await;
+        let clearing = sump.pump.is_on().await;
+        sleep(Duration::from_secs(2)).await;
+        let emptied = sump.pump.is_off().await;
+
+        assert!(filled);
+        assert!(clearing);
+        assert!(emptied);
+    }
+}
@@ -1,14 +1,7 @@
 use anyhow::Error;
-use std::{sync::Arc, time::Duration};
-use tokio::{
-    runtime::Handle,
-    sync::{mpsc::Sender, Mutex},
-};
 
-use crate::hydro::{
-    debounce::Debouncer,
-    gpio::{Gpio, InputPin, OutputPin, Pin, Trigger},
-    signal::{Message, Signal},
+use crate::hydro::gpio::{
+    mcp23017::I2cBus, Gpio, InputPin, InterruptCallback, OutputPin, Pin, Trigger,
 };
 
 impl Gpio for rppal::gpio::Gpio {
@@ -23,6 +16,9 @@ impl Pin for rppal::gpio::Pin {
     fn into_input_pullup(self: Box<Self>) -> Box<dyn InputPin> {
         Box::new(rppal::gpio::Pin::into_input_pullup(*self))
     }
+    fn into_output_high(self: Box<Self>) -> Box<dyn OutputPin> {
+        Box::new(rppal::gpio::Pin::into_output_high(*self))
+    }
     fn into_output_low(self: Box<Self>) -> Box<dyn OutputPin> {
         Box::new(rppal::gpio::Pin::into_output_low(*self))
     }
@@ -41,50 +37,17 @@ impl InputPin for rppal::gpio::InputPin {
         self.read().into()
     }
 
-    /// Wrapper around rppal's set_async_interrupt to allow for use of a shared
-    /// debouncer for each interrupt
-    ///
-    /// # Arguments
-    ///
-    /// * `name`    - The name of the pin; for logging and reporting
-    /// * `trigger` - The level(s) that trigger the interrupt
-    /// * `handle`  - The tokio runtime handle to use for async operations
-    /// * `tx`      - The channel to report triggers to the main channel
-    /// * `delay`   - The delay to use for debouncing the interrupt
-    fn set_async_interrupt(
+    /// Wrapper around rppal's set_async_interrupt, which calls back on a thread of its own
+    fn set_interrupt(
         &mut self,
-        message: Message,
         trigger: Trigger,
-        tx: &Sender<Signal>,
-        delay: Duration,
-        handle: Handle,
+        mut callback: InterruptCallback,
     ) -> Result<(), Error> {
-        let message = message.clone();
-        let tx = tx.clone();
-        //let debouncer: Arc<Mutex<Debouncer>> = Arc::new(Mutex::new(None));
-        let debouncer = Arc::from(Mutex::new(Debouncer::new(
-            delay,
-            message.clone(),
-            tx.clone(),
-        )));
-
-        let callback = move |level: rppal::gpio::Level| {
-            handle.block_on(handle_interrupt(Arc::clone(&debouncer), level));
-        };
-
-        Ok(self.set_async_interrupt(trigger.into(), callback)?)
-    }
-}
-
-async fn handle_interrupt(debouncer: Arc<Mutex<Debouncer>>, level: rppal::gpio::Level) {
-    let mut debouncer = debouncer.lock().await;
-    let running = debouncer.running.lock().await;
-    if *running {
-        drop(running);
-        debouncer.reset_deadline(level.into()).await;
-    } else {
-        drop(running);
-        debouncer.start().await;
+        Ok(
+            self.set_async_interrupt(trigger.into(), move |level: rppal::gpio::Level| {
+                callback(level.into())
+            })?,
+        )
     }
 }
 
@@ -125,3 +88,17 @@ impl From<rppal::gpio::Level> for crate::hydro::Level {
         }
     }
 }
+
+impl I2cBus for rppal::i2c::I2c {
+    fn read_register(&mut self, address: u8, register: u8) -> Result<u8, Error> {
+        self.set_slave_address(address.into())?;
+
+        Ok(self.smbus_read_byte(register)?)
+    }
+
+    fn write_register(&mut self, address: u8, register: u8, value: u8) -> Result<(), Error> {
+        self.set_slave_address(address.into())?;
+
+        Ok(self.smbus_write_byte(register, value)?)
+    }
+}
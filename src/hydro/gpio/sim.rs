@@ -0,0 +1,398 @@
+use anyhow::{anyhow, Error};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use crate::hydro::gpio::{Gpio, InputPin, InterruptCallback, Level, OutputPin, Pin, Trigger};
+
+type PinStates = Arc<Mutex<BTreeMap<u8, PinState>>>;
+
+/// What a simulated pin has been claimed as.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PinMode {
+    Unconfigured,
+    Input,
+    Output,
+}
+
+struct PinState {
+    /// Unset while no one holds the pin
+    mode: Option<PinMode>,
+    level: Level,
+    trigger: Trigger,
+    /// Feeds level changes to the pin's interrupt thread
+    interrupt: Option<mpsc::Sender<Level>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SimPinStatus {
+    pub pin: u8,
+    /// Unset while no one holds the pin
+    pub mode: Option<PinMode>,
+    pub level: Level,
+}
+
+/// Unclaimed pins read high, as if pulled up with nothing attached.
+impl Default for PinState {
+    fn default() -> Self {
+        Self {
+            mode: None,
+            level: Level::High,
+            trigger: Trigger::Disabled,
+            interrupt: None,
+        }
+    }
+}
+
+/// An in-memory GPIO for running without a Raspberry Pi. Outputs keep whatever level they
+/// were last set to, and inputs read whatever `set_input` last drove them to. Input changes
+/// fire interrupts through the same debouncer as the rppal pins.
+#[derive(Clone, Default)]
+pub struct SimGpio {
+    pins: PinStates,
+}
+
+impl SimGpio {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drives an input pin to `level`, firing its interrupt when the change matches the
+    /// trigger. Pins can be driven before they are claimed to set the level they start at.
+    pub fn set_input(&self, pin: u8, level: Level) -> Result<(), Error> {
+        if level == Level::Both {
+            return Err(anyhow!("Pin {} can only be driven high or low", pin));
+        }
+
+        let mut pins = self.pins.lock().map_err(|e| anyhow!(e.to_string()))?;
+        let state = pins.entry(pin).or_default();
+        if state.mode == Some(PinMode::Output) {
+            return Err(anyhow!("Pin {} is an output", pin));
+        }
+
+        let previous = state.level;
+        state.level = level;
+
+        if fires(state.trigger, previous, level) {
+            if let Some(interrupt) = &state.interrupt {
+                // The interrupt thread is gone once the pin is released
+                let _ = interrupt.send(level);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The level a pin is at, if it has been claimed or driven.
+    pub fn level(&self, pin: u8) -> Option<Level> {
+        self.pins.lock().ok()?.get(&pin).map(|state| state.level)
+    }
+
+    /// Every pin that has been claimed or driven, in pin order.
+    pub fn pins(&self) -> Vec<SimPinStatus> {
+        let Ok(pins) = self.pins.lock() else {
+            return vec![];
+        };
+
+        pins.iter()
+            .map(|(pin, state)| SimPinStatus {
+                pin: *pin,
+                mode: state.mode,
+                level: state.level,
+            })
+            .collect()
+    }
+
+    /// What a pin is claimed as, if anything.
+    pub fn mode(&self, pin: u8) -> Option<PinMode> {
+        self.pins
+            .lock()
+            .ok()?
+            .get(&pin)
+            .and_then(|state| state.mode)
+    }
+}
+
+/// Whether moving from `previous` to `level` is an edge `trigger` fires on.
+fn fires(trigger: Trigger, previous: Level, level: Level) -> bool {
+    matches!(
+        (trigger, previous, level),
+        (Trigger::RisingEdge | Trigger::Both, Level::Low, Level::High)
+            | (
+                Trigger::FallingEdge | Trigger::Both,
+                Level::High,
+                Level::Low
+            )
+    )
+}
+
+impl Gpio for SimGpio {
+    fn get(&self, pin: u8) -> Result<Box<dyn Pin>, Error> {
+        let mut pins = self.pins.lock().map_err(|e| anyhow!(e.to_string()))?;
+        let state = pins.entry(pin).or_default();
+        if state.mode.is_some() {
+            return Err(anyhow!("Pin {} is already in use", pin));
+        }
+        state.mode = Some(PinMode::Unconfigured);
+
+        Ok(Box::new(SimPin {
+            claim: Claim {
+                number: pin,
+                pins: Arc::clone(&self.pins),
+            },
+        }))
+    }
+}
+
+/// A pin held by the application; released again when dropped, like rppal's pins.
+struct Claim {
+    number: u8,
+    pins: PinStates,
+}
+
+impl Claim {
+    fn with<T>(&self, f: impl FnOnce(&mut PinState) -> T) -> T {
+        let mut pins = self.pins.lock().unwrap_or_else(|e| e.into_inner());
+        f(pins.entry(self.number).or_default())
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        self.with(|state| {
+            state.mode = None;
+            state.trigger = Trigger::Disabled;
+            state.interrupt = None;
+        });
+    }
+}
+
+pub struct SimPin {
+    claim: Claim,
+}
+
+impl Pin for SimPin {
+    fn into_input_pullup(self: Box<Self>) -> Box<dyn InputPin> {
+        let claim = self.claim;
+        claim.with(|state| state.mode = Some(PinMode::Input));
+
+        Box::new(SimInputPin { claim })
+    }
+
+    fn into_output_high(self: Box<Self>) -> Box<dyn OutputPin> {
+        self.into_output(Level::High)
+    }
+
+    fn into_output_low(self: Box<Self>) -> Box<dyn OutputPin> {
+        self.into_output(Level::Low)
+    }
+}
+
+impl SimPin {
+    fn into_output(self, level: Level) -> Box<dyn OutputPin> {
+        let claim = self.claim;
+        claim.with(|state| {
+            state.mode = Some(PinMode::Output);
+            state.level = level;
+        });
+
+        Box::new(SimOutputPin { claim })
+    }
+}
+
+pub struct SimInputPin {
+    claim: Claim,
+}
+
+impl InputPin for SimInputPin {
+    fn is_high(&self) -> bool {
+        self.read() == Level::High
+    }
+
+    fn is_low(&self) -> bool {
+        self.read() == Level::Low
+    }
+
+    fn read(&self) -> Level {
+        self.claim.with(|state| state.level)
+    }
+
+    /// Runs interrupts on a thread of their own, as rppal does, so `set_input` can be called
+    /// from within the runtime.
+    fn set_interrupt(
+        &mut self,
+        trigger: Trigger,
+        mut callback: InterruptCallback,
+    ) -> Result<(), Error> {
+        let (interrupt, levels) = mpsc::channel::<Level>();
+
+        thread::Builder::new()
+            .name(format!("sim-gpio-{}", self.claim.number))
+            .spawn(move || {
+                for level in levels {
+                    callback(level);
+                }
+            })?;
+
+        self.claim.with(|state| {
+            state.trigger = trigger;
+            state.interrupt = Some(interrupt);
+        });
+
+        Ok(())
+    }
+}
+
+pub struct SimOutputPin {
+    claim: Claim,
+}
+
+impl OutputPin for SimOutputPin {
+    fn is_on(&self) -> bool {
+        self.claim.with(|state| state.level == Level::High)
+    }
+
+    fn is_off(&self) -> bool {
+        self.claim.with(|state| state.level == Level::Low)
+    }
+
+    fn on(&mut self) {
+        self.claim.with(|state| state.level = Level::High)
+    }
+
+    fn off(&mut self) {
+        self.claim.with(|state| state.level = Level::Low)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use tokio::{runtime::Handle, sync::mpsc};
+
+    use super::{PinMode, SimGpio, SimPinStatus};
+    use crate::hydro::{
+        gpio::{Gpio, Level, Trigger},
+        signal::{Message, Signal},
+    };
+
+    #[test]
+    fn test_output_levels() {
+        let gpio = SimGpio::new();
+        let mut pin = gpio.get(5).unwrap().into_output_low();
+
+        assert!(gpio.mode(5) == Some(PinMode::Output));
+        assert!(gpio.level(5) == Some(Level::Low));
+        assert!(pin.is_off());
+
+        pin.on();
+
+        assert!(gpio.level(5) == Some(Level::High));
+        assert!(pin.is_on());
+        assert!(gpio.set_input(5, Level::Low).is_err());
+    }
+
+    #[test]
+    fn test_pins() {
+        let gpio = SimGpio::new();
+        let _output = gpio.get(11).unwrap().into_output_low();
+        gpio.set_input(3, Level::Low).unwrap();
+
+        assert!(
+            gpio.pins()
+                == vec![
+                    SimPinStatus {
+                        pin: 3,
+                        mode: None,
+                        level: Level::Low
+                    },
+                    SimPinStatus {
+                        pin: 11,
+                        mode: Some(PinMode::Output),
+                        level: Level::Low
+                    },
+                ]
+        );
+    }
+
+    #[test]
+    fn test_pin_released_on_drop() {
+        let gpio = SimGpio::new();
+        let pin = gpio.get(5).unwrap();
+
+        assert!(gpio.get(5).is_err());
+
+        drop(pin);
+
+        assert!(gpio.mode(5).is_none());
+        assert!(gpio.get(5).is_ok());
+    }
+
+    #[test]
+    fn test_input_driven_before_claim() {
+        let gpio = SimGpio::new();
+        gpio.set_input(7, Level::Low).unwrap();
+
+        let pin = gpio.get(7).unwrap().into_input_pullup();
+
+        assert!(pin.is_low());
+        gpio.set_input(7, Level::High).unwrap();
+        assert!(pin.read() == Level::High);
+    }
+
+    #[tokio::test]
+    async fn test_interrupt_debounced() {
+        let (tx, mut rx) = mpsc::channel(32);
+        let gpio = SimGpio::new();
+        let mut pin = gpio.get(14).unwrap().into_input_pullup();
+        pin.set_async_interrupt(
+            Message::SumpFull,
+            Trigger::RisingEdge,
+            &tx,
+            Duration::from_millis(200),
+            Handle::current(),
+        )
+        .unwrap();
+
+        // Bouncing water only reports once
+        for _ in 0..3 {
+            gpio.set_input(14, Level::Low).unwrap();
+            gpio.set_input(14, Level::High).unwrap();
+        }
+        let signal = tokio::time::timeout(Duration::from_secs(2), rx.recv()).await;
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        assert!(matches!(
+            signal,
+            Ok(Some(Signal {
+                message: Message::SumpFull,
+                ..
+            }))
+        ));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_interrupt_ignores_other_edge() {
+        let (tx, mut rx) = mpsc::channel(32);
+        let gpio = SimGpio::new();
+        let mut pin = gpio.get(14).unwrap().into_input_pullup();
+        pin.set_async_interrupt(
+            Message::SumpFull,
+            Trigger::RisingEdge,
+            &tx,
+            Duration::from_millis(100),
+            Handle::current(),
+        )
+        .unwrap();
+
+        gpio.set_input(14, Level::Low).unwrap();
+        tokio::time::sleep(Duration::from_millis(400)).await;
+
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -1,30 +1,108 @@
+use crate::hydro::control::{ChangeSource, Output};
+use crate::hydro::energy::EventLog;
 use crate::hydro::gpio::Gpio;
+use crate::hydro::pool_pump::{FlowLock, PoolPump, PoolPumpSpeed};
+use crate::hydro::state::HEATER;
+use crate::hydro::thermostat::Thermostat;
+use crate::repository::models::equipment_event::{HEATER_OFF, HEATER_ON};
 use crate::{config::HeaterConfig, hydro::control::Control};
 use anyhow::Error;
+use std::sync::{Arc, Mutex};
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum InterlockError {
+    #[error("The pool pump must be running at {0} speed or faster to heat.")]
+    PumpTooSlow(PoolPumpSpeed),
+    #[error("The pool pump is cooling down the heater.")]
+    CoolingDown,
+}
 
 #[derive(Clone)]
 pub struct Heater {
     pub control: Control,
+    pool_pump: PoolPump,
+    min_pump_speed: PoolPumpSpeed,
+    /// Set while the heater is in thermostat mode; shared by every clone of the heater
+    thermostat: Arc<Mutex<Option<Thermostat>>>,
+    event_log: Option<EventLog>,
 }
 
 impl Heater {
-    pub fn new(config: &HeaterConfig, gpio: &dyn Gpio) -> Result<Self, Error> {
-        let control = Control::new("Pool Heater".into(), config.control_pin, gpio)?;
-        Ok(Self { control })
+    /// Creates the heater and interlocks it with `pool_pump`; the heater can't run without
+    /// water flowing through it.
+    pub fn new(
+        config: &HeaterConfig,
+        gpio: &dyn Gpio,
+        pool_pump: &mut PoolPump,
+    ) -> Result<Self, Error> {
+        let control = Control::with_polarity(
+            "Pool Heater".into(),
+            config.control_pin,
+            config.polarity,
+            gpio,
+        )?;
+        pool_pump.interlock_heater(control.clone(), config);
+
+        Ok(Self {
+            control,
+            pool_pump: pool_pump.clone(),
+            min_pump_speed: config.min_pump_speed,
+            thermostat: Arc::new(Mutex::new(None)),
+            event_log: None,
+        })
+    }
+
+    /// Records each time the heater switches on or off from now on.
+    pub fn log_events(&mut self, event_log: EventLog) {
+        self.event_log = Some(event_log);
+    }
+
+    pub fn thermostat(&self) -> Option<Thermostat> {
+        *self.thermostat.lock().unwrap_or_else(|e| e.into_inner())
     }
 
-    pub async fn on(&mut self) {
-        let pin = self.control.pin.clone();
-        let mut lock = pin.lock().await;
+    /// Puts the heater in thermostat mode, or back under manual control with `None`.
+    pub fn set_thermostat(&self, thermostat: Option<Thermostat>) {
+        *self.thermostat.lock().unwrap_or_else(|e| e.into_inner()) = thermostat;
+    }
+
+    /// Refuses to switch on, with an `InterlockError`, unless the pool pump is running at the
+    /// minimum speed or faster and isn't winding down after the heater was last switched off.
+    pub async fn on(&mut self, source: ChangeSource) -> Result<(), Error> {
+        // Held until the heater is on, so the pump can't slow down in between
+        let flow = self.lock_flow().await?;
+
+        let was_on = self.control.is_on().await;
+        self.control.on(source).await?;
+        drop(flow);
+
+        if !was_on {
+            self.record(HEATER_ON).await;
+        }
+
+        Ok(())
+    }
 
-        lock.on();
+    /// Holds the pool pump at a speed the heater can run at, until the lock is dropped.
+    pub async fn lock_flow(&self) -> Result<FlowLock, InterlockError> {
+        self.pool_pump.lock_flow(self.min_pump_speed).await
     }
 
-    pub async fn off(&mut self) {
-        let pin = self.control.pin.clone();
-        let mut lock = pin.lock().await;
+    pub async fn off(&mut self, source: ChangeSource) -> Result<(), Error> {
+        let was_on = self.control.is_on().await;
+        self.control.off(source).await?;
 
-        lock.off();
+        if was_on {
+            self.record(HEATER_OFF).await;
+        }
+
+        Ok(())
+    }
+
+    async fn record(&self, state: &str) {
+        if let Some(event_log) = self.event_log {
+            event_log.record(HEATER, state).await;
+        }
     }
 
     pub async fn is_on(&self) -> bool {
@@ -39,3 +117,47 @@ impl Heater {
         lock.is_off()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::{Heater, InterlockError};
+    use crate::hydro::control::ChangeSource;
+    use crate::hydro::gpio::MockGpio;
+    use crate::hydro::pool_pump::{PoolPump, PoolPumpSpeed};
+    use crate::test_fixtures::{
+        gpio::{mock_heater, mock_pool_pump},
+        settings::SETTINGS,
+    };
+
+    fn heater(pump_speed: PoolPumpSpeed) -> Heater {
+        let mock_gpio = mock_pool_pump(MockGpio::new(), pump_speed);
+        let mock_gpio = mock_heater(mock_gpio, false);
+        let mut pool_pump = PoolPump::new(&SETTINGS.hydro.pool_pump, &mock_gpio).unwrap();
+
+        Heater::new(&SETTINGS.hydro.heater, &mock_gpio, &mut pool_pump).unwrap()
+    }
+
+    #[rstest]
+    #[case(
+        PoolPumpSpeed::Off,
+        Err(InterlockError::PumpTooSlow(PoolPumpSpeed::Low))
+    )]
+    #[case(PoolPumpSpeed::Low, Ok(()))]
+    #[case(PoolPumpSpeed::Max, Ok(()))]
+    #[tokio::test]
+    async fn test_heater_interlock(
+        #[case] pump_speed: PoolPumpSpeed,
+        #[case] expected: Result<(), InterlockError>,
+    ) {
+        let mut heater = heater(pump_speed);
+
+        let result = heater
+            .on(ChangeSource::Schedule)
+            .await
+            .map_err(|e| e.downcast::<InterlockError>().unwrap());
+
+        assert_eq!(result, expected);
+    }
+}
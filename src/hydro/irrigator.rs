@@ -30,7 +30,12 @@ impl Irrigator {
         handle: Handle,
         gpio: &dyn Gpio,
     ) -> Result<Self, Error> {
-        let pump = Control::new("Irrigation Pump".to_string(), config.pump_control_pin, gpio)?;
+        let pump = Control::with_polarity(
+            "Irrigation Pump".to_string(),
+            config.pump_control_pin,
+            config.pump_polarity,
+            gpio,
+        )?;
 
         let low_sensor = Sensor::new(
             Message::IrrigatorEmpty,
@@ -41,24 +46,28 @@ impl Irrigator {
             handle,
         )?;
 
-        let valve1 = Control::new(
+        let valve1 = Control::with_polarity(
             "irrigation valve 1".into(),
             config.valve_1_control_pin,
+            config.valve_1_polarity,
             gpio,
         )?;
-        let valve2 = Control::new(
+        let valve2 = Control::with_polarity(
             "irrigation valve 2".into(),
             config.valve_2_control_pin,
+            config.valve_2_polarity,
             gpio,
         )?;
-        let valve3 = Control::new(
+        let valve3 = Control::with_polarity(
             "irrigation valve 3".into(),
             config.valve_3_control_pin,
+            config.valve_3_polarity,
             gpio,
         )?;
-        let valve4 = Control::new(
+        let valve4 = Control::with_polarity(
             "irrigation valve 4".into(),
             config.valve_4_control_pin,
+            config.valve_4_polarity,
             gpio,
         )?;
 
@@ -0,0 +1,243 @@
+use anyhow::{anyhow, Error};
+use std::sync::{Arc, Mutex};
+use tokio::{
+    sync::mpsc::Sender,
+    task::JoinHandle,
+    time::{interval, Duration, MissedTickBehavior},
+};
+
+use crate::{
+    config::{LevelSensorConfig, PumpThresholds},
+    hydro::{
+        adc::Adc,
+        gpio::Level,
+        signal::{Message, Signal},
+    },
+};
+
+/// One converter is shared by every sensor on its channels.
+pub type SharedAdc = Arc<Mutex<Box<dyn Adc>>>;
+
+/// A continuous water level, read through an ADC channel and scaled between the readings at
+/// empty and full.
+#[derive(Clone)]
+pub struct LevelSensor {
+    adc: SharedAdc,
+    channel: u8,
+    empty: u16,
+    full: u16,
+}
+
+impl LevelSensor {
+    pub fn new(adc: &SharedAdc, config: &LevelSensorConfig) -> Result<Self, Error> {
+        let converter = adc.lock().unwrap_or_else(|e| e.into_inner());
+        if config.channel >= converter.channels() {
+            return Err(anyhow!(
+                "The ADC has no channel {}; it has {}",
+                config.channel,
+                converter.channels()
+            ));
+        }
+        let max_reading = converter.max_reading();
+        if config.empty > max_reading || config.full > max_reading {
+            return Err(anyhow!(
+                "Level readings must be from 0 to {}, the ADC's highest",
+                max_reading
+            ));
+        }
+
+        Ok(Self {
+            adc: Arc::clone(adc),
+            channel: config.channel,
+            empty: config.empty,
+            full: config.full,
+        })
+    }
+
+    /// The sensor `config` describes, if any. One without a converter is an error.
+    pub fn from_config(
+        adc: Option<&SharedAdc>,
+        config: Option<&LevelSensorConfig>,
+    ) -> Result<Option<Self>, Error> {
+        let Some(config) = config else {
+            return Ok(None);
+        };
+        let adc = adc.ok_or_else(|| {
+            anyhow!(
+                "A level sensor is on channel {}, but there is no ADC; set ADC_BACKEND",
+                config.channel
+            )
+        })?;
+
+        Ok(Some(Self::new(adc, config)?))
+    }
+
+    pub fn reading(&self) -> Result<u16, Error> {
+        self.adc
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .read(self.channel)
+    }
+
+    /// The level from 0 at empty to 100 at full; readings past either end are held there.
+    pub fn percent(&self) -> Result<f64, Error> {
+        Ok(percent(self.reading()?, self.empty, self.full))
+    }
+}
+
+fn percent(reading: u16, empty: u16, full: u16) -> f64 {
+    let span = f64::from(full) - f64::from(empty);
+    let percent = (f64::from(reading) - f64::from(empty)) / span * 100.0;
+
+    percent.clamp(0.0, 100.0)
+}
+
+/// Intended to be run at startup in place of the sump's float switches. Reads `sensor` on
+/// every poll and sends the messages the switches would: `SumpFull` once the level reaches
+/// `on_percent`, then `SumpEmpty` once it falls to `off_percent`. The gap between the two
+/// keeps a noisy reading from switching the pump on and off.
+pub fn start_pump_thresholds(
+    sensor: LevelSensor,
+    thresholds: PumpThresholds,
+    tx: Sender<Signal>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = interval(Duration::from_millis(thresholds.poll_ms));
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut full = false;
+
+        loop {
+            ticks.tick().await;
+
+            let percent = match sensor.percent() {
+                Ok(percent) => percent,
+                Err(e) => {
+                    tracing::error!(
+                        target = module_path!(),
+                        error = e.to_string(),
+                        "Could not read the sump level"
+                    );
+                    continue;
+                }
+            };
+
+            let message = if !full && percent >= thresholds.on_percent {
+                Message::SumpFull
+            } else if full && percent <= thresholds.off_percent {
+                Message::SumpEmpty
+            } else {
+                continue;
+            };
+            full = message == Message::SumpFull;
+
+            let signal = Signal {
+                message,
+                level: Level::High,
+            };
+            if tx.send(signal).await.is_err() {
+                break;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use std::sync::{Arc, Mutex};
+    use tokio::{
+        sync::mpsc,
+        time::{sleep, Duration},
+    };
+
+    use super::{percent, start_pump_thresholds, LevelSensor, SharedAdc};
+    use crate::{
+        config::{LevelSensorConfig, PumpThresholds},
+        hydro::{adc::mcp3008::Mcp3008, signal::Message},
+        test_fixtures::spi::FakeMcp3008,
+    };
+
+    fn shared_adc(fake: &FakeMcp3008) -> SharedAdc {
+        Arc::new(Mutex::new(Box::new(Mcp3008::new(Box::new(fake.clone())))))
+    }
+
+    #[rstest]
+    #[case(100, 100, 900, 0.0)]
+    #[case(500, 100, 900, 50.0)]
+    #[case(1000, 100, 900, 100.0)]
+    #[case(50, 100, 900, 0.0)]
+    // An ultrasonic sensor reads less as the water comes up to it
+    #[case(300, 900, 100, 75.0)]
+    fn test_percent(
+        #[case] reading: u16,
+        #[case] empty: u16,
+        #[case] full: u16,
+        #[case] expected: f64,
+    ) {
+        assert!(percent(reading, empty, full) == expected);
+    }
+
+    #[test]
+    fn test_sensor_percent() {
+        let fake = FakeMcp3008::new();
+        fake.set_reading(2, 700);
+        let config = LevelSensorConfig {
+            channel: 2,
+            empty: 200,
+            full: 1000,
+        };
+
+        let sensor = LevelSensor::new(&shared_adc(&fake), &config).unwrap();
+
+        assert!(sensor.percent().unwrap() == 62.5);
+    }
+
+    #[test]
+    fn test_sensor_invalid() {
+        let adc = shared_adc(&FakeMcp3008::new());
+        let channel = LevelSensorConfig {
+            channel: 8,
+            empty: 0,
+            full: 1000,
+        };
+        let reading = LevelSensorConfig {
+            channel: 0,
+            empty: 0,
+            full: 2000,
+        };
+
+        assert!(LevelSensor::new(&adc, &channel).is_err());
+        assert!(LevelSensor::new(&adc, &reading).is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_pump_thresholds() {
+        let fake = FakeMcp3008::new();
+        let config = LevelSensorConfig {
+            channel: 0,
+            empty: 0,
+            full: 1000,
+        };
+        let sensor = LevelSensor::new(&shared_adc(&fake), &config).unwrap();
+        let thresholds = PumpThresholds {
+            on_percent: 80.0,
+            off_percent: 20.0,
+            poll_ms: 1000,
+        };
+        let (tx, mut rx) = mpsc::channel(32);
+
+        let _task = start_pump_thresholds(sensor, thresholds, tx);
+        // Filling, with the reading bouncing around the on threshold
+        for reading in [100, 500, 810, 790, 850, 500, 210, 190, 150] {
+            fake.set_reading(0, reading);
+            sleep(Duration::from_millis(1000)).await;
+        }
+
+        let mut messages = vec![];
+        while let Ok(signal) = rx.try_recv() {
+            messages.push(signal.message);
+        }
+
+        assert!(messages == vec![Message::SumpFull, Message::SumpEmpty]);
+    }
+}
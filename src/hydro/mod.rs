@@ -1,42 +1,89 @@
 use anyhow::Error;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
 use tokio::{
     runtime::Handle,
     sync::mpsc::{Receiver, Sender},
+    task::JoinHandle,
+    time::sleep,
 };
 
 use crate::{
     config::HydroConfig,
     hydro::{
-        control::Control,
+        adc::Adc,
+        budget::WaterBudget,
+        control::{ChangeSource, Control, ControlStatus, Output},
+        energy::EventLog,
+        freeze::FreezeProtection,
         gpio::{Gpio, Level},
         heater::Heater,
         irrigator::Irrigator,
-        pool_pump::PoolPump,
+        level::{start_pump_thresholds, LevelSensor, SharedAdc},
+        outputs::NamedOutput,
+        pool_pump::{PoolPump, PoolPumpSpeed},
+        schedule::pool_pump::ManualOverride,
+        sensor::Sensor,
         sump::Sump,
+        temperature::{OneWire, TemperatureSensor},
+        watchdog::Health,
     },
-    repository::Repo,
+    repository::{models::equipment_event::HEATER_OFF, Repo},
 };
 
 use self::signal::Signal;
 
+pub mod adc;
+pub mod budget;
 pub mod control;
 pub mod debounce;
+pub mod energy;
+pub mod freeze;
 pub mod gpio;
 pub mod heater;
 pub mod irrigator;
+pub mod level;
+pub mod outputs;
 pub mod pool_pump;
 pub mod schedule;
+pub mod selftest;
 pub mod sensor;
 pub mod signal;
+pub mod state;
 mod sump;
+pub mod temperature;
+pub mod thermostat;
+pub mod usage;
+pub mod watchdog;
+
+/// Both ends of the channel the sensors send their changes on.
+pub type SignalChannel = (Sender<Signal>, Receiver<Signal>);
 
 pub struct Hydro {
     pub repo: Repo,
     pub heater: Heater,
     pub pool_pump: PoolPump,
+    /// Held while the pool pump is set by hand, pausing its programs
+    pub pool_pump_override: ManualOverride,
+    /// Set when a freeze temperature is configured
+    pub freeze_protection: Option<FreezeProtection>,
     pub handle: Handle,
     pub sump: Sump,
     pub irrigator: Irrigator,
+    /// Set when the sump has a level sensor
+    pub sump_level: Option<LevelSensor>,
+    /// Set when the irrigation reservoir has a level sensor
+    pub reservoir_level: Option<LevelSensor>,
+    /// Spare relays, by name
+    pub outputs: Vec<NamedOutput>,
+    /// Pool water temperature
+    pub temperature: Arc<dyn TemperatureSensor>,
+    /// Heartbeats of the tasks the watchdog waits on
+    pub health: Health,
+    /// Background tasks driving the equipment, stopped on shutdown
+    tasks: Vec<JoinHandle<()>>,
 }
 
 impl Hydro {
@@ -44,39 +91,468 @@ impl Hydro {
         config: &HydroConfig,
         handle: Handle,
         gpio: &dyn Gpio,
+        adc: Option<Box<dyn Adc>>,
         repo: Repo,
     ) -> Result<Self, Error> {
-        let mpsc: (Sender<Signal>, Receiver<Signal>) = tokio::sync::mpsc::channel(32);
+        let (mut hydro, (tx, signals)) = Self::idle(config, handle, gpio, adc, repo)?;
+        hydro.start(config, tx, signals);
+
+        Ok(hydro)
+    }
+
+    /// Claims the equipment without starting anything that drives it, so nothing switches an
+    /// output on its own. Sensor changes queue up on the returned channel, unread.
+    pub fn idle(
+        config: &HydroConfig,
+        handle: Handle,
+        gpio: &dyn Gpio,
+        adc: Option<Box<dyn Adc>>,
+        repo: Repo,
+    ) -> Result<(Self, SignalChannel), Error> {
+        let mpsc: SignalChannel = tokio::sync::mpsc::channel(32);
         let tx = mpsc.0;
 
-        let heater = Heater::new(&config.heater, gpio)?;
-        let pool_pump = PoolPump::new(&config.pool_pump, gpio)?;
+        let event_log = EventLog::new(repo);
+        let mut pool_pump = PoolPump::new(&config.pool_pump, gpio)?;
+        pool_pump.log_events(event_log);
+        let mut heater = Heater::new(&config.heater, gpio, &mut pool_pump)?;
+        heater.log_events(event_log);
+        let temperature: Arc<dyn TemperatureSensor> = Arc::new(OneWire::new(
+            &config.temperature.w1_devices_path,
+            config.temperature.sensor_id.clone(),
+        ));
 
         let sump = Sump::new(&config.sump, &tx, handle.clone(), gpio)?;
         let irrigator = Irrigator::new(&config.irrigation, &tx, handle.clone(), gpio)?;
 
-        schedule::start(
-            repo,
-            irrigator.clone(),
-            config.irrigation.process_frequency_sec,
-        );
+        let adc: Option<SharedAdc> = adc.map(|adc| Arc::new(Mutex::new(adc)));
+        let sump_level = LevelSensor::from_config(adc.as_ref(), config.sump.level.as_ref())?;
+        let reservoir_level =
+            LevelSensor::from_config(adc.as_ref(), config.irrigation.level.as_ref())?;
 
-        signal::listen(
-            mpsc.1,
-            handle.clone(),
-            irrigator.pump.pin.clone(),
-            sump.pump.pin.clone(),
-            config.sump.pump_shutoff_delay,
-            config.sump.pump_max_runtime,
-        );
+        let outputs = config
+            .outputs
+            .outputs
+            .iter()
+            .map(|output| NamedOutput::new(output, gpio))
+            .collect::<Result<Vec<NamedOutput>, Error>>()?;
 
-        Ok(Self {
+        let hydro = Self {
             irrigator,
+            outputs,
             heater,
             pool_pump,
+            pool_pump_override: ManualOverride::default(),
+            freeze_protection: FreezeProtection::from_config(&config.pool_pump),
             repo,
             handle,
             sump,
-        })
+            sump_level,
+            reservoir_level,
+            temperature,
+            health: Health::default(),
+            tasks: vec![],
+        };
+
+        Ok((hydro, (tx, mpsc.1)))
+    }
+
+    /// Starts the programs, schedules and sensor signal listener driving the equipment.
+    fn start(&mut self, config: &HydroConfig, tx: Sender<Signal>, signals: Receiver<Signal>) {
+        let repo = self.repo;
+        let event_log = EventLog::new(repo);
+
+        // Like the float switches it replaces, this only sends signals
+        if let (Some(sensor), Some(thresholds)) = (&self.sump_level, &config.sump.pump_thresholds) {
+            self.tasks.push(start_pump_thresholds(
+                sensor.clone(),
+                thresholds.clone(),
+                tx,
+            ));
+        }
+
+        self.tasks.push(schedule::start(
+            repo,
+            self.irrigator.clone(),
+            config.irrigation.process_frequency_sec,
+            WaterBudget::from_config(config),
+            self.health.register("scheduler"),
+        ));
+
+        // Restore the equipment before its programs get a say
+        let mut restored_heater = self.heater.clone();
+        let mut restored_pool_pump = self.pool_pump.clone();
+        let restored_override = self.pool_pump_override.clone();
+        let mut restored_outputs = self.outputs.clone();
+        let heater_policy = config.heater.restore;
+        let pool_pump_policy = config.pool_pump.restore;
+        let scheduler_freeze_protection = self.freeze_protection.clone();
+        let pool_pump_frequency_sec = config.pool_pump.process_frequency_sec;
+        let freeze = self.freeze_protection.clone().map(|protection| {
+            // Outdoor air gives earlier warning of a freeze than the water does
+            let sensor: Arc<dyn TemperatureSensor> = match &config.temperature.air_sensor_id {
+                Some(air_sensor_id) => Arc::new(OneWire::new(
+                    &config.temperature.w1_devices_path,
+                    Some(air_sensor_id.clone()),
+                )),
+                None => self.temperature.clone(),
+            };
+
+            freeze::run(
+                self.pool_pump.clone(),
+                sensor,
+                protection,
+                config.pool_pump.process_frequency_sec,
+            )
+        });
+        let thermostat = thermostat::run(
+            self.heater.clone(),
+            self.temperature.clone(),
+            config.heater.thermostat_frequency_sec,
+        );
+
+        // One task, so aborting it on shutdown stops every program it started
+        self.tasks.push(tokio::spawn(async move {
+            event_log.record_startup().await;
+
+            state::restore(
+                repo,
+                heater_policy,
+                pool_pump_policy,
+                &mut restored_heater,
+                &mut restored_pool_pump,
+                &restored_override,
+                &mut restored_outputs,
+            )
+            .await;
+
+            let freeze = async {
+                if let Some(freeze) = freeze {
+                    freeze.await;
+                }
+            };
+            tokio::join!(
+                schedule::pool_pump::run(
+                    repo,
+                    restored_pool_pump,
+                    restored_override,
+                    scheduler_freeze_protection,
+                    pool_pump_frequency_sec,
+                ),
+                freeze,
+                thermostat,
+            );
+        }));
+
+        self.tasks.push(outputs::start(
+            self.outputs.clone(),
+            config.outputs.process_frequency_sec,
+        ));
+
+        self.tasks.push(signal::listen(
+            signals,
+            self.handle.clone(),
+            repo,
+            self.irrigator.pump.clone(),
+            self.sump.pump.clone(),
+            config.sump.pump_shutoff_delay,
+            config.sump.pump_max_runtime,
+            self.health.register("signals"),
+        ));
+    }
+
+    /// Every output by name, in the order they are switched off on shutdown: the heater while
+    /// water still flows, then each pump before the valves it feeds, then the spare relays. The
+    /// pool pump alone waits out any heater cool-down first.
+    pub fn controls(&self) -> Vec<(String, &Control)> {
+        let mut controls = vec![
+            ("heater".to_string(), &self.heater.control),
+            ("irrigationPump".to_string(), &self.irrigator.pump),
+            ("irrigationValve1".to_string(), &self.irrigator.valve1),
+            ("irrigationValve2".to_string(), &self.irrigator.valve2),
+            ("irrigationValve3".to_string(), &self.irrigator.valve3),
+            ("irrigationValve4".to_string(), &self.irrigator.valve4),
+            ("poolPumpMax".to_string(), &self.pool_pump.max),
+            ("poolPumpHigh".to_string(), &self.pool_pump.high),
+            ("poolPumpMed".to_string(), &self.pool_pump.med),
+            ("poolPumpLow".to_string(), &self.pool_pump.low),
+            ("sumpPump".to_string(), &self.sump.pump),
+        ];
+        for output in self.outputs.iter() {
+            controls.push((output.name.clone(), &output.control));
+        }
+
+        controls
+    }
+
+    /// Every water level sensor by name.
+    pub fn sensors(&self) -> Vec<(String, &Sensor)> {
+        vec![
+            ("irrigationLow".to_string(), &self.irrigator.low_sensor),
+            ("sumpHigh".to_string(), &self.sump.high_sensor),
+            ("sumpLow".to_string(), &self.sump.low_sensor),
+        ]
+    }
+
+    pub fn output(&self, name: &str) -> Option<&NamedOutput> {
+        self.outputs.iter().find(|output| output.name == name)
+    }
+
+    /// What each output was last told to do, and what its pin reads back.
+    pub async fn equipment(&self) -> BTreeMap<String, ControlStatus> {
+        let mut equipment = BTreeMap::new();
+        for (name, control) in self.controls() {
+            equipment.insert(name, control.status().await);
+        }
+
+        equipment
+    }
+
+    /// Leaves the equipment safe to walk away from: stops the background tasks, cancels any
+    /// running irrigation, and switches every output off. A heater that was running, or still
+    /// cooling down, is switched off first, and the pool pump keeps its speed through the
+    /// heater's cool-down before it stops too.
+    pub async fn shutdown(&mut self) {
+        tracing::info!(target = module_path!(), "Shutting down equipment");
+
+        // Wait for each task to stop, so none switches an output back on afterwards
+        for task in self.tasks.iter() {
+            task.abort();
+        }
+        for task in self.tasks.drain(..) {
+            let _ = task.await;
+        }
+        // Those tasks no longer beat, so the watchdog waits on the shutdown instead
+        let heartbeat = self.health.replace_all("shutdown");
+        let cooling_down = self.pool_pump.is_cooling_down().await;
+        self.pool_pump.cancel_pending().await;
+        for output in self.outputs.iter() {
+            output.cancel_timer().await;
+        }
+        let heater_on = self.heater.is_on().await;
+        let pool_pump_on = self.pool_pump.speed().await != PoolPumpSpeed::Off;
+
+        if let Err(e) = self.repo.cancel_irrigation_event().await {
+            tracing::error!(
+                target = module_path!(),
+                error = e.to_string(),
+                "Could not cancel the running irrigation event"
+            );
+        }
+
+        let pool_pump_speeds = [
+            &self.pool_pump.low,
+            &self.pool_pump.med,
+            &self.pool_pump.high,
+            &self.pool_pump.max,
+        ];
+        let (pool_pump_controls, controls): (Vec<_>, Vec<_>) = self
+            .controls()
+            .into_iter()
+            .partition(|(_, control)| pool_pump_speeds.contains(control));
+
+        // The heater goes first, in the order of `controls`, and everything but the pool pump
+        // with it, so nothing else runs unattended through the cool-down
+        for (name, control) in controls {
+            switch_off(name, control).await;
+        }
+        if let Some(cool_down) = self.pool_pump.cool_down() {
+            if pool_pump_on && (heater_on || cooling_down) {
+                tracing::info!(
+                    target = module_path!(),
+                    cool_down_sec = cool_down.as_secs(),
+                    "Cooling down the pool heater before stopping the pool pump"
+                );
+                heartbeat.during(sleep(cool_down)).await;
+            }
+        }
+        for (name, control) in pool_pump_controls {
+            switch_off(name, control).await;
+        }
+
+        let event_log = EventLog::new(self.repo);
+        if heater_on {
+            event_log.record(state::HEATER, HEATER_OFF).await;
+        }
+        if pool_pump_on {
+            event_log
+                .record(state::POOL_PUMP, &PoolPumpSpeed::Off.to_string())
+                .await;
+        }
+    }
+}
+
+async fn switch_off(name: String, control: &Control) {
+    if let Err(e) = control.clone().off(ChangeSource::Shutdown).await {
+        tracing::error!(
+            target = module_path!(),
+            error = e.to_string(),
+            output = name,
+            "Could not switch output off"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockall::predicate;
+    use std::sync::{Arc, Mutex};
+    use tokio::{
+        runtime::Handle,
+        time::{sleep, Duration, Instant},
+    };
+
+    use super::{state, Hydro};
+    use crate::hydro::gpio::{Level, MockGpio, MockOutputPin, MockPin};
+    use crate::repository::{models::equipment_event::HEATER_OFF, MockRepository};
+    use crate::test_fixtures::{gpio::mock_input_pin_with_interrupt, settings::SETTINGS};
+
+    /// When each pin was switched off.
+    type Offs = Arc<Mutex<Vec<(u8, Instant)>>>;
+
+    /// An output that is on, and expects to be switched off exactly once, noting when in
+    /// `offs`. A `scheduled` one may also be switched on by its schedule before then,
+    /// depending on the time of day.
+    fn mock_running_output(
+        mut mock_gpio: MockGpio,
+        pin: u8,
+        scheduled: bool,
+        offs: &Offs,
+    ) -> MockGpio {
+        let offs = Arc::clone(offs);
+        mock_gpio
+            .expect_get()
+            .with(predicate::eq(pin))
+            .times(1)
+            .returning(move |_| {
+                let offs = Arc::clone(&offs);
+                let mut mock_pin = MockPin::new();
+                mock_pin.expect_into_output_low().returning(move || {
+                    let offs = Arc::clone(&offs);
+                    let mut output_pin = MockOutputPin::new();
+                    output_pin.expect_is_on().return_const(true);
+                    output_pin
+                        .expect_off()
+                        .times(1)
+                        .returning(move || offs.lock().unwrap().push((pin, Instant::now())));
+                    output_pin
+                        .expect_on()
+                        .times(if scheduled { 0..2 } else { 0..1 })
+                        .return_const(());
+                    Box::new(output_pin)
+                });
+                Ok(Box::new(mock_pin))
+            });
+
+        mock_gpio
+    }
+
+    fn mock_sensor(mut mock_gpio: MockGpio, pin: u8) -> MockGpio {
+        mock_gpio
+            .expect_get()
+            .with(predicate::eq(pin))
+            .times(1)
+            .returning(|_| Ok(mock_input_pin_with_interrupt(false, Level::Low)));
+
+        mock_gpio
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shutdown() {
+        let config = &SETTINGS.hydro;
+        let offs = Offs::default();
+        let mut mock_gpio = MockGpio::new();
+        for pin in [
+            config.heater.control_pin,
+            config.irrigation.pump_control_pin,
+            config.irrigation.valve_1_control_pin,
+            config.irrigation.valve_2_control_pin,
+            config.irrigation.valve_3_control_pin,
+            config.irrigation.valve_4_control_pin,
+            config.pool_pump.low_pin,
+            config.pool_pump.med_pin,
+            config.pool_pump.high_pin,
+            config.pool_pump.max_pin,
+            config.sump.pump_control_pin,
+        ] {
+            mock_gpio = mock_running_output(mock_gpio, pin, false, &offs);
+        }
+        for output in config.outputs.outputs.iter() {
+            mock_gpio =
+                mock_running_output(mock_gpio, output.pin, output.schedule.is_some(), &offs);
+        }
+        for pin in [
+            config.irrigation.low_sensor_pin,
+            config.sump.high_sensor_pin,
+            config.sump.low_sensor_pin,
+        ] {
+            mock_gpio = mock_sensor(mock_gpio, pin);
+        }
+
+        let mut mock_repo = MockRepository::new();
+        // Enough for the background tasks to run with nothing saved or scheduled
+        mock_repo
+            .expect_equipment_events()
+            .returning(|_| Ok(vec![]));
+        mock_repo.expect_equipment_states().returning(|| Ok(vec![]));
+        mock_repo
+            .expect_pool_pump_schedules()
+            .returning(|| Ok(vec![]));
+        mock_repo
+            .expect_schedule_statuses()
+            .returning(|| Ok(vec![]));
+        mock_repo
+            .expect_queue_irrigation_events()
+            .returning(|_| Ok(()));
+        mock_repo
+            .expect_queued_irrigation_events()
+            .returning(|| Ok(vec![]));
+        mock_repo
+            .expect_cancel_irrigation_event()
+            .times(1)
+            .returning(|| Ok(()));
+        let events = Arc::new(Mutex::new(vec![]));
+        let recorded = events.clone();
+        mock_repo
+            .expect_create_equipment_event()
+            .returning(move |kind, info| {
+                recorded.lock().unwrap().push((kind, info));
+                Ok(())
+            });
+        let repo = Box::leak(Box::new(mock_repo));
+
+        let mut hydro = Hydro::new(config, Handle::current(), &mock_gpio, None, repo).unwrap();
+        // Let the background tasks get going before they are stopped
+        sleep(Duration::from_secs(1)).await;
+        events.lock().unwrap().clear();
+        let start = Instant::now();
+        hydro.shutdown().await;
+
+        // The heater and pool pump were both running
+        assert!(
+            *events.lock().unwrap()
+                == vec![
+                    (state::HEATER.to_string(), HEATER_OFF.to_string()),
+                    (state::POOL_PUMP.to_string(), "off".to_string()),
+                ]
+        );
+        // The heater went off straight away, and the pool pump only after its cool-down
+        let pool_pump_pins = [
+            config.pool_pump.low_pin,
+            config.pool_pump.med_pin,
+            config.pool_pump.high_pin,
+            config.pool_pump.max_pin,
+        ];
+        let cool_down = Duration::from_secs(config.heater.cool_down_sec);
+        for (pin, off_at) in offs.lock().unwrap().iter() {
+            let after = *off_at - start;
+            if pool_pump_pins.contains(pin) {
+                assert!(after >= cool_down);
+            } else {
+                assert!(after < cool_down);
+            }
+        }
+        // Dropping the pins checks each was switched off once
+        drop(hydro);
     }
 }
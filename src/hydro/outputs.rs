@@ -0,0 +1,225 @@
+use anyhow::{anyhow, Error};
+use chrono::{NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{str::FromStr, sync::Arc};
+use tokio::{
+    sync::Mutex,
+    task::JoinHandle,
+    time::{sleep, Duration},
+};
+
+use crate::config::OutputConfig;
+use crate::hydro::{
+    control::{ChangeSource, Control, ControlStatus, Output},
+    gpio::Gpio,
+    state::RestorePolicy,
+};
+
+/// A daily stretch of time an output is switched on for, in UTC like the pool pump programs.
+/// An `end` before `start` runs past midnight.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct OutputSchedule {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+/// Parses `HH:MM-HH:MM`.
+impl FromStr for OutputSchedule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| anyhow!("Invalid output schedule: {}", s))?;
+
+        Ok(Self {
+            start: NaiveTime::parse_from_str(start.trim(), "%H:%M")?,
+            end: NaiveTime::parse_from_str(end.trim(), "%H:%M")?,
+        })
+    }
+}
+
+impl OutputSchedule {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+/// A relay for equipment that only needs switching on and off, such as lights or a fountain.
+#[derive(Clone)]
+pub struct NamedOutput {
+    pub name: String,
+    pub control: Control,
+    /// Seconds the output may stay on before it is switched off again
+    pub max_on_sec: Option<u64>,
+    pub schedule: Option<OutputSchedule>,
+    pub restore: RestorePolicy,
+    /// Switches the output off once the max on time runs out
+    timer: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NamedOutputStatus {
+    pub name: String,
+    pub max_on_sec: Option<u64>,
+    pub schedule: Option<OutputSchedule>,
+    #[serde(flatten)]
+    pub status: ControlStatus,
+}
+
+impl NamedOutput {
+    pub fn new(config: &OutputConfig, gpio: &dyn Gpio) -> Result<Self, Error> {
+        let control =
+            Control::with_polarity(config.name.clone(), config.pin, config.polarity, gpio)?;
+
+        Ok(Self {
+            name: config.name.clone(),
+            control,
+            max_on_sec: config.max_on_sec,
+            schedule: config.schedule,
+            restore: config.restore,
+            timer: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// Switches the output on, restarting the max on time if there is one.
+    pub async fn on(&mut self, source: ChangeSource) -> Result<(), Error> {
+        let mut timer = self.timer.lock().await;
+        if let Some(handle) = timer.take() {
+            handle.abort();
+        }
+
+        self.control.on(source).await?;
+
+        if let Some(max_on_sec) = self.max_on_sec {
+            let mut control = self.control.clone();
+            *timer = Some(tokio::spawn(async move {
+                sleep(Duration::from_secs(max_on_sec)).await;
+
+                tracing::info!(
+                    target = module_path!(),
+                    output = control.label,
+                    max_on_sec,
+                    "Output reached its max on time"
+                );
+                if let Err(e) = control.off(ChangeSource::Timer).await {
+                    tracing::error!(
+                        target = module_path!(),
+                        error = e.to_string(),
+                        output = control.label,
+                        "Could not switch the output off"
+                    );
+                }
+            }));
+        }
+
+        Ok(())
+    }
+
+    pub async fn off(&mut self, source: ChangeSource) -> Result<(), Error> {
+        self.cancel_timer().await;
+
+        self.control.off(source).await
+    }
+
+    /// Stops the max on time from switching the output off.
+    pub async fn cancel_timer(&self) {
+        if let Some(handle) = self.timer.lock().await.take() {
+            handle.abort();
+        }
+    }
+
+    pub async fn status(&self) -> NamedOutputStatus {
+        NamedOutputStatus {
+            name: self.name.clone(),
+            max_on_sec: self.max_on_sec,
+            schedule: self.schedule,
+            status: self.control.status().await,
+        }
+    }
+}
+
+/// Intended to be run at startup. Each tick switches scheduled outputs on as their schedule
+/// starts and off as it ends. Only the start and end are acted on, so an output switched by
+/// hand stays that way until its schedule next starts or ends.
+///
+///  # Arguments
+///
+///  * `outputs` - The outputs; those without a schedule are left alone
+///  * `frequency_sec` - Seconds between ticks
+///
+pub fn start(outputs: Vec<NamedOutput>, frequency_sec: u64) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut scheduled: Vec<(NamedOutput, Option<bool>)> = outputs
+            .into_iter()
+            .filter(|output| output.schedule.is_some())
+            .map(|output| (output, None))
+            .collect();
+
+        loop {
+            let now = Utc::now().time();
+
+            for (output, was_due) in scheduled.iter_mut() {
+                let Some(schedule) = output.schedule else {
+                    continue;
+                };
+                let due = schedule.contains(now);
+                if *was_due == Some(due) {
+                    continue;
+                }
+
+                // Nothing to undo at startup when the output isn't due
+                let switched = match (due, *was_due) {
+                    (true, _) => output.on(ChangeSource::Schedule).await,
+                    (false, Some(_)) => output.off(ChangeSource::Schedule).await,
+                    (false, None) => Ok(()),
+                };
+                if let Err(e) = switched {
+                    tracing::error!(
+                        target = module_path!(),
+                        error = e.to_string(),
+                        output = output.name,
+                        "Could not switch scheduled output"
+                    );
+                }
+                *was_due = Some(due);
+            }
+
+            sleep(Duration::from_secs(frequency_sec)).await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveTime;
+    use rstest::rstest;
+
+    use super::OutputSchedule;
+
+    #[rstest]
+    #[case("19:00-23:00", "18:59", false)]
+    #[case("19:00-23:00", "19:00", true)]
+    #[case("19:00-23:00", "23:00", false)]
+    #[case("22:00-02:00", "23:30", true)]
+    #[case("22:00-02:00", "01:00", true)]
+    #[case("22:00-02:00", "12:00", false)]
+    fn test_schedule_contains(#[case] schedule: &str, #[case] time: &str, #[case] due: bool) {
+        let schedule: OutputSchedule = schedule.parse().unwrap();
+        let time = NaiveTime::parse_from_str(time, "%H:%M").unwrap();
+
+        assert_eq!(schedule.contains(time), due);
+    }
+
+    #[rstest]
+    #[case("19:00")]
+    #[case("7pm-11pm")]
+    fn test_invalid_schedule(#[case] schedule: &str) {
+        assert!(schedule.parse::<OutputSchedule>().is_err());
+    }
+}
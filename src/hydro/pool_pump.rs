@@ -1,10 +1,24 @@
-use anyhow::Error;
+use anyhow::{anyhow, Error};
 use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr, sync::Arc};
+use tokio::{
+    sync::{Mutex, OwnedMutexGuard},
+    task::JoinHandle,
+    time::{sleep, Duration},
+};
 use tracing::error;
 
 use crate::{
-    config::PoolPumpConfig,
-    hydro::{control::Output, gpio::Gpio, Control},
+    config::{HeaterConfig, PoolPumpConfig},
+    hydro::{
+        control::{ChangeSource, Output},
+        energy::EventLog,
+        gpio::Gpio,
+        heater::InterlockError,
+        state::{HEATER, POOL_PUMP},
+        Control,
+    },
+    repository::models::equipment_event::HEATER_OFF,
 };
 
 #[derive(Clone)]
@@ -13,10 +27,47 @@ pub struct PoolPump {
     pub med: Control,
     pub high: Control,
     pub max: Control,
-    pub current: PoolPumpSpeed,
+    /// Speed to prime at when starting from off, and for how many seconds; `None` to skip
+    priming: Option<(PoolPumpSpeed, u64)>,
+    heater_interlock: Option<HeaterInterlock>,
+    /// The speed change waiting on a timer, if any. Held while the speed changes, and by the
+    /// heater while it checks the speed and switches on, so neither can slip in between the
+    /// other's check and switch.
+    pending: Arc<Mutex<Option<PendingChange>>>,
+    event_log: Option<EventLog>,
+}
+
+/// Why the pump hasn't reached the speed it was last asked for yet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transition {
+    /// Running fast to prime after starting from off
+    Priming,
+    /// Holding speed while the heater cools down
+    CoolingDown,
+}
+
+struct PendingChange {
+    transition: Transition,
+    target: PoolPumpSpeed,
+    handle: JoinHandle<()>,
+}
+
+/// Keeps the pool pump at its current speed until dropped.
+pub struct FlowLock {
+    _pending: OwnedMutexGuard<Option<PendingChange>>,
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+/// Keeps water moving through the heater. Below `min_speed` the heater is switched off, and
+/// the pump holds its speed for `cool_down_sec` before slowing down.
+#[derive(Clone)]
+struct HeaterInterlock {
+    heater: Control,
+    min_speed: PoolPumpSpeed,
+    cool_down_sec: u64,
+}
+
+/// Variants are declared slowest first, so speeds compare by flow.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum PoolPumpSpeed {
     Off,
@@ -26,83 +77,366 @@ pub enum PoolPumpSpeed {
     Max,
 }
 
+impl fmt::Display for PoolPumpSpeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolPumpSpeed::Off => write!(f, "off"),
+            PoolPumpSpeed::Low => write!(f, "low"),
+            PoolPumpSpeed::Med => write!(f, "med"),
+            PoolPumpSpeed::High => write!(f, "high"),
+            PoolPumpSpeed::Max => write!(f, "max"),
+        }
+    }
+}
+
+impl FromStr for PoolPumpSpeed {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(PoolPumpSpeed::Off),
+            "low" => Ok(PoolPumpSpeed::Low),
+            "med" => Ok(PoolPumpSpeed::Med),
+            "high" => Ok(PoolPumpSpeed::High),
+            "max" => Ok(PoolPumpSpeed::Max),
+            _ => Err(anyhow!("Invalid pool pump speed: {}", s)),
+        }
+    }
+}
+
 impl PoolPump {
     pub fn new(config: &PoolPumpConfig, gpio: &dyn Gpio) -> Result<Self, Error> {
-        let low = Control::new("low speed".into(), config.low_pin, gpio)?;
-        let med = Control::new("medium speed".into(), config.med_pin, gpio)?;
-        let high = Control::new("high speed".into(), config.high_pin, gpio)?;
-        let max = Control::new("max speed".into(), config.max_pin, gpio)?;
+        let low = Control::with_polarity(
+            "low speed".into(),
+            config.low_pin,
+            config.low_polarity,
+            gpio,
+        )?;
+        let med = Control::with_polarity(
+            "medium speed".into(),
+            config.med_pin,
+            config.med_polarity,
+            gpio,
+        )?;
+        let high = Control::with_polarity(
+            "high speed".into(),
+            config.high_pin,
+            config.high_polarity,
+            gpio,
+        )?;
+        let max = Control::with_polarity(
+            "max speed".into(),
+            config.max_pin,
+            config.max_polarity,
+            gpio,
+        )?;
 
         Ok(Self {
             low,
             med,
             high,
             max,
-            current: PoolPumpSpeed::Off,
+            priming: (config.priming_sec > 0).then_some((config.priming_speed, config.priming_sec)),
+            heater_interlock: None,
+            pending: Arc::new(Mutex::new(None)),
+            event_log: None,
         })
     }
 
-    pub async fn off(&mut self) -> Result<(), Error> {
-        let mut self_clone = self.clone();
+    /// Records each speed change, and the heater interlock switching the heater off, from now
+    /// on.
+    pub fn log_events(&mut self, event_log: EventLog) {
+        self.event_log = Some(event_log);
+    }
+
+    /// Ties the pool heater to this pump, so the heater is off before the pump slows below the
+    /// heater's minimum speed.
+    pub fn interlock_heater(&mut self, heater: Control, config: &HeaterConfig) {
+        self.heater_interlock = Some(HeaterInterlock {
+            heater,
+            min_speed: config.min_pump_speed,
+            cool_down_sec: config.cool_down_sec,
+        });
+    }
+
+    /// How long the pump keeps running once the heater is switched off; `None` without a
+    /// heater.
+    pub fn cool_down(&self) -> Option<Duration> {
+        self.heater_interlock
+            .as_ref()
+            .map(|interlock| Duration::from_secs(interlock.cool_down_sec))
+    }
+
+    /// True while the pump is waiting out the heater cool-down before slowing down.
+    pub async fn is_cooling_down(&self) -> bool {
+        self.transition().await == Some(Transition::CoolingDown)
+    }
+
+    /// True while the pump is priming before settling at the requested speed.
+    pub async fn is_priming(&self) -> bool {
+        self.transition().await == Some(Transition::Priming)
+    }
+
+    /// The timed speed change under way, if any.
+    pub async fn transition(&self) -> Option<Transition> {
+        let pending = self.pending.lock().await;
+
+        pending
+            .as_ref()
+            .filter(|change| !change.handle.is_finished())
+            .map(|change| change.transition)
+    }
 
-        let low_result = self_clone.low.off().await;
-        if let Err(e) = low_result {
-            error!("Error setting pool pump off: {}", e);
+    /// Holds the speed, once the pump is running at `min_speed` or faster and isn't cooling
+    /// down the heater. Nothing changes the speed until the lock is dropped, so the heater can
+    /// be switched on knowing water is flowing.
+    pub async fn lock_flow(&self, min_speed: PoolPumpSpeed) -> Result<FlowLock, InterlockError> {
+        let pending = Arc::clone(&self.pending).lock_owned().await;
+        let cooling_down = pending.as_ref().is_some_and(|change| {
+            change.transition == Transition::CoolingDown && !change.handle.is_finished()
+        });
+        if cooling_down {
+            return Err(InterlockError::CoolingDown);
         }
-        let med_result = self_clone.med.off().await;
-        if let Err(e) = med_result {
-            error!("Error setting pool pump off: {}", e);
+
+        if self.speed().await < min_speed {
+            return Err(InterlockError::PumpTooSlow(min_speed));
+        }
+
+        Ok(FlowLock { _pending: pending })
+    }
+
+    pub async fn off(&mut self, source: ChangeSource) -> Result<(), Error> {
+        self.on(PoolPumpSpeed::Off, source).await
+    }
+
+    /// Sets the new speed on the pump; a later speed change replaces any pending one.
+    ///
+    /// Starting from off, the pump first runs at the priming speed for the priming time. When
+    /// the new speed is too slow for a running heater, the heater is switched off straight
+    /// away and the speed change waits out the cool-down.
+    pub async fn on(&mut self, speed: PoolPumpSpeed, source: ChangeSource) -> Result<(), Error> {
+        self.change_speed(speed, source, true).await
+    }
+
+    async fn change_speed(
+        &mut self,
+        speed: PoolPumpSpeed,
+        source: ChangeSource,
+        prime: bool,
+    ) -> Result<(), Error> {
+        let pending_change = self.pending.clone();
+        let mut pending = pending_change.lock().await;
+        let cooling_down = pending.as_ref().is_some_and(|change| {
+            change.transition == Transition::CoolingDown && !change.handle.is_finished()
+        });
+        if let Some(change) = pending.take() {
+            change.handle.abort();
         }
-        let high_result = self_clone.high.off().await;
-        if let Err(e) = high_result {
-            error!("Error setting pool pump off: {}", e);
+
+        if let Some(mut interlock) = self.heater_interlock.clone() {
+            if speed < interlock.min_speed && (cooling_down || interlock.heater.is_on().await) {
+                self.heater_off(&mut interlock).await?;
+
+                tracing::info!(
+                    target = module_path!(),
+                    speed = speed.to_string(),
+                    cool_down_sec = interlock.cool_down_sec,
+                    "Pool heater off; cooling down before changing pool pump speed"
+                );
+
+                let mut pump = self.clone();
+                *pending = Some(PendingChange {
+                    transition: Transition::CoolingDown,
+                    target: speed,
+                    handle: tokio::spawn(async move {
+                        sleep(Duration::from_secs(interlock.cool_down_sec)).await;
+                        let pending = pump.pending.clone();
+                        let _pending = pending.lock().await;
+                        if let Err(e) = pump.set_speed(speed, source).await {
+                            error!("Error changing pool pump speed after cool-down: {}", e);
+                        }
+                    }),
+                });
+
+                return Ok(());
+            }
         }
-        let max_result = self_clone.max.off().await;
-        if let Err(e) = max_result {
-            error!("Error setting pool pump off: {}", e);
+
+        if let Some((priming_speed, priming_sec)) = self.priming.filter(|_| prime) {
+            if speed != PoolPumpSpeed::Off
+                && speed < priming_speed
+                && self.speed().await == PoolPumpSpeed::Off
+            {
+                tracing::info!(
+                    target = module_path!(),
+                    speed = speed.to_string(),
+                    priming_sec,
+                    "Priming pool pump"
+                );
+
+                self.set_speed(priming_speed, source).await?;
+
+                let mut pump = self.clone();
+                *pending = Some(PendingChange {
+                    transition: Transition::Priming,
+                    target: speed,
+                    handle: tokio::spawn(async move {
+                        sleep(Duration::from_secs(priming_sec)).await;
+                        if let Err(e) = pump.settle(speed, source).await {
+                            error!("Error changing pool pump speed after priming: {}", e);
+                        }
+                    }),
+                });
+
+                return Ok(());
+            }
         }
 
-        self.current = PoolPumpSpeed::Off;
+        self.set_speed(speed, source).await?;
+        drop(pending);
 
         Ok(())
     }
 
-    /// Sets the new speed on the pump. This pump accepts four 5v inputs, and
+    /// Drops any pending speed change, leaving the pump as it is.
+    pub async fn cancel_pending(&self) {
+        if let Some(change) = self.pending.lock().await.take() {
+            change.handle.abort();
+        }
+    }
+
+    /// Stops priming and goes straight to the requested speed. Returns false when the pump
+    /// wasn't priming.
+    pub async fn cancel_priming(&mut self, source: ChangeSource) -> Result<bool, Error> {
+        let pending = self.pending.lock().await;
+        let target = match pending.as_ref() {
+            Some(change)
+                if change.transition == Transition::Priming && !change.handle.is_finished() =>
+            {
+                change.target
+            }
+            _ => return Ok(false),
+        };
+        drop(pending);
+
+        tracing::info!(target = module_path!(), "Pool pump priming cancelled");
+        self.change_speed(target, source, false).await?;
+
+        Ok(true)
+    }
+
+    /// Drops from the priming speed to `speed`. The heater may have been switched on while
+    /// priming, in which case it is cooled down first, as `on` would.
+    async fn settle(&mut self, speed: PoolPumpSpeed, source: ChangeSource) -> Result<(), Error> {
+        let pending_change = self.pending.clone();
+        let mut pending = pending_change.lock().await;
+
+        if let Some(mut interlock) = self.heater_interlock.clone() {
+            if speed < interlock.min_speed && interlock.heater.is_on().await {
+                if let Err(e) = self.heater_off(&mut interlock).await {
+                    error!("Error switching off the pool heater: {}", e);
+                }
+
+                if let Some(change) = pending.as_mut() {
+                    change.transition = Transition::CoolingDown;
+                }
+                drop(pending);
+                sleep(Duration::from_secs(interlock.cool_down_sec)).await;
+                pending = pending_change.lock().await;
+            }
+        }
+
+        self.set_speed(speed, source).await?;
+        drop(pending);
+
+        Ok(())
+    }
+
+    async fn heater_off(&self, interlock: &mut HeaterInterlock) -> Result<(), Error> {
+        let was_on = interlock.heater.is_on().await;
+        interlock.heater.off(ChangeSource::Interlock).await?;
+
+        if let Some(event_log) = self.event_log.filter(|_| was_on) {
+            event_log.record(HEATER, HEATER_OFF).await;
+        }
+
+        Ok(())
+    }
+
+    /// Switches the speed inputs. This pump accepts four 5v inputs, and
     /// will set the speed according to the highest speed input that is active.
     /// For this reason, the new speed input is raised before lowering the pin
     /// for the old speed as to avoid an extra shift to the "off" state
     /// between speed changes.
-    pub async fn on(&mut self, speed: PoolPumpSpeed) -> Result<(), Error> {
+    async fn set_speed(&mut self, speed: PoolPumpSpeed, source: ChangeSource) -> Result<(), Error> {
+        let previous = self.speed().await;
+
         match speed {
             PoolPumpSpeed::Off => {
-                self.turn_off_all(None).await;
-                self.current = PoolPumpSpeed::Off;
+                self.turn_off_all(None, source).await?;
             }
             PoolPumpSpeed::Low => {
-                self.turn_off_all(Some(PoolPumpSpeed::Low)).await;
-                let _ = self.low.on().await;
-                self.current = PoolPumpSpeed::Low;
+                self.turn_off_all(Some(PoolPumpSpeed::Low), source).await?;
+                self.low.on(source).await?;
             }
             PoolPumpSpeed::Med => {
-                self.turn_off_all(Some(PoolPumpSpeed::Med)).await;
-                let _ = self.med.on().await;
-                self.current = PoolPumpSpeed::Med;
+                self.turn_off_all(Some(PoolPumpSpeed::Med), source).await?;
+                self.med.on(source).await?;
             }
             PoolPumpSpeed::High => {
-                self.turn_off_all(Some(PoolPumpSpeed::High)).await;
-                let _ = self.high.on().await;
-                self.current = PoolPumpSpeed::High;
+                self.turn_off_all(Some(PoolPumpSpeed::High), source).await?;
+                self.high.on(source).await?;
             }
             PoolPumpSpeed::Max => {
-                self.turn_off_all(Some(PoolPumpSpeed::Max)).await;
-                let _ = self.max.on().await;
-                self.current = PoolPumpSpeed::Max;
+                self.turn_off_all(Some(PoolPumpSpeed::Max), source).await?;
+                self.max.on(source).await?;
             }
         };
 
+        if let Some(event_log) = self.event_log.filter(|_| previous != speed) {
+            event_log.record(POOL_PUMP, &speed.to_string()).await;
+        }
+
         Ok(())
     }
 
+    /// The speed last set, whether or not the pins agree; see `speed` for what they read.
+    pub fn commanded_speed(&self) -> PoolPumpSpeed {
+        if self.max.command().on {
+            PoolPumpSpeed::Max
+        } else if self.high.command().on {
+            PoolPumpSpeed::High
+        } else if self.med.command().on {
+            PoolPumpSpeed::Med
+        } else if self.low.command().on {
+            PoolPumpSpeed::Low
+        } else {
+            PoolPumpSpeed::Off
+        }
+    }
+
+    /// The speed last asked for, counting a change still waiting on priming or a cool-down,
+    /// and who asked for it.
+    pub async fn requested(&self) -> (PoolPumpSpeed, Option<ChangeSource>) {
+        let pending = self
+            .pending
+            .lock()
+            .await
+            .as_ref()
+            .filter(|change| !change.handle.is_finished())
+            .map(|change| change.target);
+        let commanded = self.commanded_speed();
+        let control = match commanded {
+            PoolPumpSpeed::Max => &self.max,
+            PoolPumpSpeed::High => &self.high,
+            PoolPumpSpeed::Med => &self.med,
+            PoolPumpSpeed::Low | PoolPumpSpeed::Off => &self.low,
+        };
+
+        (pending.unwrap_or(commanded), control.command().changed_by)
+    }
+
     pub async fn speed(&self) -> PoolPumpSpeed {
         let mut current_speed = PoolPumpSpeed::Off;
 
@@ -119,30 +453,30 @@ impl PoolPump {
         current_speed
     }
 
-    async fn turn_off_all(&mut self, skip: Option<PoolPumpSpeed>) {
-        if skip != Some(PoolPumpSpeed::Low) {
-            if let Err(e) = self.low.off().await {
-                error!("Error removing pool pump low setting: {}", e);
-            };
-        }
-
-        if skip != Some(PoolPumpSpeed::Med) {
-            if let Err(e) = self.med.off().await {
-                error!("Error removing pool pump med setting: {}", e);
-            };
-        }
-
-        if skip != Some(PoolPumpSpeed::High) {
-            if let Err(e) = self.high.off().await {
-                error!("Error removing pool pump high setting: {}", e);
-            };
+    /// Removes every speed setting but `skip`. Each is tried even if another fails, and the
+    /// first failure is returned.
+    async fn turn_off_all(
+        &mut self,
+        skip: Option<PoolPumpSpeed>,
+        source: ChangeSource,
+    ) -> Result<(), Error> {
+        let mut result = Ok(());
+        for (speed, control) in [
+            (PoolPumpSpeed::Low, &mut self.low),
+            (PoolPumpSpeed::Med, &mut self.med),
+            (PoolPumpSpeed::High, &mut self.high),
+            (PoolPumpSpeed::Max, &mut self.max),
+        ] {
+            if skip == Some(speed) {
+                continue;
+            }
+            if let Err(e) = control.off(source).await {
+                error!("Error removing pool pump {} setting: {}", speed, e);
+                result = result.and(Err(e));
+            }
         }
 
-        if skip != Some(PoolPumpSpeed::Max) {
-            if let Err(e) = self.max.off().await {
-                error!("Error removing pool pump max setting: {}", e);
-            };
-        }
+        result
     }
 }
 
@@ -151,7 +485,10 @@ mod tests {
 
     use crate::{
         hydro::gpio::MockGpio,
-        test_fixtures::{gpio::mock_pool_pump, settings::SETTINGS},
+        test_fixtures::{
+            gpio::{mock_heater, mock_pool_pump},
+            settings::SETTINGS,
+        },
     };
 
     use super::*;
@@ -162,6 +499,89 @@ mod tests {
         let mock_gpio = mock_pool_pump(mock_gpio, PoolPumpSpeed::Low);
         let pool_pump = PoolPump::new(&SETTINGS.hydro.pool_pump, &mock_gpio).unwrap();
 
-        assert_eq!(pool_pump.current, PoolPumpSpeed::Off);
+        assert_eq!(pool_pump.commanded_speed(), PoolPumpSpeed::Off);
+    }
+
+    #[tokio::test]
+    async fn test_pool_pump_cools_down_heater() {
+        let mock_gpio = mock_pool_pump(MockGpio::new(), PoolPumpSpeed::Max);
+        let mock_gpio = mock_heater(mock_gpio, true);
+        let mut pool_pump = PoolPump::new(&SETTINGS.hydro.pool_pump, &mock_gpio).unwrap();
+        let heater = Control::new(
+            "Pool Heater".into(),
+            SETTINGS.hydro.heater.control_pin,
+            &mock_gpio,
+        )
+        .unwrap();
+        pool_pump.interlock_heater(heater, &SETTINGS.hydro.heater);
+
+        pool_pump
+            .on(PoolPumpSpeed::Med, ChangeSource::Schedule)
+            .await
+            .unwrap();
+        assert!(!pool_pump.is_cooling_down().await);
+
+        pool_pump.off(ChangeSource::Schedule).await.unwrap();
+        assert!(pool_pump.is_cooling_down().await);
+
+        // Fast enough for the heater again, so the pending stop is dropped
+        pool_pump
+            .on(PoolPumpSpeed::High, ChangeSource::Schedule)
+            .await
+            .unwrap();
+        assert!(!pool_pump.is_cooling_down().await);
+    }
+
+    #[tokio::test]
+    async fn test_pool_pump_priming() {
+        let mock_gpio = mock_pool_pump(MockGpio::new(), PoolPumpSpeed::Off);
+        let mut pool_pump = PoolPump::new(&SETTINGS.hydro.pool_pump, &mock_gpio).unwrap();
+
+        pool_pump
+            .on(PoolPumpSpeed::Low, ChangeSource::Schedule)
+            .await
+            .unwrap();
+        assert!(pool_pump.is_priming().await);
+        assert_eq!(
+            pool_pump.commanded_speed(),
+            SETTINGS.hydro.pool_pump.priming_speed
+        );
+
+        let user = ChangeSource::User { id: 1 };
+        assert!(pool_pump.cancel_priming(user).await.unwrap());
+        assert!(!pool_pump.is_priming().await);
+        assert_eq!(pool_pump.commanded_speed(), PoolPumpSpeed::Low);
+        assert_eq!(pool_pump.low.command().changed_by, Some(user));
+
+        assert!(!pool_pump.cancel_priming(user).await.unwrap());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_flow_lock_holds_speed() {
+        let mock_gpio = mock_pool_pump(MockGpio::new(), PoolPumpSpeed::Max);
+        let pool_pump = PoolPump::new(&SETTINGS.hydro.pool_pump, &mock_gpio).unwrap();
+        let flow = pool_pump.lock_flow(PoolPumpSpeed::Low).await.unwrap();
+
+        let mut stopping = pool_pump.clone();
+        let stop = tokio::spawn(async move { stopping.off(ChangeSource::Schedule).await });
+        sleep(Duration::from_millis(100)).await;
+
+        // The stop waits for the heater to finish switching on
+        assert!(!stop.is_finished());
+        assert_eq!(pool_pump.max.command().changed_by, None);
+
+        drop(flow);
+        stop.await.unwrap().unwrap();
+        assert_eq!(
+            pool_pump.max.command().changed_by,
+            Some(ChangeSource::Schedule)
+        );
+    }
+
+    #[test]
+    fn test_pool_pump_speed_order() {
+        assert!(PoolPumpSpeed::Off < PoolPumpSpeed::Low);
+        assert!(PoolPumpSpeed::Med < PoolPumpSpeed::High);
+        assert!(PoolPumpSpeed::High < PoolPumpSpeed::Max);
     }
 }
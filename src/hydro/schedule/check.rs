@@ -41,11 +41,11 @@ fn due_statuses(status_list: Vec<ScheduleStatus>, now: NaiveDateTime) -> Vec<Sch
             }
 
             // An event already exists for today
-            return false;
+            false
         })
         .collect::<Vec<ScheduleStatus>>();
 
-    schedules_to_run.sort_by(|a, b| a.schedule.start_time.cmp(&b.schedule.start_time));
+    schedules_to_run.sort_by_key(|status| status.schedule.start_time);
 
     schedules_to_run
 }
@@ -85,6 +85,7 @@ mod tests {
             ),
             status: "Completed".to_string(),
             schedule_id: 1,
+            started_at: Some(last_friday_9pm - chrono::Duration::hours(2)),
         };
 
         let friday_schedule = ScheduleStatus {
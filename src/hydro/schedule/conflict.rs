@@ -0,0 +1,355 @@
+use chrono::{NaiveTime, Timelike, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::repository::models::irrigation_schedule::{
+    CreateIrrigationScheduleParams, IrrigationSchedule,
+};
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    /// The schedules' run windows intersect on the same day.
+    Overlap,
+    /// The schedule will start later than its start time because the queue is still busy.
+    QueueDelay,
+}
+
+/// A problem found when comparing a schedule against the other saved schedules.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ScheduleConflict {
+    pub kind: ConflictKind,
+    pub day: String,
+    /// The schedule affected by the conflict; `None` for a schedule that has not been saved yet.
+    pub schedule_id: Option<i32>,
+    pub schedule_name: String,
+    pub delay_seconds: i64,
+}
+
+/// The parts of a schedule needed to work out when its events will run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScheduleWindow {
+    pub id: Option<i32>,
+    pub name: String,
+    pub active: bool,
+    pub days: Vec<Weekday>,
+    pub start_time: NaiveTime,
+    /// Total seconds the schedule keeps the queue busy; one event per hose, run back to back.
+    pub run_seconds: i64,
+}
+
+impl From<&IrrigationSchedule> for ScheduleWindow {
+    fn from(schedule: &IrrigationSchedule) -> Self {
+        let hose_count = schedule
+            .hoses
+            .split(',')
+            .filter_map(|hose| hose.parse::<i32>().ok())
+            .count() as i64;
+
+        Self {
+            id: Some(schedule.id),
+            name: schedule.name.clone(),
+            active: schedule.active,
+            days: schedule
+                .days_of_week
+                .split(',')
+                .filter_map(|day| day.parse::<Weekday>().ok())
+                .collect(),
+            start_time: schedule.start_time,
+            run_seconds: schedule.duration as i64 * hose_count,
+        }
+    }
+}
+
+impl From<&CreateIrrigationScheduleParams> for ScheduleWindow {
+    fn from(params: &CreateIrrigationScheduleParams) -> Self {
+        Self {
+            id: None,
+            name: params.name.clone(),
+            active: params.active,
+            days: params.days_of_week.clone(),
+            start_time: params.start_time,
+            run_seconds: params.duration as i64 * params.hoses.len() as i64,
+        }
+    }
+}
+
+impl ScheduleWindow {
+    fn start_seconds(&self) -> i64 {
+        self.start_time.num_seconds_from_midnight() as i64
+    }
+}
+
+const DAY_SECONDS: i64 = 24 * 60 * 60;
+
+/// One day's run of a schedule. `start` is in seconds from the midnight beginning the day
+/// being checked, so a run from the day before starts below zero and one from the day after
+/// at a day's seconds or more.
+#[derive(Clone, Copy)]
+struct Run<'a> {
+    window: &'a ScheduleWindow,
+    day: Weekday,
+    start: i64,
+}
+
+impl Run<'_> {
+    fn end(&self) -> i64 {
+        self.start + self.window.run_seconds
+    }
+
+    fn overlaps(&self, other: &Run) -> bool {
+        self.start < other.end() && other.start < self.end()
+    }
+
+    fn is(&self, other: &Run) -> bool {
+        std::ptr::eq(self.window, other.window) && self.day == other.day
+    }
+}
+
+/// Compares `candidate` against the other saved schedules and reports any overlapping run
+/// windows, along with every schedule (the candidate included) that would start late because
+/// the candidate is in the queue. Events run strictly one at a time, so a schedule that starts
+/// while another is still running waits for it to finish. A run going past midnight is
+/// compared with the next day's runs too.
+///
+/// # Arguments
+///
+/// * `candidate` - The schedule about to be saved
+/// * `schedules` - The saved schedules; an entry with the candidate's id is ignored
+pub fn find_conflicts(
+    candidate: &ScheduleWindow,
+    schedules: &[ScheduleWindow],
+) -> Vec<ScheduleConflict> {
+    if !candidate.active {
+        return vec![];
+    }
+
+    let others: Vec<&ScheduleWindow> = schedules
+        .iter()
+        .filter(|s| s.active)
+        .filter(|s| candidate.id.is_none() || s.id != candidate.id)
+        .collect();
+
+    let mut conflicts = vec![];
+    for day in candidate.days.iter().copied() {
+        let candidate_run = Run {
+            window: candidate,
+            day,
+            start: candidate.start_seconds(),
+        };
+        // Runs late the day before may still be going, and the candidate may run into tomorrow
+        let nearby: Vec<Run> = [
+            (day.pred(), -DAY_SECONDS),
+            (day, 0),
+            (day.succ(), DAY_SECONDS),
+        ]
+        .into_iter()
+        .flat_map(|(run_day, offset)| {
+            others
+                .iter()
+                .filter(move |s| s.days.contains(&run_day))
+                .map(move |s| Run {
+                    window: s,
+                    day: run_day,
+                    start: offset + s.start_seconds(),
+                })
+        })
+        .collect();
+
+        for other in nearby.iter().filter(|run| run.overlaps(&candidate_run)) {
+            add_conflict(
+                &mut conflicts,
+                ScheduleConflict {
+                    kind: ConflictKind::Overlap,
+                    day: day.to_string(),
+                    schedule_id: other.window.id,
+                    schedule_name: other.window.name.clone(),
+                    delay_seconds: 0,
+                },
+            );
+        }
+
+        let before = queue_delays(&nearby);
+        let mut with_candidate = nearby.clone();
+        with_candidate.push(candidate_run);
+        let after = queue_delays(&with_candidate);
+
+        for (run, delay) in after {
+            let previous_delay = before
+                .iter()
+                .find(|(r, _)| r.is(&run))
+                .map(|(_, d)| *d)
+                .unwrap_or(0);
+
+            if delay > previous_delay {
+                add_conflict(
+                    &mut conflicts,
+                    ScheduleConflict {
+                        kind: ConflictKind::QueueDelay,
+                        day: run.day.to_string(),
+                        schedule_id: run.window.id,
+                        schedule_name: run.window.name.clone(),
+                        delay_seconds: delay,
+                    },
+                );
+            }
+        }
+    }
+
+    conflicts
+}
+
+/// Adds `conflict`, unless the same schedule already has one of its kind that day, in which
+/// case the longer delay is kept. Runs either side of midnight are checked from both days.
+fn add_conflict(conflicts: &mut Vec<ScheduleConflict>, conflict: ScheduleConflict) {
+    let existing = conflicts.iter_mut().find(|c| {
+        c.kind == conflict.kind && c.day == conflict.day && c.schedule_id == conflict.schedule_id
+    });
+
+    match existing {
+        Some(existing) => {
+            existing.delay_seconds = existing.delay_seconds.max(conflict.delay_seconds)
+        }
+        None => conflicts.push(conflict),
+    }
+}
+
+/// Simulates the FIFO event queue over a few days' runs and returns how late each starts.
+/// Runs sharing a start time keep their order, so a newly added schedule runs last.
+fn queue_delays<'a>(runs: &[Run<'a>]) -> Vec<(Run<'a>, i64)> {
+    let mut ordered = runs.to_vec();
+    ordered.sort_by_key(|run| run.start);
+
+    let mut queue_free_at = i64::MIN;
+    ordered
+        .into_iter()
+        .map(|run| {
+            let starts_at = queue_free_at.max(run.start);
+            queue_free_at = starts_at + run.window.run_seconds;
+
+            (run, starts_at - run.start)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveTime, Weekday};
+    use rstest::rstest;
+
+    use super::{find_conflicts, ConflictKind, ScheduleWindow};
+    use crate::repository::models::irrigation_schedule::IrrigationSchedule;
+    use crate::test_fixtures::irrigation::schedule::{
+        daily_schedule, deactivated_schedule, weekend_schedule,
+    };
+
+    fn candidate(start_time: &str, run_seconds: i64, days: Vec<Weekday>) -> ScheduleWindow {
+        ScheduleWindow {
+            id: None,
+            name: "Candidate".into(),
+            active: true,
+            days,
+            start_time: NaiveTime::parse_from_str(start_time, "%H:%M:%S").unwrap(),
+            run_seconds,
+        }
+    }
+
+    #[rstest]
+    fn test_window_from_schedule(daily_schedule: IrrigationSchedule) {
+        let window = ScheduleWindow::from(&daily_schedule);
+
+        assert_eq!(window.days.len(), 7);
+        // 15 seconds for each of four hoses
+        assert_eq!(window.run_seconds, 60);
+    }
+
+    #[rstest]
+    fn test_overlap_and_delay(daily_schedule: IrrigationSchedule) {
+        let saved = vec![ScheduleWindow::from(&daily_schedule)];
+        let candidate = candidate("12:00:30", 30, vec![Weekday::Mon]);
+
+        let conflicts = find_conflicts(&candidate, &saved);
+
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].kind, ConflictKind::Overlap);
+        assert_eq!(conflicts[0].schedule_id, Some(daily_schedule.id));
+        assert_eq!(conflicts[1].kind, ConflictKind::QueueDelay);
+        assert_eq!(conflicts[1].schedule_id, None);
+        assert_eq!(conflicts[1].delay_seconds, 30);
+    }
+
+    #[rstest]
+    fn test_delays_later_schedule(daily_schedule: IrrigationSchedule) {
+        let saved = vec![ScheduleWindow::from(&daily_schedule)];
+        // Starts before the saved schedule and runs past its start time
+        let candidate = candidate("11:59:00", 90, vec![Weekday::Tue]);
+
+        let conflicts = find_conflicts(&candidate, &saved);
+        let delay = conflicts
+            .iter()
+            .find(|c| c.kind == ConflictKind::QueueDelay)
+            .unwrap();
+
+        assert_eq!(delay.schedule_id, Some(daily_schedule.id));
+        assert_eq!(delay.delay_seconds, 30);
+    }
+
+    #[rstest]
+    fn test_no_conflicts(
+        daily_schedule: IrrigationSchedule,
+        deactivated_schedule: IrrigationSchedule,
+        weekend_schedule: IrrigationSchedule,
+    ) {
+        let saved = vec![
+            ScheduleWindow::from(&daily_schedule),
+            ScheduleWindow::from(&deactivated_schedule),
+        ];
+
+        // Finishes before the daily schedule starts
+        let early = candidate("11:00:00", 60, vec![Weekday::Mon]);
+        assert!(find_conflicts(&early, &saved).is_empty());
+
+        // A schedule is never compared against its own saved copy
+        let same = ScheduleWindow::from(&daily_schedule);
+        assert!(find_conflicts(&same, &saved).is_empty());
+
+        // Inactive schedules never conflict
+        let mut inactive = ScheduleWindow::from(&weekend_schedule);
+        inactive.id = Some(99);
+        inactive.active = false;
+        assert!(find_conflicts(&inactive, &saved).is_empty());
+    }
+
+    #[test]
+    fn test_conflicts_past_midnight() {
+        let mut early = candidate("00:10:00", 600, vec![Weekday::Tue]);
+        early.id = Some(7);
+        early.name = "Early".into();
+        // Runs from 23:30 Monday until 00:30 Tuesday
+        let late = candidate("23:30:00", 3600, vec![Weekday::Mon]);
+
+        let conflicts = find_conflicts(&late, &[early.clone()]);
+
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].kind, ConflictKind::Overlap);
+        assert_eq!(conflicts[0].day, "Mon");
+        assert_eq!(conflicts[0].schedule_id, Some(7));
+        assert_eq!(conflicts[1].kind, ConflictKind::QueueDelay);
+        assert_eq!(conflicts[1].day, "Tue");
+        assert_eq!(conflicts[1].schedule_id, Some(7));
+        assert_eq!(conflicts[1].delay_seconds, 1200);
+
+        // The same clash, seen from the schedule after midnight
+        let mut saved = late.clone();
+        saved.id = Some(8);
+        early.id = None;
+        let conflicts = find_conflicts(&early, &[saved]);
+
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].kind, ConflictKind::Overlap);
+        assert_eq!(conflicts[0].schedule_id, Some(8));
+        assert_eq!(conflicts[1].kind, ConflictKind::QueueDelay);
+        assert_eq!(conflicts[1].day, "Tue");
+        assert_eq!(conflicts[1].schedule_id, None);
+        assert_eq!(conflicts[1].delay_seconds, 1200);
+    }
+}
@@ -1,4 +1,6 @@
 pub mod check;
+pub mod conflict;
+pub mod pool_pump;
 pub mod run;
 
 use tokio::task::JoinHandle;
@@ -11,7 +13,7 @@ use crate::repository::{
 };
 
 use self::run::run_irrigation_event;
-use super::irrigator::Irrigator;
+use super::{budget::WaterBudget, irrigator::Irrigator, watchdog::Heartbeat};
 
 /// Represents an IrrigationSchedule and its most recent IrrigationEvent
 #[derive(Clone, Debug, PartialEq)]
@@ -28,10 +30,19 @@ pub struct ScheduleStatus {
 ///
 ///  * `db` - Handle to the database pool
 ///  * `sump` - Instance of the Sump object for running IrrigationEvents
+///  * `budget` - Limits each event to the water in the reservoir, when water budget mode is on
+///  * `heartbeat` - Beaten on each tick, and while an event runs or the tick waits
 ///
-pub fn start(repo: Repo, irrigator: Irrigator, frequency_sec: u64) -> JoinHandle<()> {
+pub fn start(
+    repo: Repo,
+    irrigator: Irrigator,
+    frequency_sec: u64,
+    budget: Option<WaterBudget>,
+    heartbeat: Heartbeat,
+) -> JoinHandle<()> {
     tokio::spawn(async move {
         loop {
+            heartbeat.beat();
             let statuses = match check_schedule(repo).await {
                 Ok(status) => status,
                 Err(e) => {
@@ -50,9 +61,12 @@ pub fn start(repo: Repo, irrigator: Irrigator, frequency_sec: u64) -> JoinHandle
                 continue;
             }
 
+            // Beaten from inside the run, so one that hangs stalls the watchdog
             let irrigator = irrigator.clone();
-            run_irrigation_event(repo, &irrigator).await;
-            sleep(Duration::from_secs(frequency_sec)).await;
+            run_irrigation_event(repo, &irrigator, budget.as_ref(), &heartbeat).await;
+            heartbeat
+                .during(sleep(Duration::from_secs(frequency_sec)))
+                .await;
         }
     })
 }
@@ -0,0 +1,268 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use chrono::{Datelike, Duration as ChronoDuration, NaiveDateTime, Utc};
+use tokio::time::{sleep, Duration};
+
+use crate::hydro::control::ChangeSource;
+use crate::hydro::freeze::FreezeProtection;
+use crate::hydro::pool_pump::{PoolPump, PoolPumpSpeed};
+use crate::repository::{models::pool_pump_schedule::PoolPumpSchedule, Repo};
+
+/// Set while the pool pump is being driven by hand. The scheduler leaves the pump alone until
+/// the next program boundary, or until the override is explicitly resumed.
+#[derive(Clone, Debug, Default)]
+pub struct ManualOverride(Arc<AtomicBool>);
+
+impl ManualOverride {
+    pub fn hold(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_held(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// The speed the programs call for, and the program calling for it; `None` when no program
+/// is running and the pump should be off.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Target {
+    pub schedule_id: Option<i32>,
+    pub speed: PoolPumpSpeed,
+}
+
+/// When the run of `schedule` covering `now` began, if there is one.
+pub fn running_since(schedule: &PoolPumpSchedule, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    let days = schedule.days();
+    let time = now.time();
+    let today = now.date();
+
+    if schedule.start_time < schedule.end_time {
+        let running = days.contains(&today.weekday())
+            && schedule.start_time <= time
+            && time < schedule.end_time;
+
+        return running.then(|| today.and_time(schedule.start_time));
+    }
+
+    // The program runs past midnight
+    if days.contains(&today.weekday()) && schedule.start_time <= time {
+        return Some(today.and_time(schedule.start_time));
+    }
+
+    let yesterday = today - ChronoDuration::days(1);
+    if days.contains(&yesterday.weekday()) && time < schedule.end_time {
+        return Some(yesterday.and_time(schedule.start_time));
+    }
+
+    None
+}
+
+/// Works out what the pump should be doing at `now`. Where programs overlap, the one that
+/// started most recently wins. Returns `None` when there are no active programs, which leaves
+/// the pump under manual control only.
+pub fn target(schedules: &[PoolPumpSchedule], now: NaiveDateTime) -> Option<Target> {
+    let active: Vec<&PoolPumpSchedule> = schedules.iter().filter(|s| s.active).collect();
+    if active.is_empty() {
+        return None;
+    }
+
+    let running = active
+        .into_iter()
+        .filter_map(|schedule| Some((running_since(schedule, now)?, schedule)))
+        .max_by_key(|(since, schedule)| (*since, schedule.id));
+
+    Some(match running {
+        Some((_, schedule)) => Target {
+            schedule_id: Some(schedule.id),
+            speed: schedule.pump_speed(),
+        },
+        None => Target {
+            schedule_id: None,
+            speed: PoolPumpSpeed::Off,
+        },
+    })
+}
+
+/// Intended to be run at startup, once any saved state is restored. On each tick the pool pump
+/// programs are checked, and the pump is set to the programmed speed unless a manual override
+/// is being held. Crossing into a different program releases the override. Freeze protection,
+/// while active, takes precedence over the programs.
+///
+///  # Arguments
+///
+///  * `repo` - Handle to the database
+///  * `pool_pump` - The pump to drive
+///  * `manual_override` - Shared with the API, which holds it when the pump is set by hand
+///  * `freeze_protection` - Suspends the programs while active
+///  * `frequency_sec` - Seconds between ticks
+///
+pub async fn run(
+    repo: Repo,
+    mut pool_pump: PoolPump,
+    manual_override: ManualOverride,
+    freeze_protection: Option<FreezeProtection>,
+    frequency_sec: u64,
+) {
+    let mut last_target: Option<Target> = None;
+    // An override restored at startup holds until the next boundary, like any other
+    let mut first_tick = true;
+
+    loop {
+        match repo.pool_pump_schedules().await {
+            Ok(schedules) => {
+                let next_target = target(&schedules, Utc::now().naive_utc());
+
+                if let Some(next) = next_target {
+                    if !first_tick && last_target != next_target {
+                        manual_override.resume();
+                    }
+
+                    let freezing = freeze_protection.as_ref().is_some_and(|f| f.is_active());
+
+                    // Priming or a cool-down already has a speed change pending;
+                    // re-issuing it each tick would keep restarting it
+                    if !manual_override.is_held()
+                        && !freezing
+                        && pool_pump.transition().await.is_none()
+                        && pool_pump.speed().await != next.speed
+                    {
+                        tracing::info!(
+                            target = module_path!(),
+                            speed = next.speed.to_string(),
+                            schedule_id = next.schedule_id,
+                            "Setting programmed pool pump speed"
+                        );
+
+                        if let Err(e) = pool_pump.on(next.speed, ChangeSource::Schedule).await {
+                            tracing::error!(
+                                target = module_path!(),
+                                error = e.to_string(),
+                                "Could not set programmed pool pump speed"
+                            );
+                        }
+                    }
+                }
+
+                last_target = next_target;
+                first_tick = false;
+            }
+            Err(e) => {
+                tracing::error!(
+                    target = module_path!(),
+                    error = e.to_string(),
+                    "Could not get pool pump schedules"
+                );
+            }
+        }
+
+        sleep(Duration::from_secs(frequency_sec)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDateTime, NaiveTime};
+    use rstest::rstest;
+
+    use super::{running_since, target, ManualOverride, Target};
+    use crate::hydro::pool_pump::PoolPumpSpeed;
+    use crate::repository::models::pool_pump_schedule::PoolPumpSchedule;
+
+    fn at(time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn program(id: i32, speed: &str, start: &str, end: &str, days: &str) -> PoolPumpSchedule {
+        PoolPumpSchedule {
+            id,
+            active: true,
+            name: format!("{} program", speed),
+            speed: speed.into(),
+            start_time: NaiveTime::parse_from_str(start, "%H:%M").unwrap(),
+            end_time: NaiveTime::parse_from_str(end, "%H:%M").unwrap(),
+            days_of_week: days.into(),
+            created_at: at("2021-01-01 00:00:00"),
+            updated_at: at("2021-01-01 00:00:00"),
+        }
+    }
+
+    fn programs() -> Vec<PoolPumpSchedule> {
+        vec![
+            program(1, "low", "08:00", "12:00", "Mon,Tue,Wed,Thu,Fri,Sat,Sun"),
+            program(2, "high", "12:00", "14:00", "Mon,Tue,Wed,Thu,Fri,Sat,Sun"),
+        ]
+    }
+
+    // 2021-01-04 was a Monday
+    #[rstest]
+    #[case("2021-01-04 07:59:59", None, PoolPumpSpeed::Off)]
+    #[case("2021-01-04 08:00:00", Some(1), PoolPumpSpeed::Low)]
+    #[case("2021-01-04 12:00:00", Some(2), PoolPumpSpeed::High)]
+    #[case("2021-01-04 14:00:00", None, PoolPumpSpeed::Off)]
+    fn test_target(
+        #[case] now: &str,
+        #[case] schedule_id: Option<i32>,
+        #[case] speed: PoolPumpSpeed,
+    ) {
+        assert_eq!(
+            target(&programs(), at(now)),
+            Some(Target { schedule_id, speed })
+        );
+    }
+
+    #[rstest]
+    fn test_target_without_programs() {
+        let mut programs = programs();
+        programs.iter_mut().for_each(|p| p.active = false);
+
+        assert_eq!(target(&programs, at("2021-01-04 09:00:00")), None);
+        assert_eq!(target(&[], at("2021-01-04 09:00:00")), None);
+    }
+
+    #[rstest]
+    fn test_overnight_program() {
+        let overnight = program(3, "med", "22:00", "02:00", "Mon");
+
+        assert_eq!(
+            running_since(&overnight, at("2021-01-04 23:00:00")),
+            Some(at("2021-01-04 22:00:00"))
+        );
+        assert_eq!(
+            running_since(&overnight, at("2021-01-05 01:00:00")),
+            Some(at("2021-01-04 22:00:00"))
+        );
+        // Only scheduled to start on Mondays
+        assert_eq!(running_since(&overnight, at("2021-01-05 23:00:00")), None);
+    }
+
+    #[rstest]
+    fn test_latest_program_wins() {
+        let mut programs = programs();
+        programs.push(program(3, "max", "09:00", "10:00", "Mon"));
+
+        let target = target(&programs, at("2021-01-04 09:30:00")).unwrap();
+
+        assert_eq!(target.schedule_id, Some(3));
+        assert_eq!(target.speed, PoolPumpSpeed::Max);
+    }
+
+    #[rstest]
+    fn test_manual_override() {
+        let manual_override = ManualOverride::default();
+        let shared = manual_override.clone();
+
+        shared.hold();
+        assert!(manual_override.is_held());
+
+        shared.resume();
+        assert!(!manual_override.is_held());
+    }
+}
@@ -1,23 +1,40 @@
 use anyhow::{anyhow, Error};
-use std::time::{Duration, SystemTime};
-
-use tokio::time::sleep;
-
-use crate::hydro::{control::Control, schedule::IrrigationEvent, sensor::Input, Irrigator};
+use std::time::Duration;
+
+use tokio::time::{sleep, Instant};
+
+use crate::hydro::{
+    budget::{Allowance, WaterBudget},
+    control::{ChangeSource, Control, Output},
+    schedule::IrrigationEvent,
+    sensor::Input,
+    watchdog::Heartbeat,
+    Irrigator,
+};
 use crate::repository::Repo;
 
-pub async fn run_irrigation_event(repo: Repo, irrigator: &Irrigator) {
-    // Get the next event
-    let (event, schedule) = match repo.next_queued_irrigation_event().await {
-        Ok(dur_event) => match dur_event {
-            Some(dur_event) => dur_event,
-            None => return,
-        },
+/// Runs the first queued event that can run. With a water budget each event is shortened to
+/// the water the reservoir is estimated to hold, or skipped for this tick when there is too
+/// little, leaving it queued while the events behind it get a turn; the low sensor still
+/// stops anything from running on an empty reservoir.
+///
+/// `heartbeat` is beaten as the run counts down, so a run that stops getting anywhere stalls
+/// it.
+pub async fn run_irrigation_event(
+    repo: Repo,
+    irrigator: &Irrigator,
+    budget: Option<&WaterBudget>,
+    heartbeat: &Heartbeat,
+) {
+    // Get the queued events, oldest first
+    let queued = match repo.queued_irrigation_events().await {
+        Ok(queued) if queued.is_empty() => return,
+        Ok(queued) => queued,
         Err(e) => {
             tracing::error!(
                 target = module_path!(),
                 error = e.to_string(),
-                "Error getting next irrigation event"
+                "Error getting queued irrigation events"
             );
             return;
         }
@@ -31,25 +48,80 @@ pub async fn run_irrigation_event(repo: Repo, irrigator: &Irrigator) {
         return;
     }
 
-    // Start the irrigation
-    if let Err(err) = irrigate(repo, event, schedule.duration, irrigator).await {
-        tracing::error!(
-            target = module_path!(),
-            error = err.to_string(),
-            "Failed to start irrigation"
-        );
+    let available = match budget {
+        Some(budget) => match budget.available_litres(repo).await {
+            Ok(available) => {
+                tracing::info!(
+                    target = module_path!(),
+                    available_litres = available,
+                    "Estimated reservoir volume"
+                );
+                Some(available)
+            }
+            Err(e) => {
+                tracing::error!(
+                    target = module_path!(),
+                    error = e.to_string(),
+                    "Could not estimate the water budget"
+                );
+                None
+            }
+        },
+        None => None,
+    };
+
+    for (event, schedule) in queued {
+        let allowance = match (budget, available) {
+            (Some(budget), Some(available)) => {
+                budget.allowance(available, &event, schedule.duration)
+            }
+            _ => Allowance::Full,
+        };
+
+        let duration = match allowance {
+            Allowance::Full => schedule.duration,
+            Allowance::Shortened(seconds) => {
+                tracing::info!(
+                    target = module_path!(),
+                    hose_id = event.hose_id,
+                    seconds,
+                    "Shortening irrigation to fit the water budget"
+                );
+                seconds
+            }
+            Allowance::Deferred => {
+                tracing::info!(
+                    target = module_path!(),
+                    hose_id = event.hose_id,
+                    "Not enough water in the budget; deferring irrigation"
+                );
+                continue;
+            }
+        };
+
+        // Start the irrigation
+        if let Err(err) = irrigate(repo, event, duration, irrigator, heartbeat).await {
+            tracing::error!(
+                target = module_path!(),
+                error = err.to_string(),
+                "Failed to start irrigation"
+            );
+        }
+
+        return;
     }
 }
 
-#[tracing::instrument(skip(irrigator, repo))]
+#[tracing::instrument(skip(irrigator, repo, heartbeat))]
 pub async fn irrigate(
     repo: Repo,
     event: IrrigationEvent,
     duration: i32,
     irrigator: &Irrigator,
+    heartbeat: &Heartbeat,
 ) -> Result<(), Error> {
     tracing::info!(target = module_path!(), "Starting irrigation job");
-    let start_time = SystemTime::now();
+    let start_time = Instant::now();
 
     match repo.begin_irrigation(event.clone()).await {
         Ok(()) => (),
@@ -63,7 +135,7 @@ pub async fn irrigate(
         }
     }
 
-    let hose = match event_hose_pin(&event, irrigator) {
+    let mut hose = match event_hose_pin(&event, irrigator) {
         Ok(hose) => hose,
         Err(e) => {
             tracing::error!(
@@ -75,35 +147,30 @@ pub async fn irrigate(
         }
     };
 
-    let hose_pin = hose.pin.clone();
-    let pump_pin = irrigator.pump.pin.clone();
+    let mut pump = irrigator.pump.clone();
 
     // Open the solenoid and start the pump
-    let mut hose_lock = hose_pin.lock().await;
-    hose_lock.on();
-    drop(hose_lock);
-
-    let mut pump_lock = pump_pin.lock().await;
-    pump_lock.on();
-    drop(pump_lock);
+    hose.on(ChangeSource::Schedule).await?;
+    if let Err(e) = pump.on(ChangeSource::Schedule).await {
+        hose.off(ChangeSource::Schedule).await?;
+        return Err(e);
+    }
 
     // // Wait for the job to finish
     let duration = Duration::from_secs(duration as u64);
     let mut is_job_done = job_complete(duration, start_time);
     while !is_job_done {
         sleep(tokio::time::Duration::from_secs(1)).await;
+        heartbeat.beat();
         is_job_done = job_complete(duration, start_time);
     }
 
     tracing::info!(target = module_path!(), "Stopping irrigation job");
 
-    // Stop the pump and close the solenoid
-    let mut pump_lock = irrigator.pump.pin.lock().await;
-    pump_lock.off();
-
-    // Open the solenoid and start the pump
-    let mut hose_lock = hose.pin.lock().await;
-    hose_lock.off();
+    // Stop the pump and close the solenoid, closing it even if the pump fails to stop
+    let pump_off = pump.off(ChangeSource::Schedule).await;
+    hose.off(ChangeSource::Schedule).await?;
+    pump_off?;
 
     // Move the job out of "in progress" status
     if let Err(e) = repo.finish_irrigation_event().await {
@@ -139,58 +206,45 @@ fn event_hose_pin(event: &IrrigationEvent, irrigator: &Irrigator) -> Result<Cont
     }
 }
 
-fn job_complete(duration: Duration, start_time: SystemTime) -> bool {
-    match SystemTime::now().duration_since(start_time) {
-        Ok(elapsed) => elapsed >= duration,
-        Err(e) => {
-            tracing::error!(
-                target = module_path!(),
-                error = e.to_string(),
-                "Could not get duration since start time for job complete calculation"
-            );
-            true
-        }
-    }
+fn job_complete(duration: Duration, start_time: Instant) -> bool {
+    start_time.elapsed() >= duration
 }
 
 #[cfg(test)]
 mod tests {
+    use chrono::{Duration as ChronoDuration, Utc};
     use mockall::predicate;
     use rstest::rstest;
 
+    use crate::hydro::budget::WaterBudget;
     use crate::hydro::control::Control;
     use crate::hydro::gpio::{Level, MockGpio, MockInputPin, MockPin, Trigger};
     use crate::hydro::irrigator::Irrigator;
     use crate::hydro::schedule::run::{event_hose_pin, job_complete, run_irrigation_event};
     use crate::hydro::sensor::Sensor;
     use crate::hydro::signal::Message;
-    use crate::repository::models::irrigation_event::IrrigationEvent;
+    use crate::hydro::watchdog::Health;
+    use crate::repository::models::{
+        irrigation_event::{IrrigationEvent, IrrigationEventStatus},
+        irrigation_schedule::IrrigationSchedule,
+        sump_event::{SumpEvent, PUMP_OFF, PUMP_ON, SUMP_PUMP_KIND},
+    };
     use crate::test_fixtures::gpio::mock_gpio_get;
+    use crate::test_fixtures::settings::SETTINGS;
     use crate::{
         repository::{MockRepository, Repository},
-        test_fixtures::irrigation::{event::completed_event, irrigator::irrigator},
+        test_fixtures::irrigation::{
+            event::completed_event, irrigator::irrigator, schedule::daily_schedule,
+        },
     };
 
-    use std::time::{Duration, SystemTime};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::time::Instant;
 
-    #[rstest]
-    #[tokio::test]
-    async fn test_run_next_event() {
-        let mut mock_repo = MockRepository::new();
-
-        let _ = mock_repo
-            .expect_next_queued_irrigation_event()
-            .returning(|| Ok(None));
-
-        let _ = mock_repo
-            .expect_finish_irrigation_event()
-            .returning(|| Ok(()));
-        let repo = Box::new(mock_repo);
-
-        let repo_static: &'static dyn Repository = Box::leak(repo);
-
-        // TODO: combine this with the irrigation fixture in a way that
-        // provides the runtime for unit tests (rt cannot drop in sync context)
+    /// An irrigator on mock pins, with water above the low sensor, built on the test's own
+    /// runtime.
+    fn current_irrigator() -> Irrigator {
         let mut mock_gpio: MockGpio = mock_gpio_get(vec![1, 2, 3, 4, 5]);
         let (tx, _rx) = tokio::sync::mpsc::channel(32);
 
@@ -230,16 +284,179 @@ mod tests {
         )
         .unwrap();
 
-        let irrigator = Irrigator {
+        Irrigator {
             low_sensor,
             pump,
             valve1,
             valve2,
             valve3,
             valve4,
+        }
+    }
+
+    /// A budget where the sump has reclaimed 0.6 litres: nothing for hose 1 at 60 litres a
+    /// minute, and six seconds for hose 2 at 6.
+    fn budget_and_sump_events() -> (WaterBudget, Vec<SumpEvent>) {
+        let mut irrigation = SETTINGS.hydro.irrigation.clone();
+        irrigation.water_budget = true;
+        irrigation.valve_1_flow_rate = Some(60.0);
+        irrigation.valve_2_flow_rate = Some(6.0);
+        let budget = WaterBudget {
+            irrigation,
+            sump_flow_rate: 12.0,
         };
 
-        run_irrigation_event(repo_static, &irrigator).await;
+        let pump_on = Utc::now().naive_utc() - ChronoDuration::hours(1);
+        let sump_events = vec![
+            SumpEvent {
+                id: 1,
+                kind: SUMP_PUMP_KIND.into(),
+                info: PUMP_ON.into(),
+                created_at: pump_on,
+            },
+            SumpEvent {
+                id: 2,
+                kind: SUMP_PUMP_KIND.into(),
+                info: PUMP_OFF.into(),
+                created_at: pump_on + ChronoDuration::seconds(3),
+            },
+        ];
+
+        (budget, sump_events)
+    }
+
+    fn queued_event(id: i32, hose_id: i32) -> IrrigationEvent {
+        completed_event(
+            id,
+            hose_id,
+            Utc::now().naive_utc(),
+            None,
+            IrrigationEventStatus::Queued,
+            1,
+            None,
+        )
+    }
+
+    /// A repo queueing `queued` against a one minute schedule. The ids of the events begun
+    /// are collected in the returned list; a leaked mock never checks its expectations.
+    fn budget_repo(
+        queued: Vec<IrrigationEvent>,
+        sump_events: Vec<SumpEvent>,
+        mut schedule: IrrigationSchedule,
+    ) -> (&'static dyn Repository, Arc<Mutex<Vec<i32>>>) {
+        schedule.duration = 60;
+        let begun = Arc::new(Mutex::new(vec![]));
+        let begun_events = Arc::clone(&begun);
+
+        let mut mock_repo = MockRepository::new();
+        mock_repo
+            .expect_queued_irrigation_events()
+            .returning(move || {
+                Ok(queued
+                    .iter()
+                    .map(|event| (event.clone(), schedule.clone()))
+                    .collect())
+            });
+        mock_repo
+            .expect_irrigation_events()
+            .returning(|_| Ok(vec![]));
+        mock_repo
+            .expect_sump_pump_events()
+            .returning(move |_| Ok(sump_events.clone()));
+        mock_repo.expect_begin_irrigation().returning(move |event| {
+            begun_events.lock().unwrap().push(event.id);
+            Ok(())
+        });
+        mock_repo
+            .expect_finish_irrigation_event()
+            .returning(|| Ok(()));
+
+        (Box::leak(Box::new(mock_repo)), begun)
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_run_next_event() {
+        let mut mock_repo = MockRepository::new();
+
+        let _ = mock_repo
+            .expect_queued_irrigation_events()
+            .returning(|| Ok(vec![]));
+
+        let _ = mock_repo
+            .expect_finish_irrigation_event()
+            .returning(|| Ok(()));
+        let repo = Box::new(mock_repo);
+
+        let repo_static: &'static dyn Repository = Box::leak(repo);
+
+        let irrigator = current_irrigator();
+
+        run_irrigation_event(
+            repo_static,
+            &irrigator,
+            None,
+            &Health::default().register("irrigation"),
+        )
+        .await;
+    }
+
+    #[rstest]
+    #[tokio::test(start_paused = true)]
+    async fn test_run_shortened_event(daily_schedule: IrrigationSchedule) {
+        let (budget, sump_events) = budget_and_sump_events();
+        let (repo, begun) = budget_repo(vec![queued_event(2, 2)], sump_events, daily_schedule);
+        let irrigator = current_irrigator();
+        let health = Health::default();
+        let heartbeat = health.register("irrigation");
+        let start = Instant::now();
+
+        run_irrigation_event(repo, &irrigator, Some(&budget), &heartbeat).await;
+
+        assert!(*begun.lock().unwrap() == vec![2]);
+        // Six seconds of the minute scheduled
+        assert!(start.elapsed() >= Duration::from_secs(6));
+        assert!(start.elapsed() < Duration::from_secs(7));
+        // Beaten as the run counted down
+        assert!(health.stalled(Duration::from_secs(1)).is_empty());
+    }
+
+    #[rstest]
+    #[tokio::test(start_paused = true)]
+    async fn test_run_skips_deferred_event(daily_schedule: IrrigationSchedule) {
+        let (budget, sump_events) = budget_and_sump_events();
+        // Hose 1 is first in the queue, but there isn't the water for it
+        let queued = vec![queued_event(1, 1), queued_event(2, 2)];
+        let (repo, begun) = budget_repo(queued, sump_events, daily_schedule);
+        let irrigator = current_irrigator();
+
+        run_irrigation_event(
+            repo,
+            &irrigator,
+            Some(&budget),
+            &Health::default().register("irrigation"),
+        )
+        .await;
+
+        assert!(*begun.lock().unwrap() == vec![2]);
+    }
+
+    #[rstest]
+    #[tokio::test(start_paused = true)]
+    async fn test_run_all_deferred(daily_schedule: IrrigationSchedule) {
+        let (budget, sump_events) = budget_and_sump_events();
+        let (repo, begun) = budget_repo(vec![queued_event(1, 1)], sump_events, daily_schedule);
+        let irrigator = current_irrigator();
+
+        run_irrigation_event(
+            repo,
+            &irrigator,
+            Some(&budget),
+            &Health::default().register("irrigation"),
+        )
+        .await;
+
+        assert!(begun.lock().unwrap().is_empty());
     }
 
     #[rstest]
@@ -252,13 +469,13 @@ mod tests {
     fn test_job_complete() {
         // Set up test data
         let duration = Duration::from_secs(60);
-        let earlier_start_time = SystemTime::now() - Duration::from_secs(90);
-        let later_start_time = SystemTime::now() - Duration::from_secs(30);
+        let earlier_start_time = Instant::now() - Duration::from_secs(90);
+        let later_start_time = Instant::now() - Duration::from_secs(30);
 
         // Call the function being tested
         let shorter_result = job_complete(duration, later_start_time);
         let longer_result = job_complete(duration, earlier_start_time);
-        assert_eq!(shorter_result, false);
-        assert_eq!(longer_result, true);
+        assert!(!shorter_result);
+        assert!(longer_result);
     }
 }
@@ -0,0 +1,210 @@
+use anyhow::Error;
+use serde::Serialize;
+use tokio::time::{sleep, Duration};
+
+use crate::hydro::{
+    control::{Control, Output},
+    gpio::Level,
+    pool_pump::PoolPumpSpeed,
+    Hydro,
+};
+use crate::repository::models::irrigation_event::{IrrigationEventFilter, IrrigationEventStatus};
+
+/// How long each relay is switched on for when no pulse is asked for.
+pub const DEFAULT_PULSE_MS: u64 = 1000;
+/// Long enough to see or hear a relay, short enough that nothing runs dry for long.
+pub const MAX_PULSE_MS: u64 = 10_000;
+
+#[derive(thiserror::Error, Debug)]
+pub enum SelfTestError {
+    #[error("Irrigation is in progress.")]
+    IrrigationRunning,
+    #[error("The sump is pumping.")]
+    SumpPumping,
+    #[error("The pulse must be between 1 and {MAX_PULSE_MS} ms.")]
+    InvalidPulse,
+    #[error(transparent)]
+    Other(#[from] Error),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CheckOutcome {
+    Passed,
+    /// The pin didn't read back as switched
+    Failed,
+    /// Left alone, as switching it would upset running equipment
+    Skipped,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ControlCheck {
+    pub name: String,
+    pub label: String,
+    pub outcome: CheckOutcome,
+    /// Whether the pin read back on while pulsed; unset when skipped
+    pub read_on: Option<bool>,
+    /// Whether the pin read back off after the pulse; unset when skipped
+    pub read_off: Option<bool>,
+    /// Why the control was skipped
+    pub note: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SensorReading {
+    pub name: String,
+    pub level: Level,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestReport {
+    pub pulse_ms: u64,
+    /// Every control that was pulsed read back as switched on and off again
+    pub passed: bool,
+    pub controls: Vec<ControlCheck>,
+    pub sensors: Vec<SensorReading>,
+}
+
+/// Pulses every control in turn for `pulse_ms`, so someone watching can check each relay
+/// clicks, then reads each sensor as it stands. Refuses while water is being moved, and skips
+/// anything already on rather than interrupt it.
+///
+/// Some relays are never pulsed: the pool pump speeds, as a running pump would get two speeds
+/// at once and a stopped one would start without priming, and the irrigation pump, which
+/// would run against closed valves. The heater is only pulsed while the pool pump runs fast
+/// enough for it, and the pump is held at that speed until the pulse ends.
+pub async fn run(hydro: &Hydro, pulse_ms: u64) -> Result<SelfTestReport, SelfTestError> {
+    if pulse_ms == 0 || pulse_ms > MAX_PULSE_MS {
+        return Err(SelfTestError::InvalidPulse);
+    }
+    if irrigating(hydro).await? {
+        return Err(SelfTestError::IrrigationRunning);
+    }
+    if hydro.sump.pump.is_on().await {
+        return Err(SelfTestError::SumpPumping);
+    }
+
+    let pool_pump = &hydro.pool_pump;
+    let pool_pump_speeds = [
+        &pool_pump.low,
+        &pool_pump.med,
+        &pool_pump.high,
+        &pool_pump.max,
+    ];
+    let pool_pump_running = pool_pump.speed().await != PoolPumpSpeed::Off;
+
+    let mut controls = vec![];
+    for (name, control) in hydro.controls() {
+        // Held through the heater's pulse, so the pump can't slow down under it
+        let mut flow = None;
+        let skip_note = if pool_pump_speeds.contains(&control) {
+            Some(if pool_pump_running {
+                "The pool pump is running".to_string()
+            } else {
+                "The pool pump has to prime when started".to_string()
+            })
+        } else if control == &hydro.irrigator.pump {
+            Some("The valves are closed".to_string())
+        } else if control.is_on().await {
+            Some("Already on".to_string())
+        } else if control == &hydro.heater.control {
+            match hydro.heater.lock_flow().await {
+                Ok(lock) => {
+                    flow = Some(lock);
+                    None
+                }
+                Err(e) => Some(e.to_string()),
+            }
+        } else {
+            None
+        };
+
+        let check = match skip_note {
+            Some(note) => ControlCheck {
+                name,
+                label: control.label.clone(),
+                outcome: CheckOutcome::Skipped,
+                read_on: None,
+                read_off: None,
+                note: Some(note),
+            },
+            None => {
+                let (read_on, read_off) = pulse(control, Duration::from_millis(pulse_ms)).await;
+                let outcome = if read_on && read_off {
+                    CheckOutcome::Passed
+                } else {
+                    CheckOutcome::Failed
+                };
+
+                ControlCheck {
+                    name,
+                    label: control.label.clone(),
+                    outcome,
+                    read_on: Some(read_on),
+                    read_off: Some(read_off),
+                    note: None,
+                }
+            }
+        };
+        drop(flow);
+
+        tracing::info!(
+            target = module_path!(),
+            control = check.name,
+            outcome = ?check.outcome,
+            "Self-test checked control"
+        );
+        controls.push(check);
+    }
+
+    let sensors = hydro
+        .sensors()
+        .into_iter()
+        .map(|(name, sensor)| SensorReading {
+            name,
+            level: sensor.pin.lock().unwrap_or_else(|e| e.into_inner()).read(),
+        })
+        .collect();
+
+    Ok(SelfTestReport {
+        pulse_ms,
+        passed: controls
+            .iter()
+            .all(|check| check.outcome != CheckOutcome::Failed),
+        controls,
+        sensors,
+    })
+}
+
+async fn irrigating(hydro: &Hydro) -> Result<bool, Error> {
+    if hydro.irrigator.pump.is_on().await {
+        return Ok(true);
+    }
+
+    let filter = IrrigationEventFilter {
+        status: Some(IrrigationEventStatus::InProgress),
+        limit: Some(1),
+        ..Default::default()
+    };
+
+    Ok(!hydro.repo.irrigation_events(filter).await?.is_empty())
+}
+
+/// Switches the pin on and back off, reporting whether it read back each time. The pin is held
+/// throughout so nothing else switches it mid-pulse, and the control's last command is left
+/// as it was.
+async fn pulse(control: &Control, pulse: Duration) -> (bool, bool) {
+    let mut pin = control.lock().await;
+
+    pin.on();
+    sleep(pulse).await;
+    let read_on = pin.is_on();
+
+    pin.off();
+    let read_off = pin.is_off();
+
+    (read_on, read_off)
+}
@@ -45,7 +45,7 @@ pub trait Input {
 /// * `handle` - handler function to run when the trigger is detected
 /// * `tx` - The channel to send commands to
 /// * `delay` - The debounce delay in milliseconds, if any. Used to
-///    leave the pump on momentarily after a low water level is detected.
+///   leave the pump on momentarily after a low water level is detected.
 impl Sensor {
     pub fn new(
         message: Message,
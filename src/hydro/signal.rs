@@ -2,10 +2,18 @@ use tokio::{
     runtime::Handle,
     sync::mpsc::Receiver,
     task::JoinHandle,
-    time::{sleep, Duration},
+    time::{interval, sleep, sleep_until, Duration, Instant},
 };
 
-use super::{control::SharedOutputPin, gpio::Level};
+use super::{
+    control::{ChangeSource, Control, Output},
+    gpio::Level,
+    watchdog::{Heartbeat, BEAT_INTERVAL},
+};
+use crate::repository::{
+    models::sump_event::{PUMP_OFF, PUMP_ON, SUMP_PUMP_KIND},
+    Repo,
+};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Message {
@@ -26,62 +34,109 @@ pub struct Signal {
 ///
 /// * `rx` - The channel to receive messages from
 /// * `handle`           - The tokio runtime handle
-/// * `irrigator`        - The Irrigator instance
-/// * `sump`             - The Sump instance
+/// * `irrigator_pump`   - The irrigation pump, stopped when the reservoir runs low
+/// * `sump_pump`        - The sump pump
 /// * `sump_empty_delay` - The delay to wait before turning off the sump pump;
-///                        this is to clear the hose of water.
+///   this is to clear the hose of water.
+/// * `repo`             - Records sump pump cycles for water usage reporting
+/// * `heartbeat`        - Beaten between signals, and while waiting for one
 ///
+#[allow(clippy::too_many_arguments)]
 pub fn listen(
     mut rx: Receiver<Signal>,
     handle: Handle,
-    irrigator_pump_pin: SharedOutputPin,
-    sump_pump_pin: SharedOutputPin,
+    repo: Repo,
+    mut irrigator_pump: Control,
+    mut sump_pump: Control,
     sump_empty_delay: u64,
     max_pump_runtime: u64,
-) {
+    heartbeat: Heartbeat,
+) -> JoinHandle<()> {
     handle.spawn(async move {
-        let mut sump_pump_timer: Option<JoinHandle<()>> = None;
-        while let Some(signal) = rx.recv().await {
+        // Kept in this task rather than a timer task of its own, so stopping the listener
+        // on shutdown stops the timer too
+        let mut sump_pump_deadline: Option<Instant> = None;
+        let mut beats = interval(BEAT_INTERVAL);
+        loop {
+            heartbeat.beat();
+            let signal = tokio::select! {
+                signal = rx.recv() => match signal {
+                    Some(signal) => signal,
+                    None => break,
+                },
+                _ = sleep_until(sump_pump_deadline.unwrap_or_else(Instant::now)),
+                    if sump_pump_deadline.is_some() =>
+                {
+                    sump_pump_deadline = None;
+                    switch(&mut sump_pump, false).await;
+
+                    tracing::warn!("Sump pump ran for too long, turning off with safety timer");
+                    record_sump_event(repo, PUMP_OFF).await;
+                    continue;
+                }
+                _ = beats.tick() => continue,
+            };
+
             // TODO: check levels
             match signal.message {
                 Message::SumpEmpty => {
                     // Cancel the running timer when pump is to be turned off
-                    if let Some(handle) = sump_pump_timer.take() {
-                        handle.abort();
-                    }
+                    sump_pump_deadline = None;
+
+                    heartbeat
+                        .during(sleep(Duration::from_secs(sump_empty_delay)))
+                        .await;
 
-                    sleep(Duration::from_secs(sump_empty_delay)).await;
+                    switch(&mut sump_pump, false).await;
 
-                    let pin = sump_pump_pin.clone();
-                    let mut lock = pin.lock().await;
-                    lock.off();
+                    record_sump_event(repo, PUMP_OFF).await;
                 }
                 Message::SumpFull => {
-                    let pin = sump_pump_pin.clone();
-                    let mut lock = pin.lock().await;
-                    lock.on();
-
-                    // Cancel the previous timer if it exists
-                    if let Some(handle) = sump_pump_timer.take() {
-                        handle.abort();
-                    }
-                    // Start a new timer
-                    let pin_clone = sump_pump_pin.clone();
-                    sump_pump_timer = Some(tokio::spawn(async move {
-                        tokio::time::sleep(Duration::from_secs(max_pump_runtime)).await;
-                        let mut lock = pin_clone.lock().await;
-                        lock.off();
-                        tracing::warn!("Sump pump ran for too long, turning off with safety timer");
-                    }));
+                    switch(&mut sump_pump, true).await;
+
+                    record_sump_event(repo, PUMP_ON).await;
+
+                    // Start a new timer, replacing any running one
+                    sump_pump_deadline =
+                        Some(Instant::now() + Duration::from_secs(max_pump_runtime));
                 }
                 Message::IrrigatorEmpty => {
-                    let pin = irrigator_pump_pin.clone();
-                    let mut lock = pin.lock().await;
-                    lock.off();
+                    switch(&mut irrigator_pump, false).await;
                 }
             }
         }
-    });
+    })
+}
+
+/// Logs rather than returns a failure, so the listener carries on with the next signal.
+async fn switch(control: &mut Control, on: bool) {
+    let switched = if on {
+        control.on(ChangeSource::Sensor).await
+    } else {
+        control.off(ChangeSource::Sensor).await
+    };
+
+    if let Err(e) = switched {
+        tracing::error!(
+            target = module_path!(),
+            error = e.to_string(),
+            output = control.label,
+            "Could not switch output"
+        );
+    }
+}
+
+async fn record_sump_event(repo: Repo, info: &str) {
+    if let Err(e) = repo
+        .create_sump_event(info.to_string(), SUMP_PUMP_KIND.to_string())
+        .await
+    {
+        tracing::error!(
+            target = module_path!(),
+            error = e.to_string(),
+            "Could not record sump pump event"
+        );
+    }
 }
 
 //#[cfg(test)]
@@ -0,0 +1,305 @@
+use anyhow::{anyhow, Error};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::hydro::{
+    control::ChangeSource,
+    heater::Heater,
+    outputs::NamedOutput,
+    pool_pump::{PoolPump, PoolPumpSpeed},
+    schedule::pool_pump::ManualOverride,
+    thermostat::Thermostat,
+};
+use crate::repository::{models::equipment_state::EquipmentState, Repo};
+
+/// `device` the heater's state is saved under
+pub const HEATER: &str = "heater";
+/// `device` the pool pump speed is saved under
+pub const POOL_PUMP: &str = "pool_pump";
+/// `device` the pool pump manual override is saved under
+pub const POOL_PUMP_OVERRIDE: &str = "pool_pump_override";
+
+/// `device` a named output's on/off is saved under
+pub fn output_device(name: &str) -> String {
+    format!("output_{}", name)
+}
+
+/// What a device does at startup.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RestorePolicy {
+    /// Return to the last state set through the API
+    Restore,
+    /// Stay off until told otherwise
+    Off,
+}
+
+impl FromStr for RestorePolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "restore" => Ok(RestorePolicy::Restore),
+            "off" => Ok(RestorePolicy::Off),
+            _ => Err(anyhow!("Invalid restore policy: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum HeaterState {
+    Off,
+    On,
+    Thermostat(Thermostat),
+}
+
+/// Records the state of `device`. Failing to save shouldn't fail the change itself, so errors
+/// are logged rather than returned.
+pub async fn save<T: Serialize>(repo: Repo, device: &str, state: &T) {
+    let saved = match serde_json::to_string(state) {
+        Ok(state) => repo.save_equipment_state(device.into(), state).await,
+        Err(e) => Err(anyhow!(e)),
+    };
+
+    if let Err(e) = saved {
+        tracing::error!(
+            target = module_path!(),
+            error = e.to_string(),
+            device,
+            "Could not save equipment state"
+        );
+    }
+}
+
+fn find<T: for<'de> Deserialize<'de>>(states: &[EquipmentState], device: &str) -> Option<T> {
+    let state = states.iter().find(|state| state.device == device)?;
+
+    match serde_json::from_str(&state.state) {
+        Ok(state) => Some(state),
+        Err(e) => {
+            tracing::error!(
+                target = module_path!(),
+                error = e.to_string(),
+                device,
+                "Could not read saved equipment state"
+            );
+            None
+        }
+    }
+}
+
+/// Puts the equipment back the way it was before a restart, for the devices whose policy is
+/// to restore. The pool pump goes first, so the heater interlock sees water flowing. Named
+/// outputs carry their own policy.
+pub async fn restore(
+    repo: Repo,
+    heater_policy: RestorePolicy,
+    pool_pump_policy: RestorePolicy,
+    heater: &mut Heater,
+    pool_pump: &mut PoolPump,
+    manual_override: &ManualOverride,
+    outputs: &mut [NamedOutput],
+) {
+    let states = match repo.equipment_states().await {
+        Ok(states) => states,
+        Err(e) => {
+            tracing::error!(
+                target = module_path!(),
+                error = e.to_string(),
+                "Could not load equipment state; leaving everything off"
+            );
+            return;
+        }
+    };
+
+    if pool_pump_policy == RestorePolicy::Restore {
+        if find::<bool>(&states, POOL_PUMP_OVERRIDE) == Some(true) {
+            manual_override.hold();
+        }
+
+        if let Some(speed) = find::<PoolPumpSpeed>(&states, POOL_PUMP) {
+            tracing::info!(
+                target = module_path!(),
+                speed = speed.to_string(),
+                "Restoring pool pump speed"
+            );
+
+            if let Err(e) = pool_pump.on(speed, ChangeSource::Restore).await {
+                tracing::error!(
+                    target = module_path!(),
+                    error = e.to_string(),
+                    "Could not restore pool pump speed"
+                );
+            }
+        }
+    }
+
+    if heater_policy == RestorePolicy::Restore {
+        match find::<HeaterState>(&states, HEATER) {
+            Some(HeaterState::On) => {
+                tracing::info!(target = module_path!(), "Restoring pool heater");
+
+                if let Err(e) = heater.on(ChangeSource::Restore).await {
+                    tracing::warn!(
+                        target = module_path!(),
+                        error = e.to_string(),
+                        "Could not restore pool heater"
+                    );
+                }
+            }
+            Some(HeaterState::Thermostat(thermostat)) => {
+                tracing::info!(target = module_path!(), "Restoring pool heater thermostat");
+                heater.set_thermostat(Some(thermostat));
+            }
+            Some(HeaterState::Off) | None => (),
+        }
+    }
+
+    for output in outputs.iter_mut() {
+        let device = output_device(&output.name);
+        if output.restore == RestorePolicy::Off || find::<bool>(&states, &device) != Some(true) {
+            continue;
+        }
+
+        // Left alone if its max on time would have switched it off by now
+        let saved_at = states
+            .iter()
+            .find(|state| state.device == device)
+            .map(|state| state.updated_at);
+        if let (Some(max_on_sec), Some(saved_at)) = (output.max_on_sec, saved_at) {
+            let on_for = Utc::now().naive_utc() - saved_at;
+            if on_for.num_seconds() >= max_on_sec as i64 {
+                continue;
+            }
+        }
+
+        tracing::info!(
+            target = module_path!(),
+            output = output.name,
+            "Restoring output"
+        );
+
+        if let Err(e) = output.on(ChangeSource::Restore).await {
+            tracing::error!(
+                target = module_path!(),
+                error = e.to_string(),
+                output = output.name,
+                "Could not restore output"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::{
+        output_device, restore, HeaterState, RestorePolicy, HEATER, POOL_PUMP, POOL_PUMP_OVERRIDE,
+    };
+    use crate::hydro::{
+        gpio::MockGpio,
+        heater::Heater,
+        outputs::NamedOutput,
+        pool_pump::{PoolPump, PoolPumpSpeed},
+        schedule::pool_pump::ManualOverride,
+        thermostat::Thermostat,
+    };
+    use crate::repository::{models::equipment_state::EquipmentState, MockRepository, Repo};
+    use crate::test_fixtures::{
+        gpio::{mock_heater, mock_outputs, mock_pool_pump},
+        settings::SETTINGS,
+    };
+
+    fn saved(device: &str, state: &str) -> EquipmentState {
+        EquipmentState {
+            device: device.into(),
+            state: state.into(),
+            updated_at: chrono::NaiveDateTime::default(),
+        }
+    }
+
+    fn repo() -> Repo {
+        let mut mock_repo = MockRepository::new();
+        mock_repo.expect_equipment_states().returning(|| {
+            Ok(vec![
+                saved(
+                    HEATER,
+                    r#"{"mode":"thermostat","setpoint":28.0,"hysteresis":0.5}"#,
+                ),
+                saved(POOL_PUMP, r#""low""#),
+                saved(POOL_PUMP_OVERRIDE, "true"),
+                saved(&output_device("pool_lights"), "true"),
+                saved(&output_device("fountain"), "true"),
+            ])
+        });
+
+        Box::leak(Box::new(mock_repo))
+    }
+
+    #[rstest]
+    fn test_heater_state_json() {
+        let state = HeaterState::Thermostat(Thermostat {
+            setpoint: 28.0,
+            hysteresis: 0.5,
+        });
+        let json = serde_json::to_string(&state).unwrap();
+
+        assert_eq!(
+            json,
+            r#"{"mode":"thermostat","setpoint":28.0,"hysteresis":0.5}"#
+        );
+        assert_eq!(serde_json::from_str::<HeaterState>(&json).unwrap(), state);
+        assert_eq!(
+            serde_json::to_string(&HeaterState::Off).unwrap(),
+            r#"{"mode":"off"}"#
+        );
+    }
+
+    #[rstest]
+    #[case(RestorePolicy::Restore, true)]
+    #[case(RestorePolicy::Off, false)]
+    #[tokio::test]
+    async fn test_restore(#[case] policy: RestorePolicy, #[case] restored: bool) {
+        let mock_gpio = mock_pool_pump(MockGpio::new(), PoolPumpSpeed::Max);
+        let mock_gpio = mock_heater(mock_gpio, false);
+        let mock_gpio = mock_outputs(mock_gpio);
+        let mut pool_pump = PoolPump::new(&SETTINGS.hydro.pool_pump, &mock_gpio).unwrap();
+        let mut heater = Heater::new(&SETTINGS.hydro.heater, &mock_gpio, &mut pool_pump).unwrap();
+        let manual_override = ManualOverride::default();
+        let mut outputs: Vec<NamedOutput> = SETTINGS
+            .hydro
+            .outputs
+            .outputs
+            .iter()
+            .map(|config| NamedOutput::new(config, &mock_gpio).unwrap())
+            .collect();
+        for output in outputs.iter_mut() {
+            output.restore = policy;
+        }
+
+        restore(
+            repo(),
+            policy,
+            policy,
+            &mut heater,
+            &mut pool_pump,
+            &manual_override,
+            &mut outputs,
+        )
+        .await;
+
+        assert_eq!(heater.thermostat().is_some(), restored);
+        assert_eq!(manual_override.is_held(), restored);
+        assert_eq!(pool_pump.commanded_speed() == PoolPumpSpeed::Low, restored);
+        for output in outputs.iter() {
+            // The fountain's saved state is older than its max on time
+            assert_eq!(
+                output.control.command().on,
+                restored && output.name == "pool_lights"
+            );
+        }
+    }
+}
@@ -39,13 +39,24 @@ impl Sump {
         handle: Handle,
         gpio: &dyn Gpio,
     ) -> Result<Self, Error> {
-        let pump = Control::new("Sump Pump".into(), config.pump_control_pin, gpio)?;
+        let pump = Control::with_polarity(
+            "Sump Pump".into(),
+            config.pump_control_pin,
+            config.pump_polarity,
+            gpio,
+        )?;
+
+        // The float switches are still read, but leave the pump to the level thresholds
+        let trigger = match config.pump_thresholds {
+            Some(_) => Trigger::Disabled,
+            None => Trigger::RisingEdge,
+        };
 
         let high_sensor = Sensor::new(
             Message::SumpFull,
             config.high_sensor_pin,
             gpio,
-            Trigger::RisingEdge,
+            trigger,
             tx,
             handle.clone(),
         )?;
@@ -54,7 +65,7 @@ impl Sump {
             Message::SumpEmpty,
             config.low_sensor_pin,
             gpio,
-            Trigger::RisingEdge,
+            trigger,
             tx,
             handle.clone(),
         )?;
@@ -0,0 +1,146 @@
+use anyhow::{anyhow, Error};
+use mockall::automock;
+use std::{fs, path::PathBuf, sync::Arc};
+
+use crate::util::spawn_blocking_with_tracing;
+
+/// Prefix of the 1-Wire family code for DS18B20 temperature sensors.
+const DS18B20_FAMILY: &str = "28-";
+
+#[automock]
+pub trait TemperatureSensor: Send + Sync {
+    /// The current temperature in degrees Celsius.
+    fn read(&self) -> Result<f64, Error>;
+}
+
+/// A DS18B20 read through the Linux 1-Wire sysfs interface, which exposes each device as a
+/// directory holding a `w1_slave` file.
+#[derive(Clone, Debug)]
+pub struct OneWire {
+    devices_path: PathBuf,
+    sensor_id: Option<String>,
+}
+
+impl OneWire {
+    /// Reads the sensor named by `sensor_id`, or the first DS18B20 found when it isn't set.
+    /// The device is looked up on each read, so a sensor plugged in later is still picked up.
+    pub fn new(devices_path: &str, sensor_id: Option<String>) -> Self {
+        Self {
+            devices_path: PathBuf::from(devices_path),
+            sensor_id,
+        }
+    }
+
+    fn device_path(&self) -> Result<PathBuf, Error> {
+        if let Some(sensor_id) = &self.sensor_id {
+            return Ok(self.devices_path.join(sensor_id));
+        }
+
+        let mut devices: Vec<PathBuf> = fs::read_dir(&self.devices_path)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(DS18B20_FAMILY)
+            })
+            .map(|entry| entry.path())
+            .collect();
+        devices.sort();
+
+        devices
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("No 1-Wire temperature sensor found"))
+    }
+}
+
+impl TemperatureSensor for OneWire {
+    fn read(&self) -> Result<f64, Error> {
+        let contents = fs::read_to_string(self.device_path()?.join("w1_slave"))?;
+
+        parse_w1_slave(&contents)
+    }
+}
+
+/// Parses the two line `w1_slave` format; the first line ends in the CRC check, the second in
+/// the temperature in thousandths of a degree:
+///
+/// ```text
+/// 72 01 4b 46 7f ff 0e 10 57 : crc=57 YES
+/// 72 01 4b 46 7f ff 0e 10 57 t=23125
+/// ```
+pub fn parse_w1_slave(contents: &str) -> Result<f64, Error> {
+    let mut lines = contents.lines();
+
+    let crc_line = lines.next().unwrap_or_default();
+    if !crc_line.trim_end().ends_with("YES") {
+        return Err(anyhow!("1-Wire CRC check failed"));
+    }
+
+    let millidegrees: i32 = lines
+        .next()
+        .and_then(|line| line.split("t=").nth(1))
+        .ok_or_else(|| anyhow!("1-Wire reading has no temperature"))?
+        .trim()
+        .parse()?;
+
+    Ok(f64::from(millidegrees) / 1000.0)
+}
+
+/// Reads `sensor` off the async runtime; 1-Wire conversions take most of a second.
+pub async fn read_temperature(sensor: &Arc<dyn TemperatureSensor>) -> Result<f64, Error> {
+    let sensor = sensor.clone();
+
+    spawn_blocking_with_tracing(move || sensor.read()).await?
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use std::{fs, path::Path};
+
+    use super::{parse_w1_slave, OneWire, TemperatureSensor};
+
+    /// Lays out a device directory the way the kernel does.
+    fn write_w1_slave(devices_path: &Path, sensor_id: &str, contents: &str) {
+        let device = devices_path.join(sensor_id);
+        fs::create_dir_all(&device).unwrap();
+        fs::write(device.join("w1_slave"), contents).unwrap();
+    }
+
+    const READING: &str = "72 01 4b 46 7f ff 0e 10 57 : crc=57 YES\n\
+                           72 01 4b 46 7f ff 0e 10 57 t=23125\n";
+
+    #[rstest]
+    #[case(READING, Some(23.125))]
+    #[case("ff ff : crc=57 NO\nff ff t=85000\n", None)]
+    #[case("72 01 : crc=57 YES\n72 01 t=-1250\n", Some(-1.25))]
+    #[case("72 01 : crc=57 YES\n", None)]
+    fn test_parse_w1_slave(#[case] contents: &str, #[case] expected: Option<f64>) {
+        assert_eq!(parse_w1_slave(contents).ok(), expected);
+    }
+
+    #[rstest]
+    fn test_one_wire_read() {
+        let devices = tempfile::tempdir().unwrap();
+        write_w1_slave(devices.path(), "w1_bus_master1", "");
+        write_w1_slave(devices.path(), "28-000000000002", READING);
+
+        let sensor = OneWire::new(&devices.path().to_string_lossy(), None);
+
+        assert_eq!(sensor.read().unwrap(), 23.125);
+    }
+
+    #[rstest]
+    fn test_one_wire_missing_sensor() {
+        let devices = tempfile::tempdir().unwrap();
+
+        let sensor = OneWire::new(
+            &devices.path().to_string_lossy(),
+            Some("28-000000000001".into()),
+        );
+
+        assert!(sensor.read().is_err());
+    }
+}
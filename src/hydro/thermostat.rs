@@ -0,0 +1,166 @@
+use anyhow::{anyhow, Error};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+
+use crate::hydro::{
+    control::ChangeSource,
+    heater::Heater,
+    temperature::{read_temperature, TemperatureSensor},
+};
+
+/// Holds the water at `setpoint` degrees Celsius. The heater comes on once the water drops
+/// `hysteresis` degrees below the setpoint, and goes off once it rises `hysteresis` above it.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Thermostat {
+    pub setpoint: f64,
+    pub hysteresis: f64,
+}
+
+/// Degrees either side of the setpoint when none is given.
+pub const DEFAULT_HYSTERESIS: f64 = 0.5;
+/// Hottest setpoint accepted, in degrees Celsius; the safe limit for bathing.
+pub const MAX_SETPOINT: f64 = 40.0;
+
+impl Thermostat {
+    pub fn new(setpoint: f64, hysteresis: Option<f64>) -> Result<Self, Error> {
+        let hysteresis = hysteresis.unwrap_or(DEFAULT_HYSTERESIS);
+
+        if !(0.0..=MAX_SETPOINT).contains(&setpoint) {
+            return Err(anyhow!(
+                "setpoint must be between 0 and {} degrees",
+                MAX_SETPOINT
+            ));
+        }
+        if !(hysteresis > 0.0 && hysteresis <= 5.0) {
+            return Err(anyhow!("hysteresis must be above 0 and at most 5 degrees"));
+        }
+
+        Ok(Self {
+            setpoint,
+            hysteresis,
+        })
+    }
+
+    /// Whether the heater should be running at `temperature`; inside the band it keeps doing
+    /// whatever it is doing, so the relay doesn't chatter around the setpoint.
+    pub fn calls_for_heat(&self, temperature: f64, heating: bool) -> bool {
+        if temperature <= self.setpoint - self.hysteresis {
+            true
+        } else if temperature >= self.setpoint + self.hysteresis {
+            false
+        } else {
+            heating
+        }
+    }
+}
+
+/// Intended to be run at startup, once any saved state is restored. While the heater is in
+/// thermostat mode, each tick reads the water temperature and switches the heater to hold the
+/// setpoint. A failed reading switches the heater off rather than heating blind.
+///
+///  # Arguments
+///
+///  * `heater` - The heater to drive; shares its thermostat setting with the API
+///  * `sensor` - The water temperature sensor
+///  * `frequency_sec` - Seconds between ticks
+///
+pub async fn run(mut heater: Heater, sensor: Arc<dyn TemperatureSensor>, frequency_sec: u64) {
+    loop {
+        if let Some(thermostat) = heater.thermostat() {
+            let heating = heater.is_on().await;
+
+            match read_temperature(&sensor).await {
+                Ok(temperature) => {
+                    let calls_for_heat = thermostat.calls_for_heat(temperature, heating);
+
+                    if calls_for_heat && !heating {
+                        tracing::info!(target = module_path!(), temperature, "Heating pool");
+
+                        if let Err(e) = heater.on(ChangeSource::Thermostat).await {
+                            tracing::warn!(
+                                target = module_path!(),
+                                error = e.to_string(),
+                                "Thermostat could not switch the heater on"
+                            );
+                        }
+                    } else if !calls_for_heat && heating {
+                        tracing::info!(
+                            target = module_path!(),
+                            temperature,
+                            "Pool reached setpoint"
+                        );
+                        if let Err(e) = heater.off(ChangeSource::Thermostat).await {
+                            tracing::error!(
+                                target = module_path!(),
+                                error = e.to_string(),
+                                "Thermostat could not switch the heater off"
+                            );
+                        }
+                    }
+                }
+                Err(e) => {
+                    tracing::error!(
+                        target = module_path!(),
+                        error = e.to_string(),
+                        "Could not read pool temperature"
+                    );
+
+                    if heating {
+                        if let Err(e) = heater.off(ChangeSource::Thermostat).await {
+                            tracing::error!(
+                                target = module_path!(),
+                                error = e.to_string(),
+                                "Thermostat could not switch the heater off"
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        sleep(Duration::from_secs(frequency_sec)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::{Thermostat, DEFAULT_HYSTERESIS};
+
+    #[rstest]
+    #[case(27.0, false, true)]
+    #[case(27.5, false, true)]
+    #[case(28.0, false, false)]
+    #[case(28.0, true, true)]
+    #[case(28.5, true, false)]
+    #[case(30.0, true, false)]
+    fn test_calls_for_heat(
+        #[case] temperature: f64,
+        #[case] heating: bool,
+        #[case] expected: bool,
+    ) {
+        let thermostat = Thermostat {
+            setpoint: 28.0,
+            hysteresis: 0.5,
+        };
+
+        assert_eq!(thermostat.calls_for_heat(temperature, heating), expected);
+    }
+
+    #[rstest]
+    #[case(28.0, None, Some(DEFAULT_HYSTERESIS))]
+    #[case(28.0, Some(1.0), Some(1.0))]
+    #[case(45.0, None, None)]
+    #[case(28.0, Some(0.0), None)]
+    fn test_new(
+        #[case] setpoint: f64,
+        #[case] hysteresis: Option<f64>,
+        #[case] expected: Option<f64>,
+    ) {
+        let thermostat = Thermostat::new(setpoint, hysteresis).ok();
+
+        assert_eq!(thermostat.map(|t| t.hysteresis), expected);
+    }
+}
@@ -0,0 +1,378 @@
+use anyhow::Error;
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::config::HydroConfig;
+use crate::repository::{
+    models::{
+        irrigation_event::{
+            IrrigationEvent, IrrigationEventFilter, IrrigationEventStatus, MAX_PAGE_SIZE,
+        },
+        sump_event::{SumpEvent, PUMP_OFF, PUMP_ON},
+    },
+    Repo,
+};
+
+/// Number of buckets reported when the caller does not say where to start.
+const DEFAULT_BUCKETS: u32 = 12;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UsagePeriod {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+impl UsagePeriod {
+    /// The first day of the bucket that `date` falls in; weeks start on Monday.
+    pub fn bucket_start(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            UsagePeriod::Day => date,
+            UsagePeriod::Week => {
+                date - Duration::days(date.weekday().num_days_from_monday() as i64)
+            }
+            UsagePeriod::Month => date.with_day(1).unwrap_or(date),
+        }
+    }
+
+    /// The start of the oldest of the most recent `DEFAULT_BUCKETS` buckets, `today` included.
+    pub fn default_since(&self, today: NaiveDate) -> NaiveDate {
+        let current = self.bucket_start(today);
+        let previous = DEFAULT_BUCKETS - 1;
+
+        match self {
+            UsagePeriod::Day => current - Duration::days(previous as i64),
+            UsagePeriod::Week => current - Duration::weeks(previous as i64),
+            UsagePeriod::Month => current
+                .checked_sub_months(Months::new(previous))
+                .unwrap_or(current),
+        }
+    }
+}
+
+/// A single stretch of time a pump was moving water.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PumpRun {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+}
+
+impl PumpRun {
+    pub fn seconds(&self) -> i64 {
+        (self.end - self.start).num_seconds().max(0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct UsageBucket {
+    pub start: NaiveDate,
+    pub runtime_seconds: i64,
+    /// `None` when no flow rate is configured
+    pub litres: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct UsageSeries {
+    /// Litres per minute
+    pub flow_rate: Option<f64>,
+    pub runtime_seconds: i64,
+    pub litres: Option<f64>,
+    pub buckets: Vec<UsageBucket>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ZoneUsage {
+    pub hose_id: i32,
+    #[serde(flatten)]
+    pub usage: UsageSeries,
+}
+
+/// Water delivered to each irrigation zone and pumped out of the sump.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct UsageReport {
+    pub period: UsagePeriod,
+    pub since: NaiveDate,
+    pub zones: Vec<ZoneUsage>,
+    pub sump: UsageSeries,
+}
+
+/// Converts a runtime into litres for a pump moving `flow_rate` litres per minute.
+pub fn litres(runtime_seconds: i64, flow_rate: Option<f64>) -> Option<f64> {
+    flow_rate.map(|rate| runtime_seconds as f64 / 60.0 * rate)
+}
+
+/// Pages through every completed irrigation event queued on or after `since`.
+pub async fn completed_irrigation_events(
+    repo: Repo,
+    since: NaiveDateTime,
+) -> Result<Vec<IrrigationEvent>, Error> {
+    let mut filter = IrrigationEventFilter {
+        status: Some(IrrigationEventStatus::Completed),
+        created_after: Some(since),
+        limit: Some(MAX_PAGE_SIZE),
+        ..Default::default()
+    };
+
+    let mut events = vec![];
+    loop {
+        let page = repo.irrigation_events(filter.clone()).await?;
+
+        filter.cursor = filter.next_cursor(&page);
+        events.extend(page);
+        if filter.cursor.is_none() {
+            return Ok(events);
+        }
+    }
+}
+
+/// Pairs each recorded sump pump start with the stop that follows it. The pump is restarted
+/// while already running whenever the sump fills again, so repeated starts extend the open
+/// run; a stop without a start, or a run still going, is left out.
+///
+/// # Arguments
+///
+/// * `events` - Sump pump events, oldest first
+pub fn sump_runs(events: &[SumpEvent]) -> Vec<PumpRun> {
+    let mut runs = vec![];
+    let mut started_at: Option<NaiveDateTime> = None;
+
+    for event in events.iter() {
+        match event.info.as_str() {
+            PUMP_ON => {
+                started_at.get_or_insert(event.created_at);
+            }
+            PUMP_OFF => {
+                if let Some(start) = started_at.take() {
+                    runs.push(PumpRun {
+                        start,
+                        end: event.created_at,
+                    });
+                }
+            }
+            _ => (),
+        }
+    }
+
+    runs
+}
+
+/// The runs of a single hose, skipping events that have not ended.
+pub fn irrigation_runs(events: &[IrrigationEvent], hose_id: i32) -> Vec<PumpRun> {
+    events
+        .iter()
+        .filter(|event| event.hose_id == hose_id)
+        .filter_map(|event| {
+            let runtime = event.runtime_seconds()?;
+            let end = event.end_time?;
+
+            Some(PumpRun {
+                start: end - Duration::seconds(runtime),
+                end,
+            })
+        })
+        .collect()
+}
+
+/// Totals `runs` into one bucket per `period`, keyed by the day each run started. Runs that
+/// started before `since` are left out.
+pub fn series(
+    runs: &[PumpRun],
+    period: UsagePeriod,
+    since: NaiveDate,
+    flow_rate: Option<f64>,
+) -> UsageSeries {
+    let mut buckets: Vec<UsageBucket> = vec![];
+
+    for run in runs.iter().filter(|run| run.start.date() >= since) {
+        let start = period.bucket_start(run.start.date());
+        match buckets.iter_mut().find(|bucket| bucket.start == start) {
+            Some(bucket) => bucket.runtime_seconds += run.seconds(),
+            None => buckets.push(UsageBucket {
+                start,
+                runtime_seconds: run.seconds(),
+                litres: None,
+            }),
+        }
+    }
+
+    buckets.sort_by_key(|bucket| bucket.start);
+    for bucket in buckets.iter_mut() {
+        bucket.litres = litres(bucket.runtime_seconds, flow_rate);
+    }
+
+    let runtime_seconds = buckets.iter().map(|bucket| bucket.runtime_seconds).sum();
+
+    UsageSeries {
+        flow_rate,
+        runtime_seconds,
+        litres: litres(runtime_seconds, flow_rate),
+        buckets,
+    }
+}
+
+/// Builds the usage report for every irrigation zone and the sump pump.
+///
+/// # Arguments
+///
+/// * `period`             - The size of each bucket
+/// * `since`              - The first day to report on
+/// * `irrigation_events`  - Completed irrigation events
+/// * `sump_events`        - Sump pump events, oldest first
+/// * `config`             - Provides the configured flow rates
+pub fn report(
+    period: UsagePeriod,
+    since: NaiveDate,
+    irrigation_events: &[IrrigationEvent],
+    sump_events: &[SumpEvent],
+    config: &HydroConfig,
+) -> UsageReport {
+    let zones = (1..=4)
+        .map(|hose_id| ZoneUsage {
+            hose_id,
+            usage: series(
+                &irrigation_runs(irrigation_events, hose_id),
+                period,
+                since,
+                config.irrigation.flow_rate(hose_id),
+            ),
+        })
+        .collect();
+
+    UsageReport {
+        period,
+        since,
+        zones,
+        sump: series(
+            &sump_runs(sump_events),
+            period,
+            since,
+            config.sump.pump_flow_rate,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime};
+    use rstest::rstest;
+
+    use super::{report, sump_runs, UsagePeriod};
+    use crate::repository::models::{
+        irrigation_event::{IrrigationEvent, IrrigationEventStatus},
+        sump_event::{SumpEvent, PUMP_OFF, PUMP_ON, SUMP_PUMP_KIND},
+    };
+    use crate::test_fixtures::irrigation::event::completed_event;
+    use crate::test_fixtures::settings::SETTINGS;
+
+    fn at(time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    fn sump_event(id: i32, info: &str, created_at: &str) -> SumpEvent {
+        SumpEvent {
+            id,
+            kind: SUMP_PUMP_KIND.into(),
+            info: info.into(),
+            created_at: at(created_at),
+        }
+    }
+
+    #[rstest]
+    #[case(UsagePeriod::Day, "2021-01-07", "2021-01-07", "2020-12-27")]
+    #[case(UsagePeriod::Week, "2021-01-07", "2021-01-04", "2020-10-19")]
+    #[case(UsagePeriod::Month, "2021-01-07", "2021-01-01", "2020-02-01")]
+    fn test_period_bounds(
+        #[case] period: UsagePeriod,
+        #[case] today: &str,
+        #[case] bucket_start: &str,
+        #[case] default_since: &str,
+    ) {
+        assert_eq!(period.bucket_start(date(today)), date(bucket_start));
+        assert_eq!(period.default_since(date(today)), date(default_since));
+    }
+
+    #[rstest]
+    fn test_sump_runs() {
+        let events = vec![
+            // A stop with no start is ignored
+            sump_event(1, PUMP_OFF, "2021-01-01 00:00:00"),
+            sump_event(2, PUMP_ON, "2021-01-01 01:00:00"),
+            // Restarting a running pump keeps the original start
+            sump_event(3, PUMP_ON, "2021-01-01 01:00:20"),
+            sump_event(4, PUMP_OFF, "2021-01-01 01:00:30"),
+            // Still running
+            sump_event(5, PUMP_ON, "2021-01-01 02:00:00"),
+        ];
+
+        let runs = sump_runs(&events);
+
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].seconds(), 30);
+    }
+
+    #[rstest]
+    fn test_report_totals() {
+        // (queued, started, ended); the first event waited in the queue for ten minutes
+        let hose_1_events: Vec<IrrigationEvent> = [
+            (
+                "2020-12-31 23:50:00",
+                "2021-01-01 00:00:00",
+                "2021-01-01 00:01:00",
+            ),
+            (
+                "2021-01-02 00:00:00",
+                "2021-01-02 00:00:00",
+                "2021-01-02 00:00:30",
+            ),
+            (
+                "2021-01-09 00:00:00",
+                "2021-01-09 00:00:00",
+                "2021-01-09 00:02:00",
+            ),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, (queued, start, end))| {
+            completed_event(
+                i as i32 + 1,
+                1,
+                at(queued),
+                Some(at(end)),
+                IrrigationEventStatus::Completed,
+                1,
+                Some(at(start)),
+            )
+        })
+        .collect();
+        let sump_events = vec![
+            sump_event(1, PUMP_ON, "2021-01-01 00:00:00"),
+            sump_event(2, PUMP_OFF, "2021-01-01 00:00:30"),
+        ];
+
+        let report = report(
+            UsagePeriod::Week,
+            date("2020-12-28"),
+            &hose_1_events,
+            &sump_events,
+            &SETTINGS.hydro,
+        );
+
+        let hose_1 = &report.zones[0].usage;
+        assert_eq!(report.zones.len(), 4);
+        assert_eq!(hose_1.runtime_seconds, 210);
+        assert_eq!(hose_1.buckets.len(), 2);
+        assert_eq!(hose_1.buckets[0].start, date("2020-12-28"));
+        assert_eq!(hose_1.buckets[0].runtime_seconds, 90);
+        // 6 litres per minute for hose 1 in .env.test
+        assert_eq!(hose_1.litres, Some(21.0));
+        assert!(report.zones[1].usage.buckets.is_empty());
+        // 12 litres per minute for the sump pump in .env.test
+        assert_eq!(report.sump.litres, Some(6.0));
+    }
+}
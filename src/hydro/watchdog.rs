@@ -0,0 +1,311 @@
+use anyhow::{anyhow, Error};
+use std::{
+    fs::{File, OpenOptions},
+    future::Future,
+    io::Write,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    task::JoinHandle,
+    time::{interval, Duration, Instant, MissedTickBehavior},
+};
+
+/// How often a task that is waiting rather than working shows it is still alive.
+pub const BEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A hardware timer that reboots the board unless it is petted in time.
+pub trait Watchdog: Send {
+    /// Restarts the countdown to a reboot.
+    fn pet(&mut self) -> Result<(), Error>;
+    /// Stops the countdown, for a clean shutdown.
+    fn disarm(&mut self) -> Result<(), Error>;
+}
+
+/// The kernel's watchdog device. It is armed as soon as it is opened, and closing it without
+/// disarming first leaves it counting down.
+pub struct LinuxWatchdog {
+    file: File,
+}
+
+impl LinuxWatchdog {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .write(true)
+            .open(path)
+            .map_err(|e| anyhow!("Could not open watchdog {:?}: {}", path, e))?;
+
+        Ok(Self { file })
+    }
+}
+
+impl Watchdog for LinuxWatchdog {
+    fn pet(&mut self) -> Result<(), Error> {
+        self.file.write_all(b"\0")?;
+        Ok(self.file.flush()?)
+    }
+
+    /// Writes the magic close character; drivers built with nowayout ignore it.
+    fn disarm(&mut self) -> Result<(), Error> {
+        self.file.write_all(b"V")?;
+        Ok(self.file.flush()?)
+    }
+}
+
+/// Shows that one task is still making progress. Clones share the last beat.
+#[derive(Clone)]
+pub struct Heartbeat {
+    last: Arc<Mutex<Instant>>,
+}
+
+impl Heartbeat {
+    fn new() -> Self {
+        Self {
+            last: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    pub fn beat(&self) {
+        *self.last.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+    }
+
+    fn since_last(&self) -> Duration {
+        self.last
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .elapsed()
+    }
+
+    /// Runs `work`, beating while it waits, for waits longer than a beat such as a sleep
+    /// between checks. Only the task being polled is shown, not that `work` gets anywhere, so
+    /// work that could hang should beat for itself.
+    pub async fn during<F: Future>(&self, work: F) -> F::Output {
+        tokio::pin!(work);
+        let mut beats = interval(BEAT_INTERVAL);
+
+        loop {
+            tokio::select! {
+                output = &mut work => {
+                    self.beat();
+                    return output;
+                }
+                _ = beats.tick() => self.beat(),
+            }
+        }
+    }
+}
+
+/// The heartbeats the watchdog waits on, by name.
+#[derive(Clone, Default)]
+pub struct Health {
+    heartbeats: Arc<Mutex<Vec<(String, Heartbeat)>>>,
+}
+
+impl Health {
+    /// Adds a heartbeat for the watchdog to wait on, beating from now.
+    pub fn register(&self, name: &str) -> Heartbeat {
+        let heartbeat = Heartbeat::new();
+        self.heartbeats
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((name.to_string(), heartbeat.clone()));
+
+        heartbeat
+    }
+
+    /// Waits on a new heartbeat for `name` in place of every other, for once the tasks beating
+    /// them have been stopped on purpose, as on shutdown.
+    pub fn replace_all(&self, name: &str) -> Heartbeat {
+        let heartbeat = Heartbeat::new();
+        *self.heartbeats.lock().unwrap_or_else(|e| e.into_inner()) =
+            vec![(name.to_string(), heartbeat.clone())];
+
+        heartbeat
+    }
+
+    /// The names of the heartbeats not seen within `stall_after`.
+    pub fn stalled(&self, stall_after: Duration) -> Vec<String> {
+        self.heartbeats
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|(_, heartbeat)| heartbeat.since_last() > stall_after)
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+}
+
+/// The running health task; stopping it disarms the watchdog.
+pub struct WatchdogTask {
+    task: JoinHandle<()>,
+    watchdog: Arc<Mutex<Box<dyn Watchdog>>>,
+}
+
+impl WatchdogTask {
+    /// For a clean shutdown, once the equipment is safe.
+    pub async fn stop(self) {
+        self.task.abort();
+        let _ = self.task.await;
+
+        let disarmed = self
+            .watchdog
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .disarm();
+        if let Err(e) = disarmed {
+            tracing::error!(
+                target = module_path!(),
+                error = e.to_string(),
+                "Could not disarm the watchdog"
+            );
+        }
+    }
+}
+
+/// Intended to be run at startup. Pets the watchdog every `pet_interval` while every
+/// heartbeat in `health` has been seen within `stall_after`, and stops petting once one
+/// stalls so the board reboots. Petting picks up again if the stalled task recovers in time.
+pub fn start(
+    watchdog: Box<dyn Watchdog>,
+    health: Health,
+    pet_interval: Duration,
+    stall_after: Duration,
+) -> WatchdogTask {
+    let watchdog = Arc::new(Mutex::new(watchdog));
+    let task_watchdog = Arc::clone(&watchdog);
+
+    let task = tokio::spawn(async move {
+        let mut ticks = interval(pet_interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut was_stalled = false;
+
+        loop {
+            ticks.tick().await;
+
+            let stalled = health.stalled(stall_after);
+            if !stalled.is_empty() {
+                if !was_stalled {
+                    tracing::error!(
+                        target = module_path!(),
+                        stalled = stalled.join(", "),
+                        "Stopped petting the watchdog; the board will reboot"
+                    );
+                }
+                was_stalled = true;
+                continue;
+            }
+            if was_stalled {
+                tracing::info!(target = module_path!(), "Petting the watchdog again");
+                was_stalled = false;
+            }
+
+            let petted = task_watchdog
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .pet();
+            if let Err(e) = petted {
+                tracing::error!(
+                    target = module_path!(),
+                    error = e.to_string(),
+                    "Could not pet the watchdog"
+                );
+            }
+        }
+    });
+
+    WatchdogTask { task, watchdog }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time::{sleep, Duration};
+
+    use super::{start, Health};
+    use crate::test_fixtures::watchdog::FakeWatchdog;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_pets_while_healthy() {
+        let watchdog = FakeWatchdog::new();
+        let health = Health::default();
+        let signals = health.register("signals");
+        let scheduler = health.register("scheduler");
+
+        let task = start(
+            Box::new(watchdog.clone()),
+            health,
+            Duration::from_secs(5),
+            Duration::from_secs(30),
+        );
+        for _ in 0..12 {
+            sleep(Duration::from_secs(5)).await;
+            signals.beat();
+            scheduler.beat();
+        }
+        sleep(Duration::from_secs(2)).await;
+
+        // Petted straight away, then every five seconds
+        assert!(watchdog.pets() == 13);
+
+        task.stop().await;
+
+        assert!(watchdog.is_disarmed());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stops_petting_when_stalled() {
+        let watchdog = FakeWatchdog::new();
+        let health = Health::default();
+        let signals = health.register("signals");
+        let _scheduler = health.register("scheduler");
+
+        let _task = start(
+            Box::new(watchdog.clone()),
+            health.clone(),
+            Duration::from_secs(5),
+            Duration::from_secs(30),
+        );
+        for _ in 0..12 {
+            sleep(Duration::from_secs(5)).await;
+            signals.beat();
+        }
+        sleep(Duration::from_secs(2)).await;
+
+        // The scheduler stalled after 30 seconds
+        assert!(watchdog.pets() == 7);
+        assert!(health.stalled(Duration::from_secs(30)) == vec!["scheduler".to_string()]);
+        assert!(!watchdog.is_disarmed());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_replace_all() {
+        let health = Health::default();
+        let _signals = health.register("signals");
+        sleep(Duration::from_secs(60)).await;
+
+        let _shutdown = health.replace_all("shutdown");
+
+        assert!(health.stalled(Duration::from_secs(30)).is_empty());
+        sleep(Duration::from_secs(60)).await;
+        assert!(health.stalled(Duration::from_secs(30)) == vec!["shutdown".to_string()]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_beats_during_work() {
+        let health = Health::default();
+        let scheduler = health.register("scheduler");
+
+        let work = scheduler.during(async {
+            sleep(Duration::from_secs(120)).await;
+            "done"
+        });
+        let check = async {
+            sleep(Duration::from_secs(100)).await;
+            health.stalled(Duration::from_secs(2))
+        };
+        let (output, stalled) = tokio::join!(work, check);
+
+        assert!(output == "done");
+        assert!(stalled.is_empty());
+    }
+}
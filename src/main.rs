@@ -1,16 +1,41 @@
-use std::process::exit;
-
 use diesel::RunQueryDsl;
+#[cfg(feature = "stub")]
+use rpsump::hydro::gpio::sim::SimGpio;
+use rpsump::{
+    application::Application,
+    config::Settings,
+    hydro::{
+        adc::Adc,
+        gpio::Gpio,
+        selftest::{self, DEFAULT_PULSE_MS},
+        watchdog::LinuxWatchdog,
+        Hydro,
+    },
+    middleware::telemetry,
+    repository::{self, Repo},
+};
+#[cfg(not(feature = "stub"))]
 use rpsump::{
-    application::Application, config::Settings, hydro::gpio::Gpio, middleware::telemetry,
-    repository,
+    config::{AdcConfig, GpioConfig},
+    hydro::{
+        adc::{mcp3008::Mcp3008, open_spi, AdcBackend},
+        gpio::{
+            cdev::CdevGpio,
+            i2cdev::I2cDev,
+            mcp23017::{ExpandedGpio, I2cBus, Mcp23017},
+            record::{Recorder, RecordingGpio},
+            GpioBackend,
+        },
+    },
 };
+#[cfg(not(feature = "stub"))]
+use std::time::Duration;
+use tokio::runtime::Handle;
 
 /// Start the application after loading settings, database, telemetry, and the RPi board.
+/// `rpsump selftest [--pulse-ms <ms>]` instead pulses each relay once and prints a report.
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    init_exit_handler();
-
     // Application config
     let settings = Settings::new();
 
@@ -34,27 +59,154 @@ async fn main() -> std::io::Result<()> {
     let _ = diesel::sql_query("PRAGMA busy_timeout=5000;").execute(&mut conn);
     drop(conn);
 
+    // Board
+    #[cfg(not(feature = "stub"))]
+    let gpio = build_gpio(&settings.gpio);
+    #[cfg(feature = "stub")]
     let gpio = build_gpio();
+    #[cfg(not(feature = "stub"))]
+    let adc = settings.adc.as_ref().map(build_adc);
+    #[cfg(feature = "stub")]
+    let adc: Option<Box<dyn Adc>> = None;
+
+    if let Some(pulse_ms) = self_test_command() {
+        #[cfg(not(feature = "stub"))]
+        let gpio = gpio.as_ref();
+        #[cfg(feature = "stub")]
+        let gpio = &gpio;
+        return self_test(&settings, gpio, adc, repo, pulse_ms).await;
+    }
 
     // Application
-    let application = Application::build(settings, &gpio, repo);
+    let watchdog_config = settings.watchdog.clone();
+    #[cfg(not(feature = "stub"))]
+    let application = Application::build(settings, gpio.as_ref(), adc, repo);
+    #[cfg(feature = "stub")]
+    let application = Application::build_simulated(settings, gpio, repo);
+    let mut application = application.map_err(|e| std::io::Error::other(e.to_string()))?;
 
-    application.run_until_stopped().await?;
+    if let Some(config) = watchdog_config {
+        let watchdog = LinuxWatchdog::open(&config.path).expect("Could not open watchdog.");
+        application.watch(Box::new(watchdog), &config);
+    }
 
-    Ok(())
+    let result = application.run_until_stopped().await;
+
+    // Ensure all spans have been reported.
+    opentelemetry::global::shutdown_tracer_provider();
+
+    result
+}
+
+#[cfg(not(feature = "stub"))]
+fn build_gpio(config: &GpioConfig) -> Box<dyn Gpio> {
+    let gpio = build_board_gpio(config);
+
+    let Some(record_path) = &config.record_path else {
+        return gpio;
+    };
+    tracing::info!(path = record_path, "Recording GPIO input changes");
+    let recorder = Recorder::create(record_path).expect("Could not start GPIO recording.");
+
+    Box::new(RecordingGpio::new(gpio, recorder))
+}
+
+#[cfg(not(feature = "stub"))]
+fn build_board_gpio(config: &GpioConfig) -> Box<dyn Gpio> {
+    let board: Box<dyn Gpio> = match config.backend {
+        GpioBackend::Rppal => {
+            Box::new(rppal::gpio::Gpio::new().expect("Could not initialize GPIO."))
+        }
+        GpioBackend::Cdev => {
+            Box::new(CdevGpio::open(&config.chip_path).expect("Could not open GPIO chip."))
+        }
+    };
+
+    let Some(expander) = &config.expander else {
+        return board;
+    };
+    let bus: Box<dyn I2cBus> = match config.backend {
+        GpioBackend::Rppal => {
+            Box::new(rppal::i2c::I2c::with_bus(expander.i2c_bus).expect("Could not open I2C bus."))
+        }
+        GpioBackend::Cdev => {
+            Box::new(I2cDev::open(expander.i2c_bus).expect("Could not open I2C bus."))
+        }
+    };
+    let mcp23017 = Mcp23017::new(
+        bus,
+        expander.address,
+        Duration::from_millis(expander.poll_ms),
+    )
+    .expect("Could not initialize GPIO expander.");
+
+    Box::new(ExpandedGpio::new(board, mcp23017, expander.pin_base))
+}
+
+#[cfg(not(feature = "stub"))]
+fn build_adc(config: &AdcConfig) -> Box<dyn Adc> {
+    let spi = open_spi(config.spi_bus, config.chip_select, config.clock_hz)
+        .expect("Could not open the ADC's SPI bus.");
+
+    match config.backend {
+        AdcBackend::Mcp3008 => Box::new(Mcp3008::new(Box::new(spi))),
+    }
+}
+
+/// Runs without a Raspberry Pi, keeping every pin in memory.
+#[cfg(feature = "stub")]
+fn build_gpio() -> SimGpio {
+    tracing::warn!("Using simulated GPIO; no equipment will be switched");
+    SimGpio::new()
 }
 
-fn build_gpio() -> impl Gpio {
-    rppal::gpio::Gpio::new().expect("Could not initialize GPIO.")
+/// The pulse asked for when run as `rpsump selftest [--pulse-ms <ms>]`.
+fn self_test_command() -> Option<u64> {
+    let mut args = std::env::args().skip(1);
+    if args.next()? != "selftest" {
+        return None;
+    }
+
+    let pulse_ms = match (args.next().as_deref(), args.next()) {
+        (None, _) => DEFAULT_PULSE_MS,
+        (Some("--pulse-ms"), Some(pulse_ms)) => pulse_ms
+            .parse()
+            .expect("--pulse-ms must be a number of milliseconds."),
+        _ => panic!("Usage: rpsump selftest [--pulse-ms <ms>]"),
+    };
+
+    Some(pulse_ms)
 }
 
-// actix-web will handle signals to exit, but doesn't offer a hook to customize it.
-fn init_exit_handler() {
-    ctrlc::set_handler(move || {
-        // Ensure all spans have been reported.
+/// Runs the hardware self-test on its own, with nothing else driving the equipment, and
+/// prints the report. Meant to be run with the service stopped, as it claims the same pins.
+async fn self_test(
+    settings: &Settings,
+    gpio: &dyn Gpio,
+    adc: Option<Box<dyn Adc>>,
+    repo: Repo,
+    pulse_ms: u64,
+) -> std::io::Result<()> {
+    settings
+        .validate_pins()
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+
+    // Sensor changes are left unread, as nothing acts on them during the test
+    let (hydro, _signals) = Hydro::idle(&settings.hydro, Handle::current(), gpio, adc, repo)
+        .expect("Could not create hydro object");
+
+    let report = selftest::run(&hydro, pulse_ms)
+        .await
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&report).expect("Could not serialize the report.")
+    );
+
+    if !report.passed {
         opentelemetry::global::shutdown_tracer_provider();
+        std::process::exit(1);
+    }
 
-        exit(0);
-    })
-    .expect("Error setting Ctrl-C handler");
+    Ok(())
 }
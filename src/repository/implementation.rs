@@ -6,22 +6,31 @@ use crate::auth::password::Password;
 use crate::auth::token::Token;
 use crate::hydro::schedule::ScheduleStatus;
 use crate::repository::models::{
-    irrigation_event::{IrrigationEvent, IrrigationEventStatus, StatusQueryResult},
+    equipment_event::EquipmentEvent,
+    equipment_state::EquipmentState,
+    irrigation_event::{
+        IrrigationEvent, IrrigationEventFilter, IrrigationEventStatus, StatusQueryResult,
+    },
     irrigation_schedule::{
         CreateIrrigationScheduleParams, IrrigationSchedule, UpdateIrrigationScheduleParams,
     },
-    sump_event::SumpEvent,
+    pool_pump_schedule::{
+        join_days, CreatePoolPumpScheduleParams, PoolPumpSchedule, UpdatePoolPumpScheduleParams,
+    },
+    sump_event::{SumpEvent, SUMP_PUMP_KIND},
     user::User,
     user::UserFilter,
     user_event::{EventType, UserEvent},
 };
 use crate::repository::Repository;
 use crate::schema::{
-    irrigation_event, irrigation_schedule, refresh_token, sump_event, user, user_event,
+    equipment_event, equipment_state, irrigation_event, irrigation_schedule, pool_pump_schedule,
+    refresh_token, sump_event, user, user_event,
 };
 use crate::schema::{
     irrigation_event::dsl as irrigation_event_dsl,
-    irrigation_schedule::dsl as irrigation_schedule_dsl, sump_event::dsl as sump_event_dsl,
+    irrigation_schedule::dsl as irrigation_schedule_dsl,
+    pool_pump_schedule::dsl as pool_pump_schedule_dsl, sump_event::dsl as sump_event_dsl,
 };
 use crate::util::spawn_blocking_with_tracing;
 use diesel::internal::table_macro::BoxedSelectStatement;
@@ -118,7 +127,10 @@ impl Repository for Implementation {
 
                 diesel::update(irrigation_event::table)
                     .filter(irrigation_event::id.eq(event.id))
-                    .set(irrigation_event::status.eq(IrrigationEventStatus::InProgress.to_string()))
+                    .set((
+                        irrigation_event::status.eq(IrrigationEventStatus::InProgress.to_string()),
+                        irrigation_event::started_at.eq(Utc::now().naive_utc()),
+                    ))
                     .execute(conn)
                     .map_err(|e| anyhow!("Error beginning irrigation event: {}", e))?;
 
@@ -130,6 +142,27 @@ impl Repository for Implementation {
         Ok(())
     }
 
+    async fn cancel_irrigation_event(&self) -> Result<(), Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .map_err(|e| anyhow!("Database error: {:?}", e))?;
+
+        spawn_blocking_with_tracing(move || {
+            diesel::update(irrigation_event::table)
+                .filter(irrigation_event::status.eq(IrrigationEventStatus::InProgress.to_string()))
+                .set((
+                    irrigation_event::status.eq(IrrigationEventStatus::Cancelled.to_string()),
+                    irrigation_event::end_time.eq(Utc::now().naive_utc()),
+                ))
+                .execute(&mut conn)
+                .map_err(|e| anyhow!(e.to_string()))
+        })
+        .await??;
+
+        Ok(())
+    }
+
     async fn consume_refresh_token(
         &self,
         token_value: String,
@@ -223,6 +256,26 @@ impl Repository for Implementation {
         Ok(token)
     }
 
+    async fn create_equipment_event(&self, device: String, state: String) -> Result<(), Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .map_err(|e| anyhow!("Database error: {:?}", e))?;
+
+        spawn_blocking_with_tracing(move || {
+            diesel::insert_into(equipment_event::table)
+                .values((
+                    equipment_event::device.eq(device),
+                    equipment_event::state.eq(state),
+                ))
+                .execute(&mut conn)
+                .map_err(|e| anyhow!("Error creating equipment event: {}", e))
+        })
+        .await??;
+
+        Ok(())
+    }
+
     async fn create_irrigation_event(
         &self,
         schedule: IrrigationSchedule,
@@ -316,6 +369,33 @@ impl Repository for Implementation {
         Ok(token_result)
     }
 
+    async fn create_pool_pump_schedule(
+        &self,
+        params: CreatePoolPumpScheduleParams,
+    ) -> Result<PoolPumpSchedule, Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .map_err(|e| anyhow!("Database error: {:?}", e))?;
+
+        let pool_pump_sched = spawn_blocking_with_tracing(move || {
+            diesel::insert_into(pool_pump_schedule::table)
+                .values((
+                    pool_pump_schedule_dsl::active.eq(params.active),
+                    pool_pump_schedule_dsl::name.eq(params.name),
+                    pool_pump_schedule_dsl::speed.eq(params.speed.to_string()),
+                    pool_pump_schedule_dsl::start_time.eq(params.start_time),
+                    pool_pump_schedule_dsl::end_time.eq(params.end_time),
+                    pool_pump_schedule_dsl::days_of_week.eq(join_days(&params.days_of_week)),
+                ))
+                .get_result::<PoolPumpSchedule>(&mut conn)
+                .map_err(|e| anyhow!("Error creating pool pump schedule: {}", e))
+        })
+        .await??;
+
+        Ok(pool_pump_sched)
+    }
+
     async fn create_refresh_token(&self, token: &Token) -> Result<(), Error> {
         let mut conn = self
             .pool
@@ -456,6 +536,87 @@ impl Repository for Implementation {
         Ok(maybe_row_deleted)
     }
 
+    async fn delete_pool_pump_schedule(&self, sched_id: i32) -> Result<Option<usize>, Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .map_err(|e| anyhow!("Database error: {:?}", e))?;
+
+        let maybe_row_deleted = spawn_blocking_with_tracing(move || {
+            match diesel::delete(pool_pump_schedule::table)
+                .filter(pool_pump_schedule::id.eq(sched_id))
+                .execute(&mut conn)
+            {
+                Ok(0) => Ok(None),
+                Ok(n) => Ok(Some(n)),
+                Err(e) => Err(e),
+            }
+        })
+        .await
+        .map_err(|e| anyhow!(e.to_string()))??;
+
+        Ok(maybe_row_deleted)
+    }
+
+    async fn equipment_events(&self, since: NaiveDateTime) -> Result<Vec<EquipmentEvent>, Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .map_err(|e| anyhow!("Database error: {:?}", e))?;
+
+        let events = spawn_blocking_with_tracing(move || {
+            // Each device's state going into `since`, so a run already going then is counted
+            let devices = equipment_event::table
+                .filter(equipment_event::created_at.lt(since))
+                .select(equipment_event::device)
+                .distinct()
+                .load::<String>(&mut conn)?;
+            let mut events = vec![];
+            for device in devices {
+                events.push(
+                    equipment_event::table
+                        .filter(equipment_event::device.eq(device))
+                        .filter(equipment_event::created_at.lt(since))
+                        .order((
+                            equipment_event::created_at.desc(),
+                            equipment_event::id.desc(),
+                        ))
+                        .first::<EquipmentEvent>(&mut conn)?,
+                );
+            }
+            events.sort_by_key(|event| (event.created_at, event.id));
+
+            events.extend(
+                equipment_event::table
+                    .filter(equipment_event::created_at.ge(since))
+                    .order((equipment_event::created_at.asc(), equipment_event::id.asc()))
+                    .load::<EquipmentEvent>(&mut conn)?,
+            );
+
+            Ok::<_, DieselError>(events)
+        })
+        .await?
+        .map_err(|e| anyhow!(e))?;
+
+        Ok(events)
+    }
+
+    async fn equipment_states(&self) -> Result<Vec<EquipmentState>, Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .map_err(|e| anyhow!("Database error: {:?}", e))?;
+
+        let states = spawn_blocking_with_tracing(move || {
+            equipment_state::table
+                .load::<EquipmentState>(&mut conn)
+                .map_err(|e| anyhow!(e))
+        })
+        .await??;
+
+        Ok(states)
+    }
+
     async fn finish_irrigation_event(&self) -> Result<(), Error> {
         let mut conn = self
             .pool
@@ -465,7 +626,10 @@ impl Repository for Implementation {
         let _row_updated = spawn_blocking_with_tracing(move || {
             let rows_updated = diesel::update(irrigation_event::table)
                 .filter(irrigation_event::status.eq(IrrigationEventStatus::InProgress.to_string()))
-                .set(irrigation_event::status.eq(IrrigationEventStatus::Completed.to_string()))
+                .set((
+                    irrigation_event::status.eq(IrrigationEventStatus::Completed.to_string()),
+                    irrigation_event::end_time.eq(Utc::now().naive_utc()),
+                ))
                 .execute(&mut conn)
                 .map_err(|e| anyhow!(e.to_string()))?;
 
@@ -480,15 +644,17 @@ impl Repository for Implementation {
         Ok(())
     }
 
-    async fn irrigation_events(&self) -> Result<Vec<IrrigationEvent>, Error> {
+    async fn irrigation_events(
+        &self,
+        filter: IrrigationEventFilter,
+    ) -> Result<Vec<IrrigationEvent>, Error> {
         let mut conn = self
             .pool
             .get()
             .map_err(|e| anyhow!("Database error: {:?}", e))?;
 
         let irrigation_events = spawn_blocking_with_tracing(move || {
-            irrigation_event_dsl::irrigation_event
-                .limit(100)
+            IrrigationEvent::filtered(&filter)
                 .load::<IrrigationEvent>(&mut conn)
                 .map_err(|e| anyhow!(e))
         })
@@ -543,40 +709,49 @@ impl Repository for Implementation {
         Ok(irrigation_sched)
     }
 
-    async fn next_queued_irrigation_event(
-        &self,
-    ) -> Result<Option<(IrrigationEvent, IrrigationSchedule)>, Error> {
+    async fn pool(&self) -> Result<Pool<ConnectionManager<SqliteConnection>>, Error> {
+        Ok(self.pool.clone())
+    }
+
+    async fn pool_pump_schedule_by_id(&self, sched_id: i32) -> Result<PoolPumpSchedule, Error> {
         let mut conn = self
             .pool
             .get()
             .map_err(|e| anyhow!("Database error: {:?}", e))?;
 
-        let event = spawn_blocking_with_tracing(move || {
-            let event = irrigation_event::table
-                .inner_join(
-                    irrigation_schedule::table
-                        .on(irrigation_event::schedule_id.eq(irrigation_schedule::id)),
-                )
-                .filter(irrigation_event::status.eq(IrrigationEventStatus::Queued.to_string()))
-                .order(irrigation_event::created_at.asc())
-                .first::<(IrrigationEvent, IrrigationSchedule)>(&mut conn);
-
-            match event {
-                Ok(event) => Ok(Some(event)),
-                Err(DieselError::NotFound) => Ok(None),
-                Err(e) => Err(anyhow!(
-                    "Internal server error when fetching queued event: {}",
-                    e
-                )),
-            }
+        let pool_pump_sched = spawn_blocking_with_tracing(move || {
+            pool_pump_schedule_dsl::pool_pump_schedule
+                .filter(pool_pump_schedule_dsl::id.eq(sched_id))
+                .first::<PoolPumpSchedule>(&mut conn)
+                .map_err(|e| match e {
+                    DieselError::NotFound => anyhow!("Pool pump schedule not found."),
+                    e => anyhow!(
+                        "Internal server error when fetching pool pump schedule: {}",
+                        e
+                    ),
+                })
         })
         .await??;
 
-        Ok(event)
+        Ok(pool_pump_sched)
     }
 
-    async fn pool(&self) -> Result<Pool<ConnectionManager<SqliteConnection>>, Error> {
-        Ok(self.pool.clone())
+    async fn pool_pump_schedules(&self) -> Result<Vec<PoolPumpSchedule>, Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .map_err(|e| anyhow!("Database error: {:?}", e))?;
+
+        let pool_pump_schedules = spawn_blocking_with_tracing(move || {
+            pool_pump_schedule_dsl::pool_pump_schedule
+                .order(pool_pump_schedule_dsl::start_time.asc())
+                .limit(100)
+                .load::<PoolPumpSchedule>(&mut conn)
+                .map_err(|e| anyhow!(e))
+        })
+        .await??;
+
+        Ok(pool_pump_schedules)
     }
 
     /// Creates events in 'queued' status for any schedules that are eligible to run.
@@ -619,6 +794,33 @@ impl Repository for Implementation {
         Ok(())
     }
 
+    async fn queued_irrigation_events(
+        &self,
+    ) -> Result<Vec<(IrrigationEvent, IrrigationSchedule)>, Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .map_err(|e| anyhow!("Database error: {:?}", e))?;
+
+        let events = spawn_blocking_with_tracing(move || {
+            irrigation_event::table
+                .inner_join(
+                    irrigation_schedule::table
+                        .on(irrigation_event::schedule_id.eq(irrigation_schedule::id)),
+                )
+                .filter(irrigation_event::status.eq(IrrigationEventStatus::Queued.to_string()))
+                .order((
+                    irrigation_event::created_at.asc(),
+                    irrigation_event::id.asc(),
+                ))
+                .load::<(IrrigationEvent, IrrigationSchedule)>(&mut conn)
+                .map_err(|e| anyhow!("Internal server error when fetching queued events: {}", e))
+        })
+        .await??;
+
+        Ok(events)
+    }
+
     async fn revoke_refresh_tokens_for_user(&self, user_id: i32) -> Result<(), Error> {
         let mut conn = self
             .pool
@@ -691,6 +893,35 @@ impl Repository for Implementation {
         Ok(())
     }
 
+    async fn save_equipment_state(&self, device: String, state: String) -> Result<(), Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .map_err(|e| anyhow!("Database error: {:?}", e))?;
+
+        spawn_blocking_with_tracing(move || {
+            let updated_at = Utc::now().naive_utc();
+
+            diesel::insert_into(equipment_state::table)
+                .values((
+                    equipment_state::device.eq(&device),
+                    equipment_state::state.eq(&state),
+                    equipment_state::updated_at.eq(updated_at),
+                ))
+                .on_conflict(equipment_state::device)
+                .do_update()
+                .set((
+                    equipment_state::state.eq(&state),
+                    equipment_state::updated_at.eq(updated_at),
+                ))
+                .execute(&mut conn)
+                .map_err(|e| anyhow!("Error saving equipment state: {}", e))
+        })
+        .await??;
+
+        Ok(())
+    }
+
     async fn schedule_statuses(&self) -> Result<Vec<ScheduleStatus>, Error> {
         let mut conn = self
             .pool
@@ -726,6 +957,25 @@ impl Repository for Implementation {
         Ok(sump_events)
     }
 
+    async fn sump_pump_events(&self, since: NaiveDateTime) -> Result<Vec<SumpEvent>, Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .map_err(|e| anyhow!("Database error: {:?}", e))?;
+
+        let sump_events = spawn_blocking_with_tracing(move || {
+            sump_event_dsl::sump_event
+                .filter(sump_event_dsl::kind.eq(SUMP_PUMP_KIND))
+                .filter(sump_event_dsl::created_at.ge(since))
+                .order((sump_event_dsl::created_at.asc(), sump_event_dsl::id.asc()))
+                .load::<SumpEvent>(&mut conn)
+                .map_err(|e| anyhow!(e))
+        })
+        .await??;
+
+        Ok(sump_events)
+    }
+
     async fn update_irrigation_schedule(
         &self,
         schedule_id: i32,
@@ -743,32 +993,7 @@ impl Repository for Implementation {
 
             match result {
                 Ok(mut irrigation_sched) => {
-                    if let Some(active) = params.active {
-                        irrigation_sched.active = active;
-                    }
-                    if let Some(name) = params.name {
-                        irrigation_sched.name = name;
-                    }
-                    if let Some(duration) = params.duration {
-                        irrigation_sched.duration = duration;
-                    }
-                    if let Some(start_time) = params.start_time {
-                        irrigation_sched.start_time = start_time;
-                    }
-                    if let Some(days_of_week) = params.days_of_week {
-                        irrigation_sched.days_of_week = days_of_week
-                            .iter()
-                            .map(|d| d.to_string())
-                            .collect::<Vec<String>>()
-                            .join(",");
-                    }
-                    if let Some(hoses) = params.hoses {
-                        irrigation_sched.hoses = hoses
-                            .iter()
-                            .map(|d| d.to_string())
-                            .collect::<Vec<String>>()
-                            .join(",");
-                    }
+                    irrigation_sched.apply_update(params);
 
                     let irrigation_sched_clone = irrigation_sched.clone();
 
@@ -798,6 +1023,53 @@ impl Repository for Implementation {
         Ok(irrigation_sched)
     }
 
+    async fn update_pool_pump_schedule(
+        &self,
+        schedule_id: i32,
+        params: UpdatePoolPumpScheduleParams,
+    ) -> Result<Option<PoolPumpSchedule>, Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .map_err(|e| anyhow!("Database error: {:?}", e))?;
+
+        let pool_pump_sched = spawn_blocking_with_tracing(move || {
+            let result = pool_pump_schedule_dsl::pool_pump_schedule
+                .filter(pool_pump_schedule_dsl::id.eq(schedule_id))
+                .first::<PoolPumpSchedule>(&mut conn);
+
+            match result {
+                Ok(mut pool_pump_sched) => {
+                    pool_pump_sched.apply_update(params);
+                    pool_pump_sched.updated_at = Utc::now().naive_utc();
+
+                    let _row_updated = diesel::update(pool_pump_schedule::table)
+                        .filter(pool_pump_schedule::id.eq(schedule_id))
+                        .set((
+                            pool_pump_schedule::active.eq(pool_pump_sched.active),
+                            pool_pump_schedule::name.eq(&pool_pump_sched.name),
+                            pool_pump_schedule::speed.eq(&pool_pump_sched.speed),
+                            pool_pump_schedule::start_time.eq(pool_pump_sched.start_time),
+                            pool_pump_schedule::end_time.eq(pool_pump_sched.end_time),
+                            pool_pump_schedule::days_of_week.eq(&pool_pump_sched.days_of_week),
+                            pool_pump_schedule::updated_at.eq(pool_pump_sched.updated_at),
+                        ))
+                        .execute(&mut conn)
+                        .map_err(|e| anyhow!(e))?;
+
+                    Ok(Some(pool_pump_sched))
+                }
+                Err(e) => match e {
+                    DieselError::NotFound => Ok(None),
+                    _ => Err(anyhow!(e)),
+                },
+            }
+        })
+        .await??;
+
+        Ok(pool_pump_sched)
+    }
+
     async fn update_user(&self, updates: UserUpdateFilter) -> Result<(), Error> {
         let pool = self.pool.clone();
 
@@ -951,6 +1223,7 @@ fn build_statuses(results: Vec<StatusQueryResult>) -> Vec<ScheduleStatus> {
                 status,
                 end_time,
                 event_created_at,
+                started_at,
             } = result;
 
             let schedule = IrrigationSchedule {
@@ -986,7 +1259,7 @@ fn build_statuses(results: Vec<StatusQueryResult>) -> Vec<ScheduleStatus> {
             }
 
             let end_time = match end_time {
-                Some(et) => match NaiveDateTime::parse_from_str(&et, "%Y-%m-%d %H:%M:%S") {
+                Some(et) => match NaiveDateTime::parse_from_str(&et, "%Y-%m-%d %H:%M:%S%.f") {
                     Ok(et) => Some(et),
                     Err(e) => {
                         tracing::error!("Error parsing end time: {:?}", e);
@@ -999,12 +1272,19 @@ fn build_statuses(results: Vec<StatusQueryResult>) -> Vec<ScheduleStatus> {
                 None => None,
             };
 
+            let started_at = started_at.and_then(|st| {
+                NaiveDateTime::parse_from_str(&st, "%Y-%m-%d %H:%M:%S%.f")
+                    .map_err(|e| tracing::error!("Error parsing start time: {:?}", e))
+                    .ok()
+            });
+
             let last_event = IrrigationEvent {
                 id: event_id.unwrap(),
                 hose_id: hose_id.unwrap(),
                 schedule_id: id,
                 status: status.unwrap(),
                 end_time,
+                started_at,
                 created_at: NaiveDateTime::parse_from_str(
                     &event_created_at.unwrap(),
                     "%Y-%m-%d %H:%M:%S%.9f",
@@ -3,12 +3,18 @@ pub mod models;
 
 use anyhow::Error;
 use async_trait::async_trait;
+use chrono::NaiveDateTime;
 use diesel::r2d2::{ConnectionManager, Pool};
 use diesel::sqlite::SqliteConnection;
 use mockall::automock;
 use models::{
-    irrigation_event::IrrigationEvent,
+    equipment_event::EquipmentEvent,
+    equipment_state::EquipmentState,
+    irrigation_event::{IrrigationEvent, IrrigationEventFilter},
     irrigation_schedule::{IrrigationSchedule, UpdateIrrigationScheduleParams},
+    pool_pump_schedule::{
+        CreatePoolPumpScheduleParams, PoolPumpSchedule, UpdatePoolPumpScheduleParams,
+    },
     sump_event::SumpEvent,
     user::User,
     user_event::{EventType, UserEvent},
@@ -31,11 +37,13 @@ pub type Repo = &'static dyn Repository;
 #[async_trait]
 pub trait Repository: Send + Sync + 'static {
     async fn begin_irrigation(&self, event: IrrigationEvent) -> Result<(), Error>;
+    async fn cancel_irrigation_event(&self) -> Result<(), Error>;
     async fn consume_refresh_token(&self, token_value: String) -> Result<i32, RefreshTokenError>;
     async fn create(path: Option<String>) -> Result<Self, Error>
     where
         Self: Sized;
     async fn create_email_verification(&self, user: &User) -> Result<Token, Error>;
+    async fn create_equipment_event(&self, device: String, state: String) -> Result<(), Error>;
     async fn create_irrigation_event(
         &self,
         schedule: IrrigationSchedule,
@@ -46,6 +54,10 @@ pub trait Repository: Send + Sync + 'static {
         params: CreateIrrigationScheduleParams,
     ) -> Result<IrrigationSchedule, Error>;
     async fn create_password_reset(&self, user: User) -> Result<Token, Error>;
+    async fn create_pool_pump_schedule(
+        &self,
+        params: CreatePoolPumpScheduleParams,
+    ) -> Result<PoolPumpSchedule, Error>;
     async fn create_refresh_token(&self, token: &Token) -> Result<(), Error>;
     async fn create_sump_event(&self, info: String, kind: String) -> Result<(), Error>;
     async fn create_user(
@@ -61,31 +73,46 @@ pub trait Repository: Send + Sync + 'static {
         request_ip_address: String,
     ) -> Result<(), Error>;
     async fn delete_irrigation_schedule(&self, sched_id: i32) -> Result<Option<usize>, Error>;
+    async fn delete_pool_pump_schedule(&self, sched_id: i32) -> Result<Option<usize>, Error>;
+    async fn equipment_events(&self, since: NaiveDateTime) -> Result<Vec<EquipmentEvent>, Error>;
+    async fn equipment_states(&self) -> Result<Vec<EquipmentState>, Error>;
     async fn finish_irrigation_event(&self) -> Result<(), Error>;
-    async fn irrigation_events(&self) -> Result<Vec<IrrigationEvent>, Error>;
+    async fn irrigation_events(
+        &self,
+        filter: IrrigationEventFilter,
+    ) -> Result<Vec<IrrigationEvent>, Error>;
     async fn irrigation_schedules(&self) -> Result<Vec<IrrigationSchedule>, Error>;
     async fn irrigation_schedule_by_id(&self, sched_id: i32) -> Result<IrrigationSchedule, Error>;
-    async fn next_queued_irrigation_event(
-        &self,
-    ) -> Result<Option<(IrrigationEvent, IrrigationSchedule)>, Error>;
     async fn pool(&self) -> Result<Pool<ConnectionManager<SqliteConnection>>, Error>;
+    async fn pool_pump_schedule_by_id(&self, sched_id: i32) -> Result<PoolPumpSchedule, Error>;
+    async fn pool_pump_schedules(&self) -> Result<Vec<PoolPumpSchedule>, Error>;
     async fn queue_irrigation_events(
         &self,
         schedules: Vec<IrrigationSchedule>,
     ) -> Result<(), Error>;
+    async fn queued_irrigation_events(
+        &self,
+    ) -> Result<Vec<(IrrigationEvent, IrrigationSchedule)>, Error>;
     async fn revoke_refresh_tokens_for_user(&self, user_id: i32) -> Result<(), Error>;
     async fn reset_password(
         &self,
         password: &Password,
         token: String,
     ) -> Result<(), ResetPasswordError>;
+    async fn save_equipment_state(&self, device: String, state: String) -> Result<(), Error>;
     async fn schedule_statuses(&self) -> Result<Vec<ScheduleStatus>, Error>;
     async fn sump_events(&self) -> Result<Vec<SumpEvent>, Error>;
+    async fn sump_pump_events(&self, since: NaiveDateTime) -> Result<Vec<SumpEvent>, Error>;
     async fn update_irrigation_schedule(
         &self,
         sched_id: i32,
         params: UpdateIrrigationScheduleParams,
     ) -> Result<Option<IrrigationSchedule>, Error>;
+    async fn update_pool_pump_schedule(
+        &self,
+        sched_id: i32,
+        params: UpdatePoolPumpScheduleParams,
+    ) -> Result<Option<PoolPumpSchedule>, Error>;
     async fn user_events(
         &self,
         user_id: i32,
@@ -0,0 +1,22 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::schema::equipment_event;
+
+/// `state` of a heater event when the heater switches on
+pub const HEATER_ON: &str = "on";
+/// `state` of a heater event when the heater switches off
+pub const HEATER_OFF: &str = "off";
+
+/// A device changing state, recorded for runtime and energy reporting. `device` is one of the
+/// names in `hydro::state`; `state` is "on" or "off" for the heater, and the speed for the
+/// pool pump.
+#[derive(Clone, Debug, PartialEq, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = equipment_event)]
+pub struct EquipmentEvent {
+    pub id: i32,
+    pub device: String,
+    pub state: String,
+    pub created_at: NaiveDateTime,
+}
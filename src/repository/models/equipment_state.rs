@@ -0,0 +1,15 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::schema::equipment_state;
+
+/// The last state a device was set to through the API, kept so it can be restored after a
+/// restart. `state` is JSON, in whatever shape the device's state takes.
+#[derive(Clone, Debug, PartialEq, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = equipment_state)]
+pub struct EquipmentState {
+    pub device: String,
+    pub state: String,
+    pub updated_at: NaiveDateTime,
+}
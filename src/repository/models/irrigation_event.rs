@@ -8,16 +8,63 @@ use crate::schema::irrigation_event;
 
 type BoxedQuery<'a> = irrigation_event::BoxedQuery<'a, Sqlite, irrigation_event::SqlType>;
 
+/// Page size used when a filter does not specify a limit.
+pub const DEFAULT_PAGE_SIZE: i64 = 100;
+/// Largest page size a filter may request.
+pub const MAX_PAGE_SIZE: i64 = 1000;
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
-
 pub enum IrrigationEventStatus {
     Cancelled,
     Completed,
+    #[serde(alias = "in_progress")]
     InProgress,
     Queued,
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Narrows down a listing of irrigation events. Results are ordered by id, which follows the
+/// order the events were queued in, and paginated with the id of the last event seen.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct IrrigationEventFilter {
+    pub status: Option<IrrigationEventStatus>,
+    pub schedule_id: Option<i32>,
+    pub hose_id: Option<i32>,
+    pub created_after: Option<NaiveDateTime>,
+    pub created_before: Option<NaiveDateTime>,
+    pub ended_after: Option<NaiveDateTime>,
+    pub ended_before: Option<NaiveDateTime>,
+    /// Id of the last event on the previous page
+    pub cursor: Option<i32>,
+    pub limit: Option<i64>,
+    pub order: Option<SortOrder>,
+}
+
+impl IrrigationEventFilter {
+    pub fn page_size(&self) -> i64 {
+        self.limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE)
+    }
+
+    /// The cursor for the page after `events`, if the page was full.
+    pub fn next_cursor(&self, events: &[IrrigationEvent]) -> Option<i32> {
+        if (events.len() as i64) < self.page_size() {
+            return None;
+        }
+
+        events.last().map(|event| event.id)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Queryable, Selectable, Serialize, Deserialize)]
 #[diesel(belongs_to(IrrigationSchedule))]
 #[diesel(table_name = irrigation_event)]
@@ -28,6 +75,8 @@ pub struct IrrigationEvent {
     pub end_time: Option<NaiveDateTime>,
     pub status: String,
     pub schedule_id: i32,
+    /// When watering actually began; `created_at` is when the event was queued
+    pub started_at: Option<NaiveDateTime>,
 }
 
 #[derive(Clone, Debug, Insertable, PartialEq, Serialize, Deserialize)]
@@ -71,6 +120,8 @@ pub struct StatusQueryResult {
     pub end_time: Option<String>,
     #[diesel(sql_type = Nullable<Text>)]
     pub event_created_at: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub started_at: Option<String>,
 }
 
 impl fmt::Display for IrrigationEventStatus {
@@ -94,9 +145,73 @@ impl IrrigationEvent {
         }
     }
 
+    /// Seconds the hose was watering for, once the event has ended. Events recorded before
+    /// start times were stamped fall back to when they were queued.
+    pub fn runtime_seconds(&self) -> Option<i64> {
+        let started_at = self.started_at.unwrap_or(self.created_at);
+
+        self.end_time
+            .map(|end_time| (end_time - started_at).num_seconds().max(0))
+    }
+
     // Composable queries
     pub fn all() -> BoxedQuery<'static> {
-        irrigation_event::table.limit(100).into_boxed()
+        irrigation_event::table.into_boxed()
+    }
+
+    /// Applies every condition in `filter`, then the sort order and page size.
+    pub fn filtered(filter: &IrrigationEventFilter) -> BoxedQuery<'static> {
+        let mut query = Self::all();
+
+        if let Some(status) = &filter.status {
+            query = Self::with_status(query, status);
+        }
+        if let Some(sched_id) = filter.schedule_id {
+            query = query.filter(irrigation_event::schedule_id.eq(sched_id));
+        }
+        if let Some(hose_id) = filter.hose_id {
+            query = query.filter(irrigation_event::hose_id.eq(hose_id));
+        }
+        if let Some(created_after) = filter.created_after {
+            query = query.filter(irrigation_event::created_at.ge(created_after));
+        }
+        if let Some(created_before) = filter.created_before {
+            query = query.filter(irrigation_event::created_at.lt(created_before));
+        }
+        if let Some(ended_after) = filter.ended_after {
+            query = query.filter(irrigation_event::end_time.ge(ended_after));
+        }
+        if let Some(ended_before) = filter.ended_before {
+            query = query.filter(irrigation_event::end_time.lt(ended_before));
+        }
+
+        Self::paginated(query, filter.cursor, filter.order.unwrap_or_default())
+            .limit(filter.page_size())
+    }
+
+    /// Orders `query` by id and skips everything up to and including `cursor`.
+    pub fn paginated(
+        query: BoxedQuery<'static>,
+        cursor: Option<i32>,
+        order: SortOrder,
+    ) -> BoxedQuery<'static> {
+        match (order, cursor) {
+            (SortOrder::Asc, Some(cursor)) => query
+                .filter(irrigation_event::id.gt(cursor))
+                .order(irrigation_event::id.asc()),
+            (SortOrder::Asc, None) => query.order(irrigation_event::id.asc()),
+            (SortOrder::Desc, Some(cursor)) => query
+                .filter(irrigation_event::id.lt(cursor))
+                .order(irrigation_event::id.desc()),
+            (SortOrder::Desc, None) => query.order(irrigation_event::id.desc()),
+        }
+    }
+
+    pub fn with_status(
+        query: BoxedQuery<'static>,
+        status: &IrrigationEventStatus,
+    ) -> BoxedQuery<'static> {
+        query.filter(irrigation_event::status.eq(status.to_string()))
     }
 
     pub fn by_id(event_id: i32) -> BoxedQuery<'static> {
@@ -112,9 +227,7 @@ impl IrrigationEvent {
     }
 
     pub fn in_progress() -> BoxedQuery<'static> {
-        irrigation_event::table
-            .filter(irrigation_event::status.eq("in_progress"))
-            .into_boxed()
+        Self::with_status(Self::all(), &IrrigationEventStatus::InProgress)
     }
 
     pub fn status_query() -> SqlQuery {
@@ -134,7 +247,8 @@ impl IrrigationEvent {
             event.status,
             event.end_time,
             event.schedule_id,
-            event.created_at as event_created_at
+            event.created_at as event_created_at,
+            event.started_at
             FROM irrigation_schedule AS schedule
             LEFT JOIN (
                 SELECT
@@ -144,6 +258,7 @@ impl IrrigationEvent {
                     created_at,
                     end_time,
                     schedule_id,
+                    started_at,
                     ROW_NUMBER() OVER (
                         PARTITION BY schedule_id, hose_id
                         ORDER BY created_at DESC
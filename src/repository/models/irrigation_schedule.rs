@@ -36,7 +36,7 @@ pub struct CreateIrrigationScheduleParams {
     pub start_time: NaiveTime,
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Clone, Debug, serde::Deserialize)]
 pub struct UpdateIrrigationScheduleParams {
     pub active: Option<bool>,
     pub days_of_week: Option<Vec<Weekday>>,
@@ -46,6 +46,38 @@ pub struct UpdateIrrigationScheduleParams {
     pub start_time: Option<NaiveTime>,
 }
 
+impl IrrigationSchedule {
+    /// Applies the fields present in `params` to the schedule, leaving the rest untouched.
+    pub fn apply_update(&mut self, params: UpdateIrrigationScheduleParams) {
+        if let Some(active) = params.active {
+            self.active = active;
+        }
+        if let Some(name) = params.name {
+            self.name = name;
+        }
+        if let Some(duration) = params.duration {
+            self.duration = duration;
+        }
+        if let Some(start_time) = params.start_time {
+            self.start_time = start_time;
+        }
+        if let Some(days_of_week) = params.days_of_week {
+            self.days_of_week = days_of_week
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<String>>()
+                .join(",");
+        }
+        if let Some(hoses) = params.hoses {
+            self.hoses = hoses
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<String>>()
+                .join(",");
+        }
+    }
+}
+
 fn serialize_hoses<S>(hoses: &str, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -1,5 +1,8 @@
+pub mod equipment_event;
+pub mod equipment_state;
 pub mod irrigation_event;
 pub mod irrigation_schedule;
+pub mod pool_pump_schedule;
 pub mod refresh_token;
 pub mod sump_event;
 pub mod user;
@@ -0,0 +1,85 @@
+use chrono::{NaiveDateTime, NaiveTime, Weekday};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::hydro::pool_pump::PoolPumpSpeed;
+use crate::schema::pool_pump_schedule;
+
+/// Runs the pool pump at `speed` from `start_time` until `end_time` on each of `days_of_week`.
+/// A program whose end time is before its start time runs past midnight into the next day.
+#[derive(Clone, Debug, PartialEq, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = pool_pump_schedule)]
+pub struct PoolPumpSchedule {
+    pub id: i32,
+    pub active: bool,
+    pub name: String,
+    pub speed: String,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub days_of_week: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePoolPumpScheduleParams {
+    pub active: bool,
+    pub days_of_week: Vec<Weekday>,
+    pub name: String,
+    pub speed: PoolPumpSpeed,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct UpdatePoolPumpScheduleParams {
+    pub active: Option<bool>,
+    pub days_of_week: Option<Vec<Weekday>>,
+    pub name: Option<String>,
+    pub speed: Option<PoolPumpSpeed>,
+    pub start_time: Option<NaiveTime>,
+    pub end_time: Option<NaiveTime>,
+}
+
+impl PoolPumpSchedule {
+    /// Applies the fields present in `params` to the schedule, leaving the rest untouched.
+    pub fn apply_update(&mut self, params: UpdatePoolPumpScheduleParams) {
+        if let Some(active) = params.active {
+            self.active = active;
+        }
+        if let Some(name) = params.name {
+            self.name = name;
+        }
+        if let Some(speed) = params.speed {
+            self.speed = speed.to_string();
+        }
+        if let Some(start_time) = params.start_time {
+            self.start_time = start_time;
+        }
+        if let Some(end_time) = params.end_time {
+            self.end_time = end_time;
+        }
+        if let Some(days_of_week) = params.days_of_week {
+            self.days_of_week = join_days(&days_of_week);
+        }
+    }
+
+    pub fn days(&self) -> Vec<Weekday> {
+        self.days_of_week
+            .split(',')
+            .filter_map(|day| day.parse::<Weekday>().ok())
+            .collect()
+    }
+
+    /// The speed to run at; an unreadable speed is treated as off.
+    pub fn pump_speed(&self) -> PoolPumpSpeed {
+        self.speed.parse().unwrap_or(PoolPumpSpeed::Off)
+    }
+}
+
+pub fn join_days(days: &[Weekday]) -> String {
+    days.iter()
+        .map(|d| d.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
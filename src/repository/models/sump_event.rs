@@ -4,6 +4,13 @@ use serde::{Deserialize, Serialize};
 
 use crate::schema::sump_event;
 
+/// `kind` of the events recorded when the sump pump switches on or off
+pub const SUMP_PUMP_KIND: &str = "sump pump";
+/// `info` of a sump pump event when the pump switches on
+pub const PUMP_ON: &str = "pump on";
+/// `info` of a sump pump event when the pump switches off
+pub const PUMP_OFF: &str = "pump off";
+
 #[derive(Clone, Debug, PartialEq, Queryable, Selectable, Serialize, Deserialize)]
 #[diesel(table_name = sump_event)]
 pub struct SumpEvent {
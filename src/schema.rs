@@ -11,6 +11,23 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    equipment_event (id) {
+        id -> Integer,
+        device -> Text,
+        state -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    equipment_state (device) {
+        device -> Text,
+        state -> Text,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     irrigation_event (id) {
         id -> Integer,
@@ -19,6 +36,7 @@ diesel::table! {
         end_time -> Nullable<Timestamp>,
         status -> Text,
         schedule_id -> Integer,
+        started_at -> Nullable<Timestamp>,
     }
 }
 
@@ -36,6 +54,20 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    pool_pump_schedule (id) {
+        id -> Integer,
+        active -> Bool,
+        name -> Text,
+        speed -> Text,
+        start_time -> Time,
+        end_time -> Time,
+        days_of_week -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     sump_event (id) {
         id -> Integer,
@@ -75,8 +107,11 @@ diesel::joinable!(refresh_token -> user (user_id));
 diesel::joinable!(user_event -> user (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    equipment_event,
+    equipment_state,
     irrigation_event,
     irrigation_schedule,
+    pool_pump_schedule,
     refresh_token,
     sump_event,
     user,
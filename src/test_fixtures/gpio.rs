@@ -50,6 +50,9 @@ pub fn build_mock_gpio() -> impl Gpio {
     // Irrigation pins
     gpio = mock_irrigation_pump(gpio, true, Level::High, false, None);
 
+    // Spare relays
+    gpio = mock_outputs(gpio);
+
     gpio
 }
 
@@ -167,6 +170,18 @@ pub fn mock_irrigation_pump(
     mock_gpio
 }
 
+pub fn mock_outputs(mut mock_gpio: MockGpio) -> MockGpio {
+    for output in SETTINGS.hydro.outputs.outputs.iter() {
+        mock_gpio
+            .expect_get()
+            .with(predicate::eq(output.pin))
+            .times(1)
+            .returning(|_| Ok(mock_output_pin(false)));
+    }
+
+    mock_gpio
+}
+
 pub fn mock_pool_pump(mut mock_gpio: MockGpio, pump_speed: PoolPumpSpeed) -> MockGpio {
     let mut low_pin_on = false;
     let mut med_pin_on = false;
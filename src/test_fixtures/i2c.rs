@@ -0,0 +1,80 @@
+use anyhow::{anyhow, Error};
+use std::sync::{Arc, Mutex};
+
+use crate::hydro::gpio::{
+    mcp23017::{I2cBus, GPIO, OLAT},
+    Level,
+};
+
+const IODIR: u8 = 0x00;
+const REGISTERS: usize = 0x16;
+
+/// An MCP23017 on a fake I2C bus. Inputs read whatever `set_input` drove them to, pulled
+/// high until then, and outputs read back their latch.
+#[derive(Clone)]
+pub struct FakeMcp23017 {
+    address: u8,
+    registers: Arc<Mutex<[u8; REGISTERS]>>,
+    inputs: Arc<Mutex<u16>>,
+}
+
+impl FakeMcp23017 {
+    pub fn new(address: u8) -> Self {
+        let mut registers = [0; REGISTERS];
+        registers[usize::from(IODIR)] = 0xFF;
+        registers[usize::from(IODIR) + 1] = 0xFF;
+
+        Self {
+            address,
+            registers: Arc::new(Mutex::new(registers)),
+            inputs: Arc::new(Mutex::new(0xFFFF)),
+        }
+    }
+
+    pub fn set_input(&self, pin: u8, level: Level) {
+        let mut inputs = self.inputs.lock().unwrap();
+        match level {
+            Level::Low => *inputs &= !(1 << pin),
+            _ => *inputs |= 1 << pin,
+        }
+    }
+
+    pub fn register(&self, register: u8) -> u8 {
+        self.read(register)
+    }
+
+    fn read(&self, register: u8) -> u8 {
+        let registers = self.registers.lock().unwrap();
+        match register {
+            GPIO | 0x13 => {
+                let port = usize::from(register - GPIO);
+                let iodir = registers[usize::from(IODIR) + port];
+                let inputs = self.inputs.lock().unwrap().to_le_bytes()[port];
+                let latch = registers[usize::from(OLAT) + port];
+
+                (inputs & iodir) | (latch & !iodir)
+            }
+            _ => registers[usize::from(register)],
+        }
+    }
+}
+
+impl I2cBus for FakeMcp23017 {
+    fn read_register(&mut self, address: u8, register: u8) -> Result<u8, Error> {
+        if address != self.address || usize::from(register) >= REGISTERS {
+            return Err(anyhow!("No answer from {:#04x}", address));
+        }
+
+        Ok(self.read(register))
+    }
+
+    fn write_register(&mut self, address: u8, register: u8, value: u8) -> Result<(), Error> {
+        if address != self.address || usize::from(register) >= REGISTERS {
+            return Err(anyhow!("No answer from {:#04x}", address));
+        }
+
+        self.registers.lock().unwrap()[usize::from(register)] = value;
+
+        Ok(())
+    }
+}
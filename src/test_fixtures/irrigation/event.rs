@@ -13,6 +13,8 @@ pub fn completed_event(
     end_time: Option<NaiveDateTime>,
     #[default(IrrigationEventStatus::Completed)] status: IrrigationEventStatus,
     #[default(1)] schedule_id: i32,
+    #[default(Some(NaiveDateTime::parse_from_str("2021-01-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap()))]
+    started_at: Option<NaiveDateTime>,
 ) -> IrrigationEvent {
     IrrigationEvent {
         id,
@@ -21,5 +23,6 @@ pub fn completed_event(
         end_time,
         status: status.to_string(),
         schedule_id,
+        started_at,
     }
 }
@@ -39,6 +39,7 @@ pub fn daily_schedule_status_query_result(
         status: Some(completed_event.status),
         event_created_at: Some(completed_event.created_at.to_string()),
         end_time: Some(completed_event.end_time.unwrap().to_string()),
+        started_at: completed_event.started_at.map(|st| st.to_string()),
     }
 }
 
@@ -62,5 +63,6 @@ pub fn tues_thurs_schedule_status_query_result(
         status: Some(completed_event.status),
         event_created_at: Some(completed_event.created_at.to_string()),
         end_time: Some(completed_event.end_time.unwrap().to_string()),
+        started_at: completed_event.started_at.map(|st| st.to_string()),
     }
 }
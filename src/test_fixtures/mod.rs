@@ -1,6 +1,13 @@
 pub mod gpio;
+#[cfg(test)]
+pub mod i2c;
 pub mod irrigation;
 pub mod settings;
+// Fakes the integration tests use too, through the test-fixtures feature
+#[cfg(any(test, feature = "test-fixtures"))]
+pub mod spi;
+#[cfg(any(test, feature = "test-fixtures"))]
+pub mod watchdog;
 
 #[cfg(test)]
 pub mod tests {
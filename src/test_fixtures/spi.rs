@@ -0,0 +1,43 @@
+use anyhow::{anyhow, Error};
+use std::sync::{Arc, Mutex};
+
+use crate::hydro::adc::{
+    mcp3008::{CHANNELS, MAX_READING, SINGLE_ENDED, START},
+    SpiBus,
+};
+
+/// An MCP3008 on a fake SPI bus, answering single-ended reads with the channel's last
+/// `set_reading`, or 0. A test can keep a clone to move the level while the app reads it.
+#[derive(Clone, Default)]
+pub struct FakeMcp3008 {
+    readings: Arc<Mutex<[u16; CHANNELS as usize]>>,
+}
+
+impl FakeMcp3008 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_reading(&self, channel: u8, reading: u16) {
+        self.readings.lock().unwrap()[usize::from(channel)] = reading.min(MAX_READING);
+    }
+}
+
+impl SpiBus for FakeMcp3008 {
+    fn transfer(&mut self, write: &[u8], read: &mut [u8]) -> Result<(), Error> {
+        if write.len() != 3 || read.len() != 3 {
+            return Err(anyhow!("Expected a 3 byte transfer"));
+        }
+        if write[0] != START || write[1] & SINGLE_ENDED == 0 {
+            return Err(anyhow!("Expected a single-ended reading"));
+        }
+
+        let channel = usize::from((write[1] >> 4) & 0x07);
+        let reading = self.readings.lock().unwrap()[channel];
+        read[0] = 0;
+        read[1] = (reading >> 8) as u8 & 0x03;
+        read[2] = reading as u8;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+use anyhow::Error;
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+
+use crate::hydro::watchdog::Watchdog;
+
+/// Counts pets and notes whether it was disarmed, where the real device would reboot the
+/// board.
+#[derive(Clone, Default)]
+pub struct FakeWatchdog {
+    pets: Arc<AtomicUsize>,
+    disarmed: Arc<AtomicBool>,
+}
+
+impl FakeWatchdog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pets(&self) -> usize {
+        self.pets.load(Ordering::SeqCst)
+    }
+
+    pub fn is_disarmed(&self) -> bool {
+        self.disarmed.load(Ordering::SeqCst)
+    }
+}
+
+impl Watchdog for FakeWatchdog {
+    fn pet(&mut self) -> Result<(), Error> {
+        self.pets.fetch_add(1, Ordering::SeqCst);
+        self.disarmed.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn disarm(&mut self) -> Result<(), Error> {
+        self.disarmed.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+}
@@ -22,8 +22,8 @@ fn create_expired_token(user: User) -> String {
         exp: expiration_time as u64,
     };
 
-    let jwt_secret =
-        env::var("JWT_SECRET").expect(&format!("{} environment variable not found.", "JWT_SECRET"));
+    let jwt_secret = env::var("JWT_SECRET")
+        .unwrap_or_else(|_| panic!("{} environment variable not found.", "JWT_SECRET"));
 
     jsonwebtoken::encode(
         &jsonwebtoken::Header::new(Algorithm::HS256),
@@ -42,8 +42,8 @@ fn create_valid_token(user: User) -> String {
         exp: expiration_time as u64,
     };
 
-    let jwt_secret =
-        env::var("JWT_SECRET").expect(&format!("{} environment variable not found.", "JWT_SECRET"));
+    let jwt_secret = env::var("JWT_SECRET")
+        .unwrap_or_else(|_| panic!("{} environment variable not found.", "JWT_SECRET"));
 
     jsonwebtoken::encode(
         &jsonwebtoken::Header::new(Algorithm::HS256),
@@ -77,7 +77,7 @@ mod tests {
 
         let result = app
             .api_client
-            .get(&format!("{}/info", &app.address))
+            .get(format!("{}/info", &app.address))
             .header(header_name, header_value)
             .send()
             .await
@@ -92,7 +92,7 @@ mod tests {
 
         let result = app
             .api_client
-            .get(&format!("{}/info", &app.address))
+            .get(format!("{}/info", &app.address))
             .send()
             .await
             .expect("Failed to execute request.");
@@ -116,7 +116,7 @@ mod tests {
 
         let result = app
             .api_client
-            .get(&format!("{}/info", &app.address))
+            .get(format!("{}/info", &app.address))
             .header(header_name, header_value)
             .send()
             .await
@@ -0,0 +1,13 @@
+use rpsump::repository::Repo;
+
+/// Inserts an EquipmentEvent directly into the database, bypassing any application logic.
+async fn insert_equipment_event(repo: Repo, device: &str, state: &str) {
+    repo.create_equipment_event(device.to_string(), state.to_string())
+        .await
+        .unwrap();
+}
+
+pub async fn insert_pool_pump_events(repo: Repo) {
+    insert_equipment_event(repo, "pool_pump", "low").await;
+    insert_equipment_event(repo, "pool_pump", "off").await;
+}
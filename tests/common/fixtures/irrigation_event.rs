@@ -26,7 +26,7 @@ pub fn insert_irrigation_event(
             irrigation_event::created_at.eq(now),
         ))
         .execute(conn)
-        .map_err(|e| anyhow::Error::new(e));
+        .map_err(anyhow::Error::new);
 }
 
 pub fn insert_completed_event(
@@ -44,5 +44,5 @@ pub fn insert_completed_event(
             irrigation_event::end_time.eq(Some(finished_at)),
         ))
         .execute(conn)
-        .map_err(|e| anyhow::Error::new(e));
+        .map_err(anyhow::Error::new);
 }
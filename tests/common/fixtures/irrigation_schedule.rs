@@ -191,10 +191,9 @@ pub async fn insert_irrigation_schedule(
 }
 
 pub async fn insert_irrigation_schedules_fixed(repo: Repo, count: u8) {
-    let now =
-        NaiveDateTime::parse_from_str("2021-01-01 12:34:56".into(), "%Y-%m-%d %H:%M:%S").unwrap();
+    let now = NaiveDateTime::parse_from_str("2021-01-01 12:34:56", "%Y-%m-%d %H:%M:%S").unwrap();
 
-    let dt1 = NaiveTime::parse_from_str("12:34:56".into(), "%H:%M:%S").unwrap();
+    let dt1 = NaiveTime::parse_from_str("12:34:56", "%H:%M:%S").unwrap();
     insert_irrigation_schedule(
         repo,
         true,
@@ -211,7 +210,7 @@ pub async fn insert_irrigation_schedules_fixed(repo: Repo, count: u8) {
     if count < 2 {
         return;
     }
-    let dt2 = NaiveTime::parse_from_str("12:34:56".into(), "%H:%M:%S").unwrap();
+    let dt2 = NaiveTime::parse_from_str("12:34:56", "%H:%M:%S").unwrap();
     insert_irrigation_schedule(
         repo,
         true,
@@ -227,7 +226,7 @@ pub async fn insert_irrigation_schedules_fixed(repo: Repo, count: u8) {
     if count < 3 {
         return;
     }
-    let dt3 = NaiveTime::parse_from_str("12:34:56".into(), "%H:%M:%S").unwrap();
+    let dt3 = NaiveTime::parse_from_str("12:34:56", "%H:%M:%S").unwrap();
     insert_irrigation_schedule(
         repo,
         true,
@@ -249,7 +248,7 @@ pub async fn insert_irrigation_schedules_fixed(repo: Repo, count: u8) {
     if count < 4 {
         return;
     }
-    let dt4 = NaiveTime::parse_from_str("12:34:56".into(), "%H:%M:%S").unwrap();
+    let dt4 = NaiveTime::parse_from_str("12:34:56", "%H:%M:%S").unwrap();
     insert_irrigation_schedule(
         repo,
         false,
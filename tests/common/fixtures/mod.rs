@@ -1,3 +1,4 @@
+pub mod equipment_event;
 pub mod irrigation_event;
 pub mod irrigation_schedule;
 pub mod sump_event;
@@ -2,6 +2,7 @@ use std::env;
 use std::fs::{self, File};
 use std::path::PathBuf;
 
+use anyhow::Error;
 use chrono::Utc;
 use diesel::r2d2::{ConnectionManager, Pool};
 use diesel::sql_types::Text;
@@ -16,9 +17,12 @@ use tokio::sync::OnceCell;
 use wiremock::MockServer;
 
 use rpsump::application::Application;
-use rpsump::config::Settings;
-use rpsump::hydro::gpio::Gpio;
+use rpsump::config::{LevelSensorConfig, Settings, WatchdogConfig};
+#[cfg(feature = "stub")]
+use rpsump::hydro::gpio::sim::SimGpio;
+use rpsump::hydro::{adc::mcp3008::Mcp3008, gpio::Gpio};
 use rpsump::repository::{self, Repo};
+use rpsump::test_fixtures::{spi::FakeMcp3008, watchdog::FakeWatchdog};
 
 use crate::auth::authenticated_user::create_auth_header;
 use crate::controllers::auth::{TEST_EMAIL, TEST_PASSWORD};
@@ -97,7 +101,18 @@ impl TestApp {
         let (header_name, header_value) = create_auth_header(&token);
 
         self.api_client
-            .delete(&format!("{}/irrigation/schedule/{}", &self.address, id))
+            .delete(format!("{}/irrigation/schedule/{}", &self.address, id))
+            .header(header_name, header_value)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    pub async fn delete_pool_pump_schedule(&self, token: String, id: i32) -> reqwest::Response {
+        let (header_name, header_value) = create_auth_header(&token);
+
+        self.api_client
+            .delete(format!("{}/pool_pump/schedule/{}", &self.address, id))
             .header(header_name, header_value)
             .send()
             .await
@@ -106,7 +121,7 @@ impl TestApp {
 
     pub async fn get_email_verification(&self, token: String) -> reqwest::Response {
         self.api_client
-            .get(&format!(
+            .get(format!(
                 "{}/auth/verify_email?token={}",
                 &self.address, token
             ))
@@ -115,11 +130,19 @@ impl TestApp {
             .unwrap()
     }
 
+    pub async fn get_health(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/health", &self.address))
+            .send()
+            .await
+            .unwrap()
+    }
+
     pub async fn get_info(&self, token: String) -> reqwest::Response {
         let (header_name, header_value) = create_auth_header(&token);
 
         self.api_client
-            .get(&format!("{}/info", &self.address))
+            .get(format!("{}/info", &self.address))
             .header(header_name, header_value)
             .send()
             .await
@@ -130,7 +153,33 @@ impl TestApp {
         let (header_name, header_value) = create_auth_header(&token);
 
         self.api_client
-            .get(&format!("{}/irrigation/event", &self.address))
+            .get(format!("{}/irrigation/event", &self.address))
+            .header(header_name, header_value)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_irrigation_events_filtered(
+        &self,
+        token: String,
+        query: &str,
+    ) -> reqwest::Response {
+        let (header_name, header_value) = create_auth_header(&token);
+
+        self.api_client
+            .get(format!("{}/irrigation/event?{}", &self.address, query))
+            .header(header_name, header_value)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_irrigation_usage(&self, token: String, query: &str) -> reqwest::Response {
+        let (header_name, header_value) = create_auth_header(&token);
+
+        self.api_client
+            .get(format!("{}/irrigation/usage?{}", &self.address, query))
             .header(header_name, header_value)
             .send()
             .await
@@ -141,7 +190,7 @@ impl TestApp {
         let (header_name, header_value) = create_auth_header(&token);
 
         self.api_client
-            .get(&format!("{}/irrigation/schedule/{}", &self.address, id))
+            .get(format!("{}/irrigation/schedule/{}", &self.address, id))
             .header(header_name, header_value)
             .send()
             .await
@@ -152,7 +201,52 @@ impl TestApp {
         let (header_name, header_value) = create_auth_header(&token);
 
         self.api_client
-            .get(&format!("{}/irrigation/schedule", &self.address))
+            .get(format!("{}/irrigation/schedule", &self.address))
+            .header(header_name, header_value)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_outputs(&self, token: String) -> reqwest::Response {
+        let (header_name, header_value) = create_auth_header(&token);
+
+        self.api_client
+            .get(format!("{}/outputs", &self.address))
+            .header(header_name, header_value)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_pool_pump_schedules(&self, token: String) -> reqwest::Response {
+        let (header_name, header_value) = create_auth_header(&token);
+
+        self.api_client
+            .get(format!("{}/pool_pump/schedule", &self.address))
+            .header(header_name, header_value)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_pool_usage(&self, token: String, query: &str) -> reqwest::Response {
+        let (header_name, header_value) = create_auth_header(&token);
+
+        self.api_client
+            .get(format!("{}/pool/usage?{}", &self.address, query))
+            .header(header_name, header_value)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    #[cfg(feature = "stub")]
+    pub async fn get_sim_pins(&self, token: String) -> reqwest::Response {
+        let (header_name, header_value) = create_auth_header(&token);
+
+        self.api_client
+            .get(format!("{}/sim/pins", &self.address))
             .header(header_name, header_value)
             .send()
             .await
@@ -163,7 +257,7 @@ impl TestApp {
         let (header_name, header_value) = create_auth_header(&token);
 
         self.api_client
-            .get(&format!("{}/sump_event", &self.address))
+            .get(format!("{}/sump_event", &self.address))
             .header(header_name, header_value)
             .send()
             .await
@@ -175,11 +269,44 @@ impl TestApp {
         token: String,
         id: i32,
         body: Value,
+        query: &str,
     ) -> reqwest::Response {
         let (header_name, header_value) = create_auth_header(&token);
 
         self.api_client
-            .patch(&format!("{}/irrigation/schedule/{}", &self.address, id))
+            .patch(format!(
+                "{}/irrigation/schedule/{}?{}",
+                &self.address, id, query
+            ))
+            .header(header_name, header_value)
+            .json(&body)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    pub async fn patch_pool_pump_schedule(
+        &self,
+        token: String,
+        id: i32,
+        body: Value,
+    ) -> reqwest::Response {
+        let (header_name, header_value) = create_auth_header(&token);
+
+        self.api_client
+            .patch(format!("{}/pool_pump/schedule/{}", &self.address, id))
+            .header(header_name, header_value)
+            .json(&body)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_hardware_selftest(&self, token: String, body: Value) -> reqwest::Response {
+        let (header_name, header_value) = create_auth_header(&token);
+
+        self.api_client
+            .post(format!("{}/hardware/selftest", &self.address))
             .header(header_name, header_value)
             .json(&body)
             .send()
@@ -191,7 +318,7 @@ impl TestApp {
         let (header_name, header_value) = create_auth_header(&token);
 
         self.api_client
-            .post(&format!("{}/heater", &self.address))
+            .post(format!("{}/heater", &self.address))
             .header(header_name, header_value)
             .json(&json!({"switch": "off"}))
             .send()
@@ -203,7 +330,7 @@ impl TestApp {
         let (header_name, header_value) = create_auth_header(&token);
 
         self.api_client
-            .post(&format!("{}/heater", &self.address))
+            .post(format!("{}/heater", &self.address))
             .header(header_name, header_value)
             .json(&json!({"switch": "on"}))
             .send()
@@ -211,11 +338,28 @@ impl TestApp {
             .unwrap()
     }
 
-    pub async fn post_irrigation_schedule(&self, token: String, body: Value) -> reqwest::Response {
+    pub async fn post_heater_thermostat(&self, token: String, body: Value) -> reqwest::Response {
         let (header_name, header_value) = create_auth_header(&token);
 
         self.api_client
-            .post(&format!("{}/irrigation/schedule", &self.address))
+            .post(format!("{}/heater", &self.address))
+            .header(header_name, header_value)
+            .json(&body)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_irrigation_schedule(
+        &self,
+        token: String,
+        body: Value,
+        query: &str,
+    ) -> reqwest::Response {
+        let (header_name, header_value) = create_auth_header(&token);
+
+        self.api_client
+            .post(format!("{}/irrigation/schedule?{}", &self.address, query))
             .header(header_name, header_value)
             .json(&body)
             .send()
@@ -228,19 +372,31 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/auth/login", &self.address))
+            .post(format!("{}/auth/login", &self.address))
             .json(body)
             .send()
             .await
             .unwrap()
     }
 
+    pub async fn post_output(&self, token: String, name: &str, body: Value) -> reqwest::Response {
+        let (header_name, header_value) = create_auth_header(&token);
+
+        self.api_client
+            .post(format!("{}/outputs/{}", &self.address, name))
+            .header(header_name, header_value)
+            .json(&body)
+            .send()
+            .await
+            .unwrap()
+    }
+
     pub async fn post_password_reset<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/auth/reset_password?token", &self.address))
+            .post(format!("{}/auth/reset_password?token", &self.address))
             .json(body)
             .send()
             .await
@@ -250,7 +406,41 @@ impl TestApp {
     pub async fn post_pool_pump(&self, token: String, body: Value) -> reqwest::Response {
         let (header_name, header_value) = create_auth_header(&token);
         self.api_client
-            .post(&format!("{}/pool_pump", &self.address))
+            .post(format!("{}/pool_pump", &self.address))
+            .header(header_name, header_value)
+            .json(&body)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_pool_pump_cancel_priming(&self, token: String) -> reqwest::Response {
+        let (header_name, header_value) = create_auth_header(&token);
+
+        self.api_client
+            .post(format!("{}/pool_pump/cancel_priming", &self.address))
+            .header(header_name, header_value)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_pool_pump_resume(&self, token: String) -> reqwest::Response {
+        let (header_name, header_value) = create_auth_header(&token);
+
+        self.api_client
+            .post(format!("{}/pool_pump/resume", &self.address))
+            .header(header_name, header_value)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_pool_pump_schedule(&self, token: String, body: Value) -> reqwest::Response {
+        let (header_name, header_value) = create_auth_header(&token);
+
+        self.api_client
+            .post(format!("{}/pool_pump/schedule", &self.address))
             .header(header_name, header_value)
             .json(&body)
             .send()
@@ -263,7 +453,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/auth/request_password_reset", &self.address))
+            .post(format!("{}/auth/request_password_reset", &self.address))
             .json(body)
             .send()
             .await
@@ -276,7 +466,7 @@ impl TestApp {
     {
         let result = self
             .api_client
-            .post(&format!("{}/auth/signup", &self.address))
+            .post(format!("{}/auth/signup", &self.address))
             .json(body)
             .send()
             .await
@@ -284,9 +474,90 @@ impl TestApp {
 
         result
     }
+
+    #[cfg(feature = "stub")]
+    pub async fn post_sim_pin_level(
+        &self,
+        token: String,
+        pin: u8,
+        body: Value,
+    ) -> reqwest::Response {
+        let (header_name, header_value) = create_auth_header(&token);
+
+        self.api_client
+            .post(format!("{}/sim/pin/{}/level", &self.address, pin))
+            .header(header_name, header_value)
+            .json(&body)
+            .send()
+            .await
+            .unwrap()
+    }
 }
 
 pub async fn spawn_app(gpio: &dyn Gpio) -> TestApp {
+    spawn_app_with(|settings, repo| Application::build(settings, gpio, None, repo)).await
+}
+
+/// Spawns the app reading the sump's level on channel 0 of `adc`, and the reservoir's on
+/// channel 1, both from 100 at empty to 900 at full.
+pub async fn spawn_levelled_app(gpio: &dyn Gpio, adc: FakeMcp3008) -> TestApp {
+    spawn_app_with(|mut settings, repo| {
+        let level = |channel| LevelSensorConfig {
+            channel,
+            empty: 100,
+            full: 900,
+        };
+        settings.hydro.sump.level = Some(level(0));
+        settings.hydro.irrigation.level = Some(level(1));
+
+        let adc = Mcp3008::new(Box::new(adc));
+        Application::build(settings, gpio, Some(Box::new(adc)), repo)
+    })
+    .await
+}
+
+/// Spawns the app petting `watchdog` while it makes progress.
+pub async fn spawn_watched_app(
+    gpio: &dyn Gpio,
+    watchdog: FakeWatchdog,
+    config: WatchdogConfig,
+) -> TestApp {
+    spawn_app_with(|settings, repo| {
+        let mut application = Application::build(settings, gpio, None, repo)?;
+        application.watch(Box::new(watchdog), &config);
+        Ok(application)
+    })
+    .await
+}
+
+/// Spawns the app with the `/sim` routes, driving `gpio`.
+#[cfg(feature = "stub")]
+pub async fn spawn_simulated_app(gpio: SimGpio) -> TestApp {
+    spawn_app_with(|settings, repo| Application::build_simulated(settings, gpio, repo)).await
+}
+
+/// Builds the app on `gpio` from the test settings, adjusted by `configure`, without serving it.
+pub async fn build_app(
+    gpio: &dyn Gpio,
+    configure: impl FnOnce(&mut Settings),
+) -> Result<Application, Error> {
+    env::set_var("RPSUMP_TEST", "true");
+
+    let mut settings = Settings::new();
+    settings.server.port = 0;
+    configure(&mut settings);
+
+    let (test_repo, _temp_dir) = migrated_pathbuf().await;
+    let repo = repository::implementation(Some(test_repo.to_str().unwrap().to_string()))
+        .await
+        .expect("Could not create repository.");
+
+    Application::build(settings, gpio, None, repo)
+}
+
+async fn spawn_app_with(
+    build: impl FnOnce(Settings, Repo) -> Result<Application, Error>,
+) -> TestApp {
     // TODO: move this to a settings input
     env::set_var("RPSUMP_TEST", "true");
 
@@ -302,10 +573,10 @@ pub async fn spawn_app(gpio: &dyn Gpio) -> TestApp {
         .await
         .expect("Could not create repository.");
 
-    let application = Application::build(settings.clone(), gpio, repo);
+    let application = build(settings.clone(), repo).expect("Could not build the application.");
     let port = application.port();
 
-    let _ = tokio::spawn(application.run_until_stopped());
+    drop(tokio::spawn(application.run_until_stopped()));
 
     let client = reqwest::Client::builder()
         .redirect(reqwest::redirect::Policy::none())
@@ -119,5 +119,5 @@ async fn set_email_verification_expiry(
         .filter(user::email.eq(email))
         .set(user::email_verification_token_expires_at.eq(time.to_string()))
         .execute(&mut conn)
-        .map_err(|e| anyhow::Error::new(e))
+        .map_err(anyhow::Error::new)
 }
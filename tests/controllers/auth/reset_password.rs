@@ -84,7 +84,7 @@ async fn reset_password_failed_token_expired() {
         password_reset_token: Some(Some(expired_token.value.clone())),
         password_reset_token_expires_at: Some(yesterday),
     };
-    let _user_update = app.repo.update_user(user_update_filter).await.unwrap();
+    app.repo.update_user(user_update_filter).await.unwrap();
 
     // Validate the reset password response
     let reset_response = app
@@ -0,0 +1,79 @@
+use rpsump::hydro::gpio::{sim::SimGpio, Level};
+use serde_json::{json, Value};
+use tokio::time::{sleep, Duration};
+
+use crate::common::test_app::spawn_app;
+use crate::controllers::user_params;
+
+#[tokio::test]
+async fn hardware_selftest_success() {
+    // Arrange
+    let gpio = SimGpio::new();
+    let app = spawn_app(&gpio).await;
+    let response = app.post_login(&user_params()).await;
+    let body: Value = response.json().await.unwrap();
+    let token = body["token"].as_str().unwrap();
+    gpio.set_input(5, Level::Low).unwrap();
+
+    // Act
+    let response = app
+        .post_hardware_selftest(token.to_string(), json!({"pulseMs": 10}))
+        .await;
+    let status = response.status();
+    let report: Value = response.json().await.unwrap();
+
+    // Assert
+    assert!(status.is_success());
+    assert!(report["passed"] == true);
+    assert!(report["pulseMs"] == 10);
+    assert!(report["controls"].as_array().unwrap().len() == 13);
+    // The pool pump is off, so the heater has no flow, and neither pump may be run dry
+    assert!(report["controls"][0]["name"] == "heater");
+    assert!(report["controls"][0]["outcome"] == "skipped");
+    assert!(
+        report["controls"][0]["note"]
+            == "The pool pump must be running at low speed or faster to heat."
+    );
+    for skipped in [1, 6, 7, 8, 9] {
+        assert!(report["controls"][skipped]["outcome"] == "skipped");
+    }
+    assert!(report["controls"][2]["name"] == "irrigationValve1");
+    assert!(report["controls"][2]["outcome"] == "passed");
+    assert!(report["controls"][2]["readOn"] == true);
+    assert!(report["sensors"][1]["name"] == "sumpHigh");
+    assert!(report["sensors"][1]["level"] == "low");
+    // Each relay is left off afterwards
+    assert!(gpio.level(26) == Some(Level::Low));
+}
+
+#[tokio::test]
+async fn hardware_selftest_refused() {
+    // Arrange
+    let gpio = SimGpio::new();
+    let app = spawn_app(&gpio).await;
+    let response = app.post_login(&user_params()).await;
+    let body: Value = response.json().await.unwrap();
+    let token = body["token"].as_str().unwrap();
+
+    // Act
+    let invalid_response = app
+        .post_hardware_selftest(token.to_string(), json!({"pulseMs": 60000}))
+        .await;
+    let no_auth_response = app
+        .post_hardware_selftest("invalid-token".to_string(), json!({}))
+        .await;
+
+    // The sump high sensor (pin 5) starts the sump pump once it settles
+    gpio.set_input(5, Level::Low).unwrap();
+    gpio.set_input(5, Level::High).unwrap();
+    sleep(Duration::from_secs(3)).await;
+    let pumping_response = app
+        .post_hardware_selftest(token.to_string(), json!({}))
+        .await;
+    let pumping_body: Value = pumping_response.json().await.unwrap();
+
+    // Assert
+    assert!(invalid_response.status() == 400);
+    assert!(no_auth_response.status() == 401);
+    assert!(pumping_body["message"] == "The sump is pumping.");
+}
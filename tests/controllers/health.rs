@@ -0,0 +1,43 @@
+use rpsump::config::WatchdogConfig;
+use rpsump::hydro::gpio::sim::SimGpio;
+use rpsump::test_fixtures::watchdog::FakeWatchdog;
+use serde_json::Value;
+use tokio::time::{sleep, Duration};
+
+use crate::common::test_app::{spawn_app, spawn_watched_app};
+
+#[tokio::test]
+async fn health_without_auth() {
+    // Arrange
+    let gpio = SimGpio::new();
+    let app = spawn_app(&gpio).await;
+
+    // Act
+    let response = app.get_health().await;
+    let status = response.status();
+    let body: Value = response.json().await.unwrap();
+
+    // Assert
+    assert!(status.is_success());
+    assert!(body["status"] == "ok");
+}
+
+#[tokio::test]
+async fn health_watchdog_petted() {
+    // Arrange
+    let gpio = SimGpio::new();
+    let watchdog = FakeWatchdog::new();
+    let config = WatchdogConfig {
+        path: "".into(),
+        pet_sec: 1,
+        stall_sec: 5,
+    };
+    let _app = spawn_watched_app(&gpio, watchdog.clone(), config).await;
+
+    // Act
+    sleep(Duration::from_millis(2500)).await;
+
+    // Assert
+    assert!(watchdog.pets() >= 2);
+    assert!(!watchdog.is_disarmed());
+}
@@ -1,5 +1,9 @@
-use rpsump::test_fixtures::gpio::build_mock_gpio;
-use serde_json::Value;
+use rpsump::hydro::{gpio::Level, gpio::MockGpio, pool_pump::PoolPumpSpeed};
+use rpsump::test_fixtures::gpio::{
+    build_mock_gpio, mock_heater, mock_irrigation_pump, mock_outputs, mock_pool_pump,
+    mock_sump_pump,
+};
+use serde_json::{json, Value};
 
 use crate::common::test_app::spawn_app;
 use crate::controllers::user_params;
@@ -25,6 +29,84 @@ async fn heater_success() {
     assert_eq!(off_response_body["status"].as_str(), Some("ok"))
 }
 
+#[tokio::test]
+async fn heater_interlock_pump_off() {
+    // Arrange
+    let mut gpio = mock_heater(MockGpio::new(), false);
+    gpio = mock_pool_pump(gpio, PoolPumpSpeed::Off);
+    gpio = mock_sump_pump(gpio, false, false, false);
+    gpio = mock_irrigation_pump(gpio, true, Level::High, false, None);
+    gpio = mock_outputs(gpio);
+    let app = spawn_app(&gpio).await;
+
+    // Act
+    let response = app.post_login(&user_params()).await;
+    let body: Value = response.json().await.unwrap();
+    let token = body["token"].as_str().unwrap();
+
+    let on_response = app.post_heater_on(token.to_string()).await;
+
+    // Assert
+    assert!(on_response.status() == 409);
+}
+
+#[tokio::test]
+async fn heater_thermostat() {
+    // Arrange
+    let app = spawn_app(&build_mock_gpio()).await;
+
+    let response = app.post_login(&user_params()).await;
+    let body: Value = response.json().await.unwrap();
+    let token = body["token"].as_str().unwrap();
+
+    // Act
+    let missing_setpoint_response = app
+        .post_heater_thermostat(token.to_string(), json!({"switch": "thermostat"}))
+        .await;
+
+    let thermostat_response = app
+        .post_heater_thermostat(
+            token.to_string(),
+            json!({"switch": "thermostat", "setpoint": 28.0, "hysteresis": 1.0}),
+        )
+        .await;
+    let thermostat_info: Value = app.get_info(token.to_string()).await.json().await.unwrap();
+
+    let _ = app.post_heater_off(token.to_string()).await;
+    let manual_info: Value = app.get_info(token.to_string()).await.json().await.unwrap();
+
+    // Assert
+    assert!(missing_setpoint_response.status() == 400);
+    assert!(thermostat_response.status() == 200);
+    assert!(thermostat_info["thermostat"]["setpoint"].as_f64() == Some(28.0));
+    assert!(thermostat_info["thermostat"]["hysteresis"].as_f64() == Some(1.0));
+    assert!(manual_info["thermostat"].is_null());
+}
+
+#[tokio::test]
+async fn heater_state_saved() {
+    // Arrange
+    let app = spawn_app(&build_mock_gpio()).await;
+
+    let response = app.post_login(&user_params()).await;
+    let body: Value = response.json().await.unwrap();
+    let token = body["token"].as_str().unwrap();
+
+    // Act
+    let _ = app
+        .post_heater_thermostat(
+            token.to_string(),
+            json!({"switch": "thermostat", "setpoint": 28.0}),
+        )
+        .await;
+    let states = app.repo.equipment_states().await.unwrap();
+
+    // Assert
+    let heater = states.iter().find(|s| s.device == "heater").unwrap();
+    let heater_state: Value = serde_json::from_str(&heater.state).unwrap();
+    assert!(heater_state == json!({"mode": "thermostat", "setpoint": 28.0, "hysteresis": 0.5}));
+}
+
 #[tokio::test]
 async fn heater_failed_no_auth() {
     // Arrange
@@ -1,8 +1,8 @@
-use rpsump::test_fixtures::gpio::build_mock_gpio;
+use rpsump::test_fixtures::{gpio::build_mock_gpio, spi::FakeMcp3008};
 use serde_json::Value;
 
 use crate::common::fixtures::sump_event::insert_sump_events;
-use crate::common::test_app::spawn_app;
+use crate::common::test_app::{spawn_app, spawn_levelled_app};
 use crate::controllers::user_params;
 
 #[tokio::test]
@@ -20,8 +20,60 @@ async fn info_success() {
     let response: Value = sump_event_response.json().await.unwrap();
 
     // Assert
+    assert!(response["freezeProtection"].as_bool() == Some(false));
     assert!(response["heater"].as_bool() == Some(true));
+    assert!(response["poolPumpPriming"].as_bool() == Some(false));
     assert!(response["poolPumpSpeed"].as_str() == Some("max"));
+    assert!(response["reservoirLevel"].is_null());
+    assert!(response["sumpLevel"].is_null());
+    assert!(response["temperature"].as_f64() == Some(23.125));
+    assert!(response["thermostat"].is_null());
+}
+
+#[tokio::test]
+async fn info_water_levels() {
+    // Arrange
+    let adc = FakeMcp3008::new();
+    adc.set_reading(0, 300);
+    adc.set_reading(1, 900);
+    let app = spawn_levelled_app(&build_mock_gpio(), adc).await;
+    let response = app.post_login(&user_params()).await;
+    let body: Value = response.json().await.unwrap();
+    let token = body["token"].as_str().unwrap();
+
+    // Act
+    let response: Value = app.get_info(token.to_string()).await.json().await.unwrap();
+
+    // Assert
+    assert!(response["sumpLevel"].as_f64() == Some(25.0));
+    assert!(response["reservoirLevel"].as_f64() == Some(100.0));
+}
+
+#[tokio::test]
+async fn info_equipment_snapshot() {
+    // Arrange
+    let app = spawn_app(&build_mock_gpio()).await;
+    let response = app.post_login(&user_params()).await;
+    let body: Value = response.json().await.unwrap();
+    let token = body["token"].as_str().unwrap();
+
+    // Act
+    let before: Value = app.get_info(token.to_string()).await.json().await.unwrap();
+    app.post_heater_on(token.to_string()).await;
+    let after: Value = app.get_info(token.to_string()).await.json().await.unwrap();
+
+    // Assert
+    // Including the two spare relays in .env.test
+    assert!(before["equipment"].as_object().unwrap().len() == 13);
+    // The mock heater pin reads on, but nothing has switched it on
+    assert!(before["equipment"]["heater"]["commanded"] == false);
+    assert!(before["equipment"]["heater"]["pin"] == true);
+    assert!(before["equipment"]["heater"]["mismatch"] == true);
+    assert!(before["equipment"]["heater"]["changedBy"].is_null());
+    assert!(after["equipment"]["heater"]["commanded"] == true);
+    assert!(after["equipment"]["heater"]["mismatch"] == false);
+    assert!(after["equipment"]["heater"]["changedAt"].is_string());
+    assert!(after["equipment"]["heater"]["changedBy"]["kind"] == "user");
 }
 
 #[tokio::test]
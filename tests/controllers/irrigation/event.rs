@@ -1,3 +1,4 @@
+use rpsump::controllers::irrigation::event::NEXT_CURSOR_HEADER;
 use rpsump::repository::models::irrigation_event::{IrrigationEvent, IrrigationEventStatus};
 use rpsump::test_fixtures::gpio::build_mock_gpio;
 use serde_json::Value;
@@ -43,3 +44,111 @@ async fn list_events_success() {
 
     assert!(status.is_success());
 }
+
+#[tokio::test]
+async fn list_events_filtered() {
+    // Arrange
+    let app = spawn_app(&build_mock_gpio()).await;
+
+    // Inserts completed events for hoses 3 and 4
+    let sched = insert_finished_schedule(app.repo).await;
+
+    // The scheduler leaves cancelled events alone, so they are stable for the assertions.
+    let mut conn = app.repo.pool().await.unwrap().get().unwrap();
+    insert_irrigation_event(
+        &mut conn,
+        1,
+        sched.clone(),
+        IrrigationEventStatus::Cancelled,
+    );
+    insert_irrigation_event(
+        &mut conn,
+        2,
+        sched.clone(),
+        IrrigationEventStatus::Cancelled,
+    );
+    drop(conn);
+
+    // Act
+    let response = app.post_login(&user_params()).await;
+    let body: Value = response.json().await.unwrap();
+    let token = body["token"].as_str().unwrap();
+
+    let cancelled_response = app
+        .get_irrigation_events_filtered(token.to_string(), "status=cancelled")
+        .await;
+    let cancelled: Vec<IrrigationEvent> = cancelled_response.json().await.unwrap();
+
+    let hose_response = app
+        .get_irrigation_events_filtered(
+            token.to_string(),
+            &format!("schedule_id={}&hose_id=3&status=completed", sched.id),
+        )
+        .await;
+    let hose_events: Vec<IrrigationEvent> = hose_response.json().await.unwrap();
+
+    let invalid_response = app
+        .get_irrigation_events_filtered(token.to_string(), "status=unknown")
+        .await;
+
+    // Assert
+    assert!(cancelled.len() == 2);
+    assert!(cancelled
+        .iter()
+        .all(|e| e.status == IrrigationEventStatus::Cancelled.to_string()));
+    assert!(hose_events.len() == 1);
+    assert!(hose_events[0].hose_id == 3);
+    assert!(invalid_response.status() == 400);
+}
+
+#[tokio::test]
+async fn list_events_paginated() {
+    // Arrange
+    let app = spawn_app(&build_mock_gpio()).await;
+
+    // Inserts two completed events
+    let sched = insert_finished_schedule(app.repo).await;
+
+    let mut conn = app.repo.pool().await.unwrap().get().unwrap();
+    insert_irrigation_event(
+        &mut conn,
+        1,
+        sched.clone(),
+        IrrigationEventStatus::Cancelled,
+    );
+    drop(conn);
+
+    // Act
+    let response = app.post_login(&user_params()).await;
+    let body: Value = response.json().await.unwrap();
+    let token = body["token"].as_str().unwrap();
+
+    let first_response = app
+        .get_irrigation_events_filtered(token.to_string(), "limit=2&order=desc")
+        .await;
+    let cursor = first_response
+        .headers()
+        .get(NEXT_CURSOR_HEADER)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+    let first_page: Vec<IrrigationEvent> = first_response.json().await.unwrap();
+
+    let second_response = app
+        .get_irrigation_events_filtered(
+            token.to_string(),
+            &format!("limit=2&order=desc&cursor={}", cursor),
+        )
+        .await;
+    let last_page = second_response.headers().get(NEXT_CURSOR_HEADER).is_none();
+    let second_page: Vec<IrrigationEvent> = second_response.json().await.unwrap();
+
+    // Assert
+    assert!(first_page.len() == 2);
+    assert!(first_page[0].id > first_page[1].id);
+    assert!(cursor == first_page[1].id.to_string());
+    assert!(second_page.len() == 1);
+    assert!(second_page[0].id < first_page[1].id);
+    assert!(last_page);
+}
@@ -148,7 +148,7 @@ async fn patch_schedule_success() {
         "days_of_week": ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"]
     });
     let response = app
-        .patch_irrigation_schedule(token.to_string(), update.id, body)
+        .patch_irrigation_schedule(token.to_string(), update.id, body, "")
         .await;
     let status = response.status();
     let updated_schedule: Value = response.json().await.unwrap();
@@ -175,7 +175,7 @@ async fn patch_schedule_not_found() {
         "days_of_week": ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"]
     });
     let response = app
-        .patch_irrigation_schedule(token.to_string(), 1, body)
+        .patch_irrigation_schedule(token.to_string(), 1, body, "")
         .await;
 
     assert!(response.status() == 404);
@@ -205,7 +205,7 @@ async fn patch_schedule_invalid() {
         "days_of_week": ["Monday", "NotTuesday"]
     });
     let response = app
-        .patch_irrigation_schedule(token.to_string(), update.id, body)
+        .patch_irrigation_schedule(token.to_string(), update.id, body, "")
         .await;
 
     assert!(response.status().is_client_error());
@@ -232,7 +232,9 @@ async fn post_schedule_success() {
         "days_of_week": ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"]
     });
 
-    let schedule_response = app.post_irrigation_schedule(token.to_string(), body).await;
+    let schedule_response = app
+        .post_irrigation_schedule(token.to_string(), body, "")
+        .await;
 
     let status = schedule_response.status();
     let new_schedule = schedule_response
@@ -263,10 +265,129 @@ async fn post_schedule_invalid() {
         "days_of_week": ["Monday"]
     });
 
-    let schedule_response = app.post_irrigation_schedule(token.to_string(), body).await;
+    let schedule_response = app
+        .post_irrigation_schedule(token.to_string(), body, "")
+        .await;
     let status = schedule_response.status();
     let body: Value = schedule_response.json().await.unwrap();
     assert!(body["message"] == "Json deserialize error: input is out of range at line 1 column 93");
 
     assert!(status.is_client_error());
 }
+
+#[tokio::test]
+async fn post_schedule_overlap_warning() {
+    // Arrange
+    let app = spawn_app(&build_mock_gpio()).await;
+    insert_irrigation_schedules_fixed(app.repo, 1).await;
+
+    // Act
+    let response = app.post_login(&user_params()).await;
+    let body: Value = response.json().await.unwrap();
+
+    let token = body["token"].as_str().unwrap();
+
+    // "Schedule 1" runs hose 3 for 15 seconds from 12:34:56 on Mondays and Wednesdays
+    let body = serde_json::json!({
+        "active": true,
+        "hoses": [1],
+        "name": "Overlapping",
+        "start_time": "12:35:00",
+        "duration": 15,
+        "days_of_week": ["Monday"]
+    });
+
+    let schedule_response = app
+        .post_irrigation_schedule(token.to_string(), body, "")
+        .await;
+    let status = schedule_response.status();
+    let new_schedule: Value = schedule_response.json().await.unwrap();
+    let warnings = new_schedule["warnings"].as_array().unwrap();
+
+    // Assert
+    assert!(status.is_success());
+    assert!(new_schedule["name"] == "Overlapping");
+    assert!(warnings.len() == 2);
+    assert!(warnings[0]["kind"] == "overlap");
+    assert!(warnings[0]["schedule_name"] == "Schedule 1");
+    assert!(warnings[1]["kind"] == "queue_delay");
+    assert!(warnings[1]["delay_seconds"] == 11);
+}
+
+#[tokio::test]
+async fn post_schedule_overlap_strict() {
+    // Arrange
+    let app = spawn_app(&build_mock_gpio()).await;
+    insert_irrigation_schedules_fixed(app.repo, 1).await;
+
+    // Act
+    let response = app.post_login(&user_params()).await;
+    let body: Value = response.json().await.unwrap();
+
+    let token = body["token"].as_str().unwrap();
+
+    let body = serde_json::json!({
+        "active": true,
+        "hoses": [1],
+        "name": "Overlapping",
+        "start_time": "12:35:00",
+        "duration": 15,
+        "days_of_week": ["Wednesday"]
+    });
+
+    let schedule_response = app
+        .post_irrigation_schedule(token.to_string(), body, "strict=true")
+        .await;
+    let status = schedule_response.status();
+    let body: Value = schedule_response.json().await.unwrap();
+
+    let schedules_response = app.get_irrigation_schedules(token.to_string()).await;
+    let schedules = schedules_response
+        .json::<Vec<IrrigationSchedule>>()
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(status == 409);
+    assert!(body["conflicts"].as_array().unwrap().len() == 2);
+    assert!(schedules.len() == 1);
+}
+
+#[tokio::test]
+async fn patch_schedule_overlap_strict() {
+    // Arrange
+    let app = spawn_app(&build_mock_gpio()).await;
+    insert_irrigation_schedules_fixed(app.repo, 2).await;
+
+    // Act
+    let response = app.post_login(&user_params()).await;
+    let body: Value = response.json().await.unwrap();
+
+    let token = body["token"].as_str().unwrap();
+
+    let schedules_response = app.get_irrigation_schedules(token.to_string()).await;
+    let schedules = schedules_response
+        .json::<Vec<IrrigationSchedule>>()
+        .await
+        .unwrap();
+
+    // Move "Schedule 2" onto a Monday, where "Schedule 1" runs at the same time
+    let body = serde_json::json!({ "days_of_week": ["Monday"] });
+    let response = app
+        .patch_irrigation_schedule(token.to_string(), schedules[1].id, body, "strict=true")
+        .await;
+    let status = response.status();
+
+    // Moving it to a free day is accepted without warnings
+    let body = serde_json::json!({ "days_of_week": ["Sunday"] });
+    let free_response = app
+        .patch_irrigation_schedule(token.to_string(), schedules[1].id, body, "strict=true")
+        .await;
+    let free_status = free_response.status();
+    let updated: Value = free_response.json().await.unwrap();
+
+    // Assert
+    assert!(status == 409);
+    assert!(free_status.is_success());
+    assert!(updated["warnings"].as_array().unwrap().is_empty());
+}
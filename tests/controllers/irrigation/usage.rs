@@ -0,0 +1,44 @@
+use rpsump::test_fixtures::gpio::build_mock_gpio;
+use serde_json::Value;
+
+use crate::common::fixtures::irrigation_schedule::insert_finished_schedule;
+use crate::common::fixtures::sump_event::insert_sump_events;
+use crate::common::test_app::spawn_app;
+use crate::controllers::user_params;
+
+#[tokio::test]
+async fn usage_per_zone() {
+    // Arrange
+    let app = spawn_app(&build_mock_gpio()).await;
+
+    // Inserts completed events of 10 seconds each for hoses 3 and 4
+    let _sched = insert_finished_schedule(app.repo).await;
+    insert_sump_events(app.repo).await;
+
+    // Act
+    let response = app.post_login(&user_params()).await;
+    let body: Value = response.json().await.unwrap();
+    let token = body["token"].as_str().unwrap();
+
+    let usage_response = app
+        .get_irrigation_usage(token.to_string(), "period=month")
+        .await;
+    let status = usage_response.status();
+    let usage: Value = usage_response.json().await.unwrap();
+
+    let invalid_response = app
+        .get_irrigation_usage(token.to_string(), "period=year")
+        .await;
+
+    // Assert
+    assert!(status.is_success());
+    assert!(usage["period"] == "month");
+    assert!(usage["zones"].as_array().unwrap().len() == 4);
+    assert!(usage["zones"][0]["runtime_seconds"] == 0);
+    assert!(usage["zones"][2]["hose_id"] == 3);
+    assert!(usage["zones"][2]["runtime_seconds"] == 10);
+    // 3 litres per minute for hose 3 in .env.test
+    assert!(usage["zones"][2]["litres"] == 0.5);
+    assert!(usage["sump"]["flow_rate"] == 12.0);
+    assert!(invalid_response.status() == 400);
+}
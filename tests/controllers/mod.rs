@@ -10,13 +10,19 @@ use serde_json::{Map, Value};
 use self::auth::{NEW_EMAIL, TEST_EMAIL, TEST_PASSWORD};
 
 pub mod auth;
+pub mod hardware;
+pub mod health;
 pub mod heater;
 pub mod info;
 pub mod irrigation;
+pub mod outputs;
+pub mod pool;
 pub mod pool_pump;
+#[cfg(feature = "stub")]
+pub mod sim;
 pub mod sump_event;
 
-pub fn link_from_email_text<'a>(text: &str) -> Vec<String> {
+pub fn link_from_email_text(text: &str) -> Vec<String> {
     let finder = LinkFinder::new();
     let links: Vec<_> = finder.links(text).collect();
 
@@ -27,10 +33,10 @@ pub fn link_from_email_text<'a>(text: &str) -> Vec<String> {
         }
     }
 
-    return found_links;
+    found_links
 }
 
-pub fn param_from_email_text<'a>(text: &str, param: &str) -> Vec<String> {
+pub fn param_from_email_text(text: &str, param: &str) -> Vec<String> {
     let finder = LinkFinder::new();
     let links: Vec<_> = finder.links(text).collect();
 
@@ -47,7 +53,7 @@ pub fn param_from_email_text<'a>(text: &str, param: &str) -> Vec<String> {
         }
     }
 
-    return found_params;
+    found_params
 }
 
 async fn email_link_from_mock_server(app: &TestApp) -> String {
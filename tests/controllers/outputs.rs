@@ -0,0 +1,77 @@
+use rpsump::test_fixtures::gpio::build_mock_gpio;
+use serde_json::{json, Value};
+use tokio::time::{sleep, Duration};
+
+use crate::common::test_app::spawn_app;
+use crate::controllers::user_params;
+
+#[tokio::test]
+async fn outputs_success() {
+    // Arrange
+    let app = spawn_app(&build_mock_gpio()).await;
+    let response = app.post_login(&user_params()).await;
+    let body: Value = response.json().await.unwrap();
+    let token = body["token"].as_str().unwrap();
+
+    // Act
+    let on_response = app
+        .post_output(token.to_string(), "fountain", json!({"switch": "on"}))
+        .await;
+    let on_status = on_response.status();
+    let states = app.repo.equipment_states().await.unwrap();
+    let switched_on: Value = app
+        .get_outputs(token.to_string())
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    // The fountain has a one second max on time in .env.test
+    sleep(Duration::from_millis(1500)).await;
+    let timed_out: Value = app
+        .get_outputs(token.to_string())
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(on_status.is_success());
+    let fountain = states
+        .iter()
+        .find(|s| s.device == "output_fountain")
+        .unwrap();
+    assert!(fountain.state == "true");
+    assert!(switched_on.as_array().unwrap().len() == 2);
+    assert!(switched_on[0]["name"] == "pool_lights");
+    assert!(switched_on[0]["schedule"]["start"] == "19:00:00");
+    assert!(switched_on[1]["name"] == "fountain");
+    assert!(switched_on[1]["maxOnSec"] == 1);
+    assert!(switched_on[1]["commanded"] == true);
+    assert!(switched_on[1]["changedBy"]["kind"] == "user");
+    assert!(timed_out[1]["commanded"] == false);
+    assert!(timed_out[1]["changedBy"]["kind"] == "timer");
+}
+
+#[tokio::test]
+async fn outputs_failed() {
+    // Arrange
+    let app = spawn_app(&build_mock_gpio()).await;
+    let response = app.post_login(&user_params()).await;
+    let body: Value = response.json().await.unwrap();
+    let token = body["token"].as_str().unwrap();
+
+    // Act
+    let unknown_response = app
+        .post_output(token.to_string(), "hot_tub", json!({"switch": "on"}))
+        .await;
+    let invalid_response = app
+        .post_output(token.to_string(), "fountain", json!({"switch": "dim"}))
+        .await;
+    let no_auth_response = app.get_outputs("invalid-token".to_string()).await;
+
+    // Assert
+    assert!(unknown_response.status() == 404);
+    assert!(invalid_response.status() == 400);
+    assert!(no_auth_response.status() == 401);
+}
@@ -0,0 +1,43 @@
+use rpsump::test_fixtures::gpio::build_mock_gpio;
+use serde_json::Value;
+
+use crate::common::fixtures::equipment_event::insert_pool_pump_events;
+use crate::common::test_app::spawn_app;
+use crate::controllers::user_params;
+
+#[tokio::test]
+async fn pool_usage_per_device() {
+    // Arrange
+    let app = spawn_app(&build_mock_gpio()).await;
+    insert_pool_pump_events(app.repo).await;
+
+    // Act
+    let response = app.post_login(&user_params()).await;
+    let body: Value = response.json().await.unwrap();
+    let token = body["token"].as_str().unwrap();
+
+    let usage_response = app.get_pool_usage(token.to_string(), "period=week").await;
+    let status = usage_response.status();
+    let usage: Value = usage_response.json().await.unwrap();
+
+    let invalid_response = app.get_pool_usage(token.to_string(), "period=year").await;
+
+    // Assert
+    assert!(status.is_success());
+    assert!(usage["period"] == "week");
+    // Wattages from .env.test
+    assert!(usage["heater"]["watts"] == 5000.0);
+    assert!(usage["pool_pump"].as_array().unwrap().len() == 4);
+    assert!(usage["pool_pump"][0]["speed"] == "low");
+    assert!(usage["pool_pump"][0]["watts"] == 300.0);
+    assert!(usage["pool_pump"][0]["buckets"].as_array().unwrap().len() == 1);
+    assert!(usage["pool_pump"][0]["cost"].is_number());
+    assert!(invalid_response.status() == 400);
+}
+
+#[tokio::test]
+async fn pool_usage_failed_no_auth() {
+    let app = spawn_app(&build_mock_gpio()).await;
+    let response = app.get_pool_usage("invalid-token".to_string(), "").await;
+    assert!(response.status().is_client_error());
+}
@@ -1,4 +1,12 @@
-use rpsump::test_fixtures::gpio::build_mock_gpio;
+use rpsump::hydro::{
+    gpio::{Level, MockGpio},
+    pool_pump::PoolPumpSpeed,
+};
+use rpsump::repository::models::pool_pump_schedule::PoolPumpSchedule;
+use rpsump::test_fixtures::gpio::{
+    build_mock_gpio, mock_heater, mock_irrigation_pump, mock_outputs, mock_pool_pump,
+    mock_sump_pump,
+};
 use serde_json::{json, Value};
 
 use crate::common::test_app::spawn_app;
@@ -47,3 +55,115 @@ async fn pool_pump_failed_no_auth() {
     // Assert
     assert!(sump_event_response.status() == 401);
 }
+
+#[tokio::test]
+async fn pool_pump_schedule_crud() {
+    // Arrange
+    let app = spawn_app(&build_mock_gpio()).await;
+
+    let response = app.post_login(&user_params()).await;
+    let body: Value = response.json().await.unwrap();
+    let token = body["token"].as_str().unwrap();
+
+    // Act
+    let create_response = app
+        .post_pool_pump_schedule(
+            token.to_string(),
+            json!({
+                "active": true,
+                "name": "Morning filter",
+                "speed": "low",
+                "start_time": "08:00:00",
+                "end_time": "12:00:00",
+                "days_of_week": ["Mon", "Wed", "Fri"],
+            }),
+        )
+        .await;
+    let created: PoolPumpSchedule = create_response.json().await.unwrap();
+
+    let patch_response = app
+        .patch_pool_pump_schedule(token.to_string(), created.id, json!({"speed": "high"}))
+        .await;
+    let patched: PoolPumpSchedule = patch_response.json().await.unwrap();
+
+    let empty_window_response = app
+        .patch_pool_pump_schedule(
+            token.to_string(),
+            created.id,
+            json!({"end_time": "08:00:00"}),
+        )
+        .await;
+
+    let list_response = app.get_pool_pump_schedules(token.to_string()).await;
+    let schedules: Vec<PoolPumpSchedule> = list_response.json().await.unwrap();
+
+    let delete_response = app
+        .delete_pool_pump_schedule(token.to_string(), created.id)
+        .await;
+    let missing_response = app
+        .delete_pool_pump_schedule(token.to_string(), created.id)
+        .await;
+
+    // Assert
+    assert!(created.speed == "low");
+    assert!(created.days_of_week == "Mon,Wed,Fri");
+    assert!(patched.speed == "high");
+    assert!(patched.start_time == created.start_time);
+    assert!(empty_window_response.status() == 400);
+    assert!(schedules.len() == 1);
+    assert!(delete_response.status().is_success());
+    assert!(missing_response.status() == 404);
+}
+
+#[tokio::test]
+async fn pool_pump_resume() {
+    // Arrange
+    let app = spawn_app(&build_mock_gpio()).await;
+
+    let response = app.post_login(&user_params()).await;
+    let body: Value = response.json().await.unwrap();
+    let token = body["token"].as_str().unwrap();
+
+    // Act
+    let resume_response = app.post_pool_pump_resume(token.to_string()).await;
+    let resume_body: Value = resume_response.json().await.unwrap();
+    let no_auth_response = app.post_pool_pump_resume("123".to_string()).await;
+    let states = app.repo.equipment_states().await.unwrap();
+
+    // Assert
+    let saved_override = states.iter().find(|s| s.device == "pool_pump_override");
+    assert!(saved_override.map(|s| s.state.as_str()) == Some("false"));
+    assert_eq!(resume_body["status"].as_str(), Some("ok"));
+    assert!(no_auth_response.status() == 401);
+}
+
+#[tokio::test]
+async fn pool_pump_priming() {
+    // Arrange
+    let mut gpio = mock_heater(MockGpio::new(), false);
+    gpio = mock_pool_pump(gpio, PoolPumpSpeed::Off);
+    gpio = mock_sump_pump(gpio, false, false, false);
+    gpio = mock_irrigation_pump(gpio, true, Level::High, false, None);
+    gpio = mock_outputs(gpio);
+    let app = spawn_app(&gpio).await;
+
+    let response = app.post_login(&user_params()).await;
+    let body: Value = response.json().await.unwrap();
+    let token = body["token"].as_str().unwrap();
+
+    // Act
+    let _ = app
+        .post_pool_pump(token.to_string(), json!({"speed": "low"}))
+        .await;
+    let priming_info: Value = app.get_info(token.to_string()).await.json().await.unwrap();
+
+    let cancel_response = app.post_pool_pump_cancel_priming(token.to_string()).await;
+    let cancelled_info: Value = app.get_info(token.to_string()).await.json().await.unwrap();
+    let second_cancel_response = app.post_pool_pump_cancel_priming(token.to_string()).await;
+
+    // Assert
+    assert!(priming_info["poolPumpPriming"].as_bool() == Some(true));
+    assert!(cancel_response.status() == 200);
+    assert!(cancelled_info["poolPumpPriming"].as_bool() == Some(false));
+    assert!(second_cancel_response.status() == 409);
+}
@@ -0,0 +1,96 @@
+use rpsump::hydro::gpio::sim::SimGpio;
+use serde_json::{json, Value};
+use tokio::time::{sleep, Duration, Instant};
+
+use crate::common::test_app::{spawn_simulated_app, TestApp};
+use crate::controllers::user_params;
+
+/// The level of `pin` in a `/sim/pins` listing.
+fn pin_level(pins: &Value, pin: u64) -> Option<&str> {
+    pins.as_array()?
+        .iter()
+        .find(|status| status["pin"].as_u64() == Some(pin))?["level"]
+        .as_str()
+}
+
+/// Polls `/sim/pins` until `pin` reads `level`, returning how long that took, or `None` once
+/// `timeout` passes without it.
+async fn wait_for_level(
+    app: &TestApp,
+    token: &str,
+    pin: u64,
+    level: &str,
+    timeout: Duration,
+) -> Option<Duration> {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        let pins: Value = app
+            .get_sim_pins(token.to_string())
+            .await
+            .json()
+            .await
+            .unwrap();
+        if pin_level(&pins, pin) == Some(level) {
+            return Some(start.elapsed());
+        }
+        sleep(Duration::from_millis(50)).await;
+    }
+
+    None
+}
+
+#[tokio::test]
+async fn sim_sump_cycle() {
+    // Arrange
+    let app = spawn_simulated_app(SimGpio::new()).await;
+    let response = app.post_login(&user_params()).await;
+    let body: Value = response.json().await.unwrap();
+    let token = body["token"].as_str().unwrap();
+
+    // Act
+    // The sump high sensor (pin 5) rises as the sump fills, then debounces for two seconds
+    let _ = app
+        .post_sim_pin_level(token.to_string(), 5, json!({"level": "low"}))
+        .await;
+    let full_response = app
+        .post_sim_pin_level(token.to_string(), 5, json!({"level": "high"}))
+        .await;
+    let full_status = full_response.status();
+    let full_body: Value = full_response.json().await.unwrap();
+    let running = wait_for_level(&app, token, 26, "high", Duration::from_secs(10)).await;
+
+    // The low sensor (pin 18) empties the sump; the pump runs on for the shutoff delay
+    let _ = app
+        .post_sim_pin_level(token.to_string(), 18, json!({"level": "low"}))
+        .await;
+    let _ = app
+        .post_sim_pin_level(token.to_string(), 18, json!({"level": "high"}))
+        .await;
+    let stopped = wait_for_level(&app, token, 26, "low", Duration::from_secs(10)).await;
+
+    // Assert
+    assert!(full_status.is_success());
+    assert!(full_body == json!({"pin": 5, "mode": "input", "level": "high"}));
+    assert!(running.is_some());
+    // SUMP_SHUTOFF_DELAY in .env.test
+    assert!(stopped.is_some_and(|elapsed| elapsed >= Duration::from_secs(2)));
+}
+
+#[tokio::test]
+async fn sim_output_pin_rejected() {
+    // Arrange
+    let app = spawn_simulated_app(SimGpio::new()).await;
+    let response = app.post_login(&user_params()).await;
+    let body: Value = response.json().await.unwrap();
+    let token = body["token"].as_str().unwrap();
+
+    // Act
+    let output_response = app
+        .post_sim_pin_level(token.to_string(), 26, json!({"level": "high"}))
+        .await;
+    let no_auth_response = app.get_sim_pins("123".to_string()).await;
+
+    // Assert
+    assert!(output_response.status() == 400);
+    assert!(no_auth_response.status() == 401);
+}
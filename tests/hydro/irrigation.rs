@@ -1,6 +1,8 @@
 #[cfg(test)]
 mod tests {
-    use rpsump::repository::models::irrigation_event::IrrigationEventStatus;
+    use rpsump::repository::models::irrigation_event::{
+        IrrigationEventFilter, IrrigationEventStatus,
+    };
     use rpsump::repository::Repo;
     use rpsump::test_fixtures::gpio::build_mock_gpio;
 
@@ -20,7 +22,10 @@ mod tests {
 
         insert_test_data(app.repo).await;
 
-        let events_before = app.repo.irrigation_events().await?;
+        let events_before = app
+            .repo
+            .irrigation_events(IrrigationEventFilter::default())
+            .await?;
         let queued_events = events_before
             .iter()
             .filter(|e| e.status == IrrigationEventStatus::Queued.to_string());
@@ -42,7 +47,10 @@ mod tests {
         tokio::time::sleep(Duration::from_secs(15)).await;
 
         // Check that the schedules have been run
-        let events_after = app.repo.irrigation_events().await?;
+        let events_after = app
+            .repo
+            .irrigation_events(IrrigationEventFilter::default())
+            .await?;
         let queued_events = events_after
             .iter()
             .filter(|e| e.status == IrrigationEventStatus::Queued.to_string())
@@ -58,9 +66,16 @@ mod tests {
             .filter(|e| e.status == IrrigationEventStatus::Completed.to_string())
             .count();
 
+        // Only the events the scheduler ran have a start time
+        let started_events = events_after
+            .iter()
+            .filter(|e| e.started_at.is_some() && e.started_at <= e.end_time)
+            .count();
+
         assert!(queued_events == 0);
         assert!(in_prog_events == 0);
         assert!(completed_events == 8);
+        assert!(started_events == 3);
 
         Ok(())
     }
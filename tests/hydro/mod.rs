@@ -1 +1,3 @@
 mod irrigation;
+mod pins;
+mod sim;
@@ -0,0 +1,21 @@
+use rpsump::hydro::gpio::sim::SimGpio;
+
+use crate::common::test_app::build_app;
+
+#[tokio::test]
+async fn conflicting_pins_rejected() {
+    // Arrange
+    let gpio = SimGpio::new();
+
+    // Act
+    let result = build_app(&gpio, |settings| {
+        settings.hydro.heater.control_pin = settings.hydro.sump.pump_control_pin;
+        settings.hydro.pool_pump.low_pin = 15;
+    })
+    .await;
+
+    // Assert
+    let error = result.err().expect("The application was built").to_string();
+    assert!(error.contains("is used by HEATER_CONTROL_PIN, SUMP_CONTROL_PIN"));
+    assert!(error.contains("POOL_PUMP_LOW_PIN=15 is reserved for UART RX"));
+}
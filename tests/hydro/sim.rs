@@ -0,0 +1,32 @@
+use rpsump::hydro::gpio::{sim::SimGpio, Level};
+use serde_json::{json, Value};
+use tokio::time::{sleep, Duration};
+
+use crate::common::test_app::spawn_app;
+use crate::controllers::user_params;
+
+#[tokio::test]
+async fn simulated_gpio_end_to_end() {
+    // Arrange
+    let gpio = SimGpio::new();
+    let app = spawn_app(&gpio).await;
+    let response = app.post_login(&user_params()).await;
+    let body: Value = response.json().await.unwrap();
+    let token = body["token"].as_str().unwrap();
+
+    // Act
+    let _ = app
+        .post_output(token.to_string(), "fountain", json!({"switch": "on"}))
+        .await;
+    let fountain_level = gpio.level(27);
+
+    // The sump high sensor (pin 5) rises as the sump fills, then debounces for two seconds
+    gpio.set_input(5, Level::Low).unwrap();
+    gpio.set_input(5, Level::High).unwrap();
+    sleep(Duration::from_secs(3)).await;
+    let sump_pump_level = gpio.level(26);
+
+    // Assert
+    assert!(fountain_level == Some(Level::High));
+    assert!(sump_pump_level == Some(Level::High));
+}